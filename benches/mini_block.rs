@@ -0,0 +1,240 @@
+use commonware_cryptography::{
+    bls12381::{dkg::ops, primitives::group},
+    Ed25519, PublicKey, Scheme, Sha256,
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use little_dipper::application::{
+    attestation::{Attestation, Kind},
+    chatter::verifier::{self, Verifier},
+    mini_block::{MiniBlock, ProtoBlock},
+    supervisor::{weighted_threshold, Supervisor},
+};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use std::sync::mpsc;
+
+const VIEW: u64 = 2;
+
+/// Chats per mini-block
+const CHATS: usize = 16;
+
+/// Bytes per chat
+const CHAT_SIZES: [usize; 3] = [32, 256, 2048];
+
+/// Validators in the proto-block checks
+const VALIDATORS: [u64; 5] = [4, 10, 100, 400, 1000];
+
+/// Verifier threads and batch size, as in the default validator config
+const VERIFIER_THREADS: usize = 4;
+const VERIFY_BATCH_SIZE: usize = 64;
+
+fn chats(rng: &mut StdRng, size: usize) -> Vec<Vec<u8>> {
+    (0..CHATS)
+        .map(|_| {
+            let mut chat = vec![0; size];
+            rng.fill_bytes(&mut chat);
+            chat
+        })
+        .collect()
+}
+
+fn signed(rng: &mut StdRng, hasher: &mut Sha256, signer: &mut Ed25519, size: usize) -> MiniBlock {
+    let mut mini_block = MiniBlock::new(VIEW, chats(rng, size), signer.public_key().to_vec());
+    mini_block.sign(hasher, signer);
+    mini_block
+}
+
+/// Supervisor of `n` equally weighted validators, along with their signers and shares
+/// ordered by participant index
+fn setup(n: u64) -> (Supervisor, Vec<Ed25519>, Vec<group::Share>) {
+    let mut signers: Vec<Ed25519> = (0..n).map(Ed25519::from_seed).collect();
+    signers.sort_by_key(|signer| signer.public_key());
+    let participants: Vec<(PublicKey, u64)> = signers
+        .iter()
+        .map(|signer| (signer.public_key(), 1))
+        .collect();
+    let threshold = weighted_threshold(&vec![1; n as usize]).unwrap();
+    let (identity, shares) =
+        ops::generate_shares(&mut StdRng::seed_from_u64(0), None, n as u32, threshold);
+    let supervisor = Supervisor::new(identity, participants, shares[0].clone(), Vec::new());
+    (supervisor, signers, shares)
+}
+
+/// Proto-block with a signed mini-block from every validator
+fn proto_block(rng: &mut StdRng, signers: &mut [Ed25519], size: usize) -> ProtoBlock {
+    let mut hasher = Sha256::default();
+    let mini_blocks = signers
+        .iter_mut()
+        .map(|signer| signed(rng, &mut hasher, signer, size))
+        .collect();
+    ProtoBlock { mini_blocks, attestation: None, state: None }
+}
+
+/// Proto-block with a mini-block from every validator signed with its BLS share
+fn bls_proto_block(rng: &mut StdRng, signers: &[Ed25519], shares: &[group::Share]) -> ProtoBlock {
+    let mut hasher = Sha256::default();
+    let mini_blocks = signers
+        .iter()
+        .zip(shares.iter())
+        .map(|(signer, share)| {
+            let mut mini_block = MiniBlock::new(VIEW, chats(rng, CHAT_SIZES[0]), signer.public_key().to_vec());
+            mini_block.sign_bls(&mut hasher, share);
+            mini_block
+        })
+        .collect();
+    ProtoBlock { mini_blocks, attestation: None, state: None }
+}
+
+/// Verifier as a validator runs it, remembering `cache_size` valid mini-blocks
+fn verifier(kind: Kind, cache_size: usize) -> Verifier<Sha256> {
+    let config = verifier::Config {
+        threads: VERIFIER_THREADS,
+        batch_size: VERIFY_BATCH_SIZE,
+        cache_size,
+    };
+    Verifier::new(Sha256::default(), kind, config)
+}
+
+/// Run the verifier on the mini-blocks and wait for its answer
+fn verify(verifier: &Verifier<Sha256>, mini_blocks: &[MiniBlock], supervisor: &Supervisor) -> Vec<bool> {
+    let (sender, receiver) = mpsc::channel();
+    verifier.verify(mini_blocks.to_vec(), supervisor.clone(), move |_, valid| {
+        let _ = sender.send(valid);
+    });
+    receiver.recv().unwrap()
+}
+
+fn bench_sign(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mini_block");
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut hasher = Sha256::default();
+    let mut signer = Ed25519::from_seed(0);
+    for size in CHAT_SIZES {
+        group.throughput(Throughput::Bytes((CHATS * size) as u64));
+        let mini_block = signed(&mut rng, &mut hasher, &mut signer, size);
+        group.bench_with_input(BenchmarkId::new("sign", size), &mini_block, |b, mini_block| {
+            b.iter(|| {
+                let mut mini_block = mini_block.clone();
+                mini_block.sign(&mut hasher, &mut signer);
+                black_box(mini_block)
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("verify", size), &mini_block, |b, mini_block| {
+            b.iter(|| assert!(mini_block.verify(&mut hasher)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_collect(c: &mut Criterion) {
+    let mut group = c.benchmark_group("check_sufficient_proto_block");
+    group.sample_size(10);
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut hasher = Sha256::default();
+    for n in VALIDATORS {
+        let (supervisor, mut signers, _) = setup(n);
+        for size in CHAT_SIZES {
+            let proto_block = proto_block(&mut rng, &mut signers, size);
+            group.throughput(Throughput::Elements(n));
+            group.bench_with_input(
+                BenchmarkId::new(format!("n={}", n), size),
+                &proto_block,
+                |b, proto_block| {
+                    b.iter(|| {
+                        let collected = proto_block.collect(&mut hasher, VIEW, &supervisor);
+                        assert_eq!(collected.weight, n);
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+/// The verifier as the chatter runs it on the mini-blocks of a proposal: those never
+/// seen before (`cold`), or all already verified when relayed (`cached`)
+fn bench_verifier(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verifier");
+    group.sample_size(10);
+    let mut rng = StdRng::seed_from_u64(0);
+    for n in VALIDATORS {
+        let (supervisor, mut signers, shares) = setup(n);
+        group.throughput(Throughput::Elements(n));
+        for size in CHAT_SIZES {
+            let proto_block = proto_block(&mut rng, &mut signers, size);
+            let id = format!("n={}/{}", n, size);
+            let cold = verifier(Kind::Ed25519, 0);
+            group.bench_with_input(BenchmarkId::new("ed25519/cold", &id), &proto_block, |b, proto_block| {
+                b.iter(|| assert!(verify(&cold, &proto_block.mini_blocks, &supervisor).into_iter().all(|valid| valid)))
+            });
+            let cached = verifier(Kind::Ed25519, n as usize);
+            verify(&cached, &proto_block.mini_blocks, &supervisor);
+            group.bench_with_input(BenchmarkId::new("ed25519/cached", &id), &proto_block, |b, proto_block| {
+                b.iter(|| assert!(verify(&cached, &proto_block.mini_blocks, &supervisor).into_iter().all(|valid| valid)))
+            });
+        }
+
+        // BLS mini-blocks relayed on their own are checked one by one
+        let proto_block = bls_proto_block(&mut rng, &signers, &shares);
+        let cold = verifier(Kind::Bls, 0);
+        group.bench_with_input(BenchmarkId::new("bls/cold", n), &proto_block, |b, proto_block| {
+            b.iter(|| assert!(verify(&cold, &proto_block.mini_blocks, &supervisor).into_iter().all(|valid| valid)))
+        });
+    }
+    group.finish();
+}
+
+/// Checking the aggregate of a BLS proposal, directly and through the verifier
+fn bench_attestation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("attestation");
+    group.sample_size(10);
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut hasher = Sha256::default();
+    for n in VALIDATORS {
+        let (supervisor, signers, shares) = setup(n);
+        let mut proto_block = bls_proto_block(&mut rng, &signers, &shares);
+        let attestation = Attestation::aggregate(VIEW, &mut proto_block, &supervisor).unwrap();
+
+        // Evaluate the public shares once, as a running validator would have
+        assert!(attestation.verify(&mut hasher, VIEW, &proto_block, &supervisor));
+        group.throughput(Throughput::Elements(n));
+        group.bench_with_input(BenchmarkId::new("verify", n), &proto_block, |b, proto_block| {
+            b.iter(|| assert!(attestation.verify(&mut hasher, VIEW, proto_block, &supervisor)))
+        });
+        let verifier = verifier(Kind::Bls, 0);
+        group.bench_with_input(BenchmarkId::new("verify_attested", n), &proto_block, |b, proto_block| {
+            b.iter(|| {
+                let (sender, receiver) = mpsc::channel();
+                verifier.verify_attested(VIEW, proto_block.clone(), supervisor.clone(), move |_, valid| {
+                    let _ = sender.send(valid);
+                });
+                assert!(receiver.recv().unwrap().into_iter().all(|valid| valid))
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_encoding(c: &mut Criterion) {
+    let mut group = c.benchmark_group("proto_block");
+    group.sample_size(10);
+    let mut rng = StdRng::seed_from_u64(0);
+    for n in VALIDATORS {
+        let mut signers: Vec<Ed25519> = (0..n).map(Ed25519::from_seed).collect();
+        for size in CHAT_SIZES {
+            let proto_block = proto_block(&mut rng, &mut signers, size);
+            let encoded = serde_json::to_vec(&proto_block).unwrap();
+            group.throughput(Throughput::Bytes(encoded.len() as u64));
+            let id = format!("n={}/{}", n, size);
+            group.bench_with_input(BenchmarkId::new("encode", &id), &proto_block, |b, proto_block| {
+                b.iter(|| black_box(serde_json::to_vec(proto_block).unwrap()))
+            });
+            group.bench_with_input(BenchmarkId::new("decode", &id), &encoded, |b, encoded| {
+                b.iter(|| black_box(serde_json::from_slice::<ProtoBlock>(encoded).unwrap()))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_sign, bench_collect, bench_verifier, bench_attestation, bench_encoding);
+criterion_main!(benches);
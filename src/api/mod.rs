@@ -0,0 +1,324 @@
+//! HTTP API served by a validator.
+//!
+//! - `/metrics`: Prometheus metrics of every component.
+//! - `/status`: JSON snapshot of consensus progress and the chatter ([`Status`]).
+//! - `POST /chats`: queue the request body, a JSON [`Transaction`], as a chat for our
+//!   next mini-block, returning its hex-encoded digest ([`Submitted`]). Refused
+//!   transactions get the reason as body and a status telling clients what to do:
+//!   400 for a bad signature or a transaction the state machine refuses, 409 for
+//!   replays of executed or queued ones, 413 when too large and 429 when the mempool
+//!   (or the sender's share of it) is full, in which case they should retry later or
+//!   with another validator.
+//! - `/state/<key>`: value of a key in the state machine (e.g. the kv store) as an
+//!   [`Entry`], 404 when absent.
+//! - `/nonces/<sender>`: [`Nonce`] the next transaction of a hex-encoded public key
+//!   must carry.
+//! - `/receipts/<digest>`: inclusion [`Receipt`] of a finalized chat, 404 until it is
+//!   finalized (or once it is no longer retained).
+//! - `/finalizations/latest` and `/finalizations/<view>`: threshold [`Finalization`]
+//!   certificate of a retained view, as followed by light clients.
+//! - `/snapshot`: latest [`Snapshot`] proven by a finalized state commitment, to start
+//!   another validator from (404 before the first one, or if the state machine does not
+//!   support them).
+//! - `/stream`: websocket of finalized proto-blocks, chats and nullifications, see [`stream`].
+
+use crate::application::{
+    chatter::{ingress::Mailbox as ChatterMailbox, mempool},
+    mini_block::chat_digest,
+    receipt::Receipt,
+    status::Status,
+    sync::Snapshot,
+    transaction::{self, Transaction},
+    Mailbox,
+};
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use commonware_cryptography::{Hasher, Sha256};
+use commonware_utils::{from_hex, hex};
+use prometheus_client::{encoding::text::encode, registry::Registry};
+use serde::{Deserialize, Serialize};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+use tokio::net::TcpListener;
+use tracing::info;
+
+pub mod stream;
+
+/// Configuration for the API server.
+pub struct Config {
+    /// Address to listen on.
+    pub listen: SocketAddr,
+
+    /// Registry shared by every component of the validator.
+    pub registry: Arc<Mutex<Registry>>,
+
+    /// Mailbox of the application, queried for consensus progress.
+    pub application: Mailbox,
+
+    /// Mailbox of the chatter, queried for mini-blocks and peers.
+    pub chatter: ChatterMailbox,
+}
+
+/// Response to a submitted chat.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Submitted {
+    /// Hex-encoded digest identifying the chat in receipt queries.
+    pub digest: String,
+}
+
+/// Value of a key in the state machine.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub key: String,
+    /// Hex-encoded value.
+    pub value: String,
+    /// Latest finalized view, whose execution the value reflects.
+    pub view: u64,
+    /// Hex-encoded state root after executing `view`.
+    pub state_root: String,
+}
+
+/// Next nonce of a sender.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Nonce {
+    /// Hex-encoded public key of the sender.
+    pub sender: String,
+    pub next: u64,
+    /// Latest finalized view, whose execution the nonce reflects.
+    pub view: u64,
+}
+
+/// Finalization certificate of a view.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Finalization {
+    pub view: u64,
+    /// Certificate as produced by consensus, checked with its `Prover`.
+    pub certificate: Vec<u8>,
+}
+
+/// State shared by all handlers.
+#[derive(Clone)]
+struct Shared {
+    registry: Arc<Mutex<Registry>>,
+    application: Mailbox,
+    chatter: ChatterMailbox,
+}
+
+/// API server.
+pub struct Server {
+    listen: SocketAddr,
+    shared: Shared,
+}
+
+impl Server {
+    pub fn new(config: Config) -> Self {
+        Self {
+            listen: config.listen,
+            shared: Shared {
+                registry: config.registry,
+                application: config.application,
+                chatter: config.chatter,
+            },
+        }
+    }
+
+    /// Serve requests until the listener fails.
+    pub async fn run(self) {
+        let router = Router::new()
+            .route("/metrics", get(metrics))
+            .route("/status", get(status))
+            .route("/chats", post(submit))
+            .route("/state/:key", get(state))
+            .route("/nonces/:sender", get(nonce))
+            .route("/receipts/:digest", get(receipt))
+            .route("/finalizations/latest", get(latest_finalization))
+            .route("/finalizations/:view", get(finalization))
+            .route("/snapshot", get(snapshot))
+            .route("/stream", get(stream::stream))
+            .with_state(self.shared);
+        let listener = TcpListener::bind(self.listen)
+            .await
+            .expect("Failed to bind api listener");
+        info!(listen = %self.listen, "api server started");
+        axum::serve(listener, router).await.expect("api server failed");
+    }
+}
+
+/// Prometheus metrics of every component.
+async fn metrics(State(shared): State<Shared>) -> impl IntoResponse {
+    let mut buffer = String::new();
+    encode(&mut buffer, &shared.registry.lock().unwrap()).expect("failed to encode metrics");
+    (
+        [(
+            CONTENT_TYPE,
+            "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        )],
+        buffer,
+    )
+}
+
+/// Snapshot of consensus progress and the chatter, as rendered by the dashboard.
+async fn status(State(mut shared): State<Shared>) -> Result<Json<Status>, StatusCode> {
+    let consensus = shared
+        .application
+        .status()
+        .await
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    let chatter = shared
+        .chatter
+        .status(consensus.view)
+        .await
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    Ok(Json(Status { consensus, chatter }))
+}
+
+/// Queue a transaction for our next mini-block.
+async fn submit(
+    State(mut shared): State<Shared>,
+    chat: Bytes,
+) -> Result<Json<Submitted>, (StatusCode, String)> {
+    let transaction = Transaction::decode_verified(&chat)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    shared
+        .application
+        .validate(transaction.clone())
+        .await
+        .await
+        .map_err(|_| (StatusCode::SERVICE_UNAVAILABLE, "application stopped".to_string()))?
+        .map_err(|reason| (StatusCode::BAD_REQUEST, reason))?;
+
+    // The digest is computed over the chat exactly as it will be included
+    let chat = transaction.encode();
+    let digest = chat_digest(&mut Sha256::default(), &chat);
+    shared
+        .chatter
+        .load_chat(transaction)
+        .await
+        .await
+        .map_err(|_| (StatusCode::SERVICE_UNAVAILABLE, "chatter stopped".to_string()))?
+        .map_err(|e| (rejection_status(&e), e.to_string()))?;
+    Ok(Json(Submitted {
+        digest: hex(&digest),
+    }))
+}
+
+/// Status telling a client why its transaction was not queued.
+fn rejection_status(error: &mempool::Error) -> StatusCode {
+    match error {
+        mempool::Error::Transaction(transaction::Error::Replayed(_, _))
+        | mempool::Error::Transaction(transaction::Error::Duplicate) => StatusCode::CONFLICT,
+        mempool::Error::Transaction(_) => StatusCode::BAD_REQUEST,
+        mempool::Error::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+        mempool::Error::SenderLimit(_) | mempool::Error::Full => StatusCode::TOO_MANY_REQUESTS,
+    }
+}
+
+/// Value of a key in the state machine.
+async fn state(
+    State(mut shared): State<Shared>,
+    Path(key): Path<String>,
+) -> Result<Json<Entry>, StatusCode> {
+    let (view, state_root, value) = shared
+        .application
+        .query(key.as_bytes().to_vec())
+        .await
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    let value = value.ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(Entry {
+        key,
+        value: hex(&value),
+        view,
+        state_root: hex(&state_root),
+    }))
+}
+
+/// Nonce the next transaction of a sender must carry.
+async fn nonce(
+    State(mut shared): State<Shared>,
+    Path(sender): Path<String>,
+) -> Result<Json<Nonce>, StatusCode> {
+    let public_key = from_hex(&sender).ok_or(StatusCode::BAD_REQUEST)?;
+    let (view, next) = shared
+        .application
+        .nonce(public_key)
+        .await
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    Ok(Json(Nonce {
+        sender: sender.to_lowercase(),
+        next,
+        view,
+    }))
+}
+
+/// Inclusion receipt of a finalized chat.
+async fn receipt(
+    State(mut shared): State<Shared>,
+    Path(digest): Path<String>,
+) -> Result<Json<Receipt>, StatusCode> {
+    let digest = from_hex(&digest).ok_or(StatusCode::BAD_REQUEST)?;
+    if digest.len() != Sha256::len() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    shared
+        .application
+        .receipt(digest.into())
+        .await
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Certificate of the latest finalized view.
+async fn latest_finalization(State(shared): State<Shared>) -> Result<Json<Finalization>, StatusCode> {
+    lookup_finalization(shared, None).await
+}
+
+/// Certificate of a retained finalized view.
+async fn finalization(
+    State(shared): State<Shared>,
+    Path(view): Path<u64>,
+) -> Result<Json<Finalization>, StatusCode> {
+    lookup_finalization(shared, Some(view)).await
+}
+
+/// Latest proven snapshot of the state.
+async fn snapshot(State(mut shared): State<Shared>) -> Result<Json<Snapshot>, StatusCode> {
+    shared
+        .application
+        .snapshot()
+        .await
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn lookup_finalization(
+    mut shared: Shared,
+    view: Option<u64>,
+) -> Result<Json<Finalization>, StatusCode> {
+    let (view, certificate) = shared
+        .application
+        .finalization(view)
+        .await
+        .await
+        .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(Finalization {
+        view,
+        certificate: certificate.to_vec(),
+    }))
+}
@@ -0,0 +1,247 @@
+//! `/stream`: websocket pushing finalized proto-blocks, decoded chats and nullifications.
+//!
+//! Query parameters:
+//! - `from`: resume after this view, replaying retained events first (live only when unset).
+//! - `room`: only push chat messages posted to this room.
+//! - `sender`: only push chat messages signed by this hex-encoded public key (the name
+//!   in a message is chosen by its sender, so it is not filtered on).
+//! - `blocks`: also push full proto-blocks instead of only their roots.
+//!
+//! Updates are JSON text frames in view order, and every view ends with a `finalized`
+//! or `nullified` update. Clients reconnect with `from` set to the view of the last one
+//! they received; a `pruned` update tells them views up to its view were skipped.
+
+use super::Shared;
+use crate::application::{
+    chat::ChatMessage,
+    event::Event,
+    mini_block::{chat_digest, ProtoBlock},
+    transaction::Transaction,
+    Mailbox,
+};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    response::Response,
+};
+use commonware_cryptography::{Digest, Sha256};
+use commonware_utils::hex;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Which updates a client wants.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Filter {
+    pub from: Option<u64>,
+    pub room: Option<String>,
+    pub sender: Option<String>,
+    pub blocks: bool,
+}
+
+/// Update pushed to a client.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Update {
+    Pruned {
+        view: u64,
+    },
+    Chat {
+        view: u64,
+        /// Hex-encoded public key of the validator whose mini-block included it.
+        creator: String,
+        /// Hex-encoded public key of the user who signed the transaction.
+        signer: String,
+        message: ChatMessage,
+    },
+    Finalized {
+        view: u64,
+        /// Hex-encoded Merkle root finalized by consensus.
+        root: String,
+        /// Only sent when `blocks` is requested.
+        proto_block: Option<ProtoBlock>,
+    },
+    Nullified {
+        view: u64,
+    },
+}
+
+impl Filter {
+    /// Whether a message signed by `signer` (hex-encoded) is wanted.
+    fn matches(&self, signer: &str, message: &ChatMessage) -> bool {
+        self.room.as_ref().map_or(true, |room| *room == message.room)
+            && self.sender.as_ref().map_or(true, |sender| sender.eq_ignore_ascii_case(signer))
+    }
+
+    /// Updates for an event, chats first and the view's closing update last.
+    fn updates(&self, event: Event) -> Vec<Update> {
+        match event {
+            Event::Pruned { view } => vec![Update::Pruned { view }],
+            Event::Nullified { view } => vec![Update::Nullified { view }],
+            Event::Finalized { view, root, proto_block, executed } => {
+                // The same transaction may have been sent to several validators, only
+                // the first copy is executed
+                let mut executed: HashSet<Digest> = executed.into_iter().collect();
+                let mut hasher = Sha256::default();
+                let mut updates = Vec::new();
+                for mini_block in proto_block.mini_blocks.iter() {
+                    for chat in mini_block.chats.iter() {
+                        if !executed.remove(&chat_digest(&mut hasher, chat)) {
+                            continue;
+                        }
+                        let Some(transaction) = Transaction::decode(chat) else {
+                            continue;
+                        };
+                        let Some(message) = ChatMessage::decode(&transaction.payload) else {
+                            continue;
+                        };
+                        // Executed transactions carry a valid signature of their sender
+                        let signer = hex(&transaction.sender);
+                        if !self.matches(&signer, &message) {
+                            continue;
+                        }
+                        updates.push(Update::Chat {
+                            view,
+                            creator: hex(&mini_block.pubkey),
+                            signer,
+                            message,
+                        });
+                    }
+                }
+                updates.push(Update::Finalized {
+                    view,
+                    root: hex(&root),
+                    proto_block: self.blocks.then_some(proto_block),
+                });
+                updates
+            }
+        }
+    }
+}
+
+pub(super) async fn stream(
+    State(shared): State<Shared>,
+    Query(filter): Query<Filter>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade.on_upgrade(move |socket| forward(socket, shared.application, filter))
+}
+
+/// Push updates until the client leaves or falls behind (it then reconnects with `from`).
+async fn forward(mut socket: WebSocket, mut application: Mailbox, filter: Filter) {
+    let Ok(mut events) = application.subscribe(filter.from).await.await else {
+        return;
+    };
+    loop {
+        tokio::select! {
+            event = events.next() => {
+                let Some(event) = event else {
+                    break;
+                };
+                for update in filter.updates(event) {
+                    let update = serde_json::to_string(&update).unwrap();
+                    if socket.send(Message::Text(update)).await.is_err() {
+                        return;
+                    }
+                }
+            },
+            message = socket.recv() => {
+                match message {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                    Some(Ok(_)) => {}
+                }
+            },
+        }
+    }
+    let _ = socket.close().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::mini_block::MiniBlock;
+    use commonware_cryptography::{Ed25519, Scheme};
+
+    fn chat(signer: &mut Ed25519, nonce: u64, room: &str, name: &str) -> Vec<u8> {
+        let message = ChatMessage {
+            room: room.to_string(),
+            sender: name.to_string(),
+            text: "hi".to_string(),
+        };
+        Transaction::sign(signer, nonce, 0, message.encode()).encode()
+    }
+
+    /// Finalization of the chats of two users, executing all but `skipped`
+    fn finalized(alice: &mut Ed25519, bob: &mut Ed25519, skipped: usize) -> Event {
+        let chats = vec![
+            chat(alice, 1, "general", "alice"),
+            chat(bob, 1, "general", "alice"),
+            chat(alice, 2, "random", "alice"),
+        ];
+        let mut hasher = Sha256::default();
+        let executed = chats
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != skipped)
+            .map(|(_, chat)| chat_digest(&mut hasher, chat))
+            .collect();
+        let mini_block = MiniBlock::new(3, chats, Ed25519::from_seed(0).public_key().to_vec());
+        let proto_block = ProtoBlock { mini_blocks: vec![mini_block], attestation: None, state: None };
+        Event::Finalized {
+            view: 3,
+            root: proto_block.root(&mut hasher),
+            proto_block,
+            executed,
+        }
+    }
+
+    fn signers(updates: &[Update]) -> Vec<String> {
+        updates
+            .iter()
+            .filter_map(|update| match update {
+                Update::Chat { signer, .. } => Some(signer.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_updates() {
+        let mut alice = Ed25519::from_seed(1);
+        let mut bob = Ed25519::from_seed(2);
+        let (alice_key, bob_key) = (hex(&alice.public_key()), hex(&bob.public_key()));
+
+        // Every executed chat, then the view's closing update
+        let updates = Filter::default().updates(finalized(&mut alice, &mut bob, usize::MAX));
+        assert_eq!(signers(&updates), vec![alice_key.clone(), bob_key.clone(), alice_key.clone()]);
+        assert!(matches!(updates.last(), Some(Update::Finalized { view: 3, proto_block: None, .. })));
+
+        // Chats that were not executed (e.g. replays) are left out
+        let updates = Filter::default().updates(finalized(&mut alice, &mut bob, 0));
+        assert_eq!(signers(&updates), vec![bob_key.clone(), alice_key.clone()]);
+
+        // Rooms and signers are filtered on
+        let filter = Filter { room: Some("general".to_string()), ..Default::default() };
+        let updates = filter.updates(finalized(&mut alice, &mut bob, usize::MAX));
+        assert_eq!(signers(&updates), vec![alice_key.clone(), bob_key.clone()]);
+        let filter = Filter { blocks: true, ..Default::default() };
+        let updates = filter.updates(finalized(&mut alice, &mut bob, usize::MAX));
+        assert!(matches!(updates.last(), Some(Update::Finalized { proto_block: Some(_), .. })));
+    }
+
+    #[test]
+    fn test_sender_is_authenticated() {
+        let mut alice = Ed25519::from_seed(1);
+        let mut bob = Ed25519::from_seed(2);
+
+        // Bob claiming alice's name does not pass as alice
+        let filter = Filter { sender: Some(hex(&alice.public_key())), ..Default::default() };
+        let updates = filter.updates(finalized(&mut alice, &mut bob, usize::MAX));
+        assert_eq!(signers(&updates), vec![hex(&alice.public_key()); 2]);
+        let filter = Filter { sender: Some("alice".to_string()), ..Default::default() };
+        assert!(signers(&filter.updates(finalized(&mut alice, &mut bob, usize::MAX))).is_empty());
+    }
+}
@@ -1,4 +1,15 @@
-use crate::application::mini_block::ProtoBlock;
+use crate::application::attestation;
+use crate::application::epoch::{schedule_root, Reconfiguration};
+use crate::application::event::Event;
+use crate::application::history::{History, Recovered};
+use crate::application::metrics::{Metrics, Outcome, Peer, Reason};
+use crate::application::merkle;
+use crate::application::mini_block::{chat_digest, MiniBlock, ProtoBlock, StateCommitment};
+use crate::application::receipt::Receipt;
+use crate::application::state_machine::StateMachine;
+use crate::application::status::{self, HISTORY};
+use crate::application::sync::{self, Finalized, Snapshot};
+use crate::application::transaction::{nonce_order, Error as TransactionError, Nonces, Transaction};
 
 use super::{
     ingress::{Mailbox, Message},
@@ -8,165 +19,1166 @@ use super::{
 
 use super::chatter::ingress::Mailbox as ChatterMailbox;
 
-use commonware_consensus::threshold_simplex::Prover;
+use commonware_consensus::{
+    threshold_simplex::{Prover, View},
+    Proof, Supervisor as Su, ThresholdSupervisor as TSu,
+};
 use commonware_cryptography::{
-    bls12381::primitives::{group::Element, poly},
-    Hasher,
+    bls12381::primitives::{
+        group::{self, Element},
+        poly,
+    },
+    Digest, Hasher, PublicKey,
+};
+use commonware_macros::select;
+use commonware_p2p::{Receiver, Recipients, Sender};
+use commonware_utils::hex;
+
+use futures::{
+    channel::{mpsc, oneshot},
+    SinkExt, StreamExt,
 };
 
-use futures::{channel::mpsc, StreamExt};
+use commonware_runtime::{Blob, Clock, Storage};
+use rand::{seq::SliceRandom, Rng};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::time::SystemTime;
+use tracing::{debug, info, warn};
 
-use rand::Rng;
-use tracing::info;
+/// Live events a subscriber may fall behind by before it is dropped.
+const SUBSCRIBER_BACKLOG: usize = 1024;
 
-/// Genesis message to use during initialization.
-const GENESIS: &[u8] = b"commonware is neat";
+/// Views ahead of the latest one consensus asked us about that a leader's proto-block
+/// may be for, as it can reach us before consensus does.
+const PROPOSAL_WINDOW: View = 16;
+
+/// A proto-block relayed by the leader of the view proposing it.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Proposal {
+    view: View,
+    proto_block: ProtoBlock,
+}
 
 /// Application actor.
-pub struct Application<R: Rng, H: Hasher> {
+pub struct Application<B: Blob, E: Storage<B>, R: Rng + Clock, H: Hasher, S: StateMachine> {
     runtime: R,
-    prover: Prover<H>,
+    /// namespace of consensus messages
+    namespace: Vec<u8>,
+    /// epoch -> prover checking finalizations against its group key
+    provers: HashMap<u64, Prover<H>>,
     public: Vec<u8>,
     hasher: H,
+    state_machine: S,
+    /// state root after the latest finalized view
+    state_root: Vec<u8>,
     mailbox: mpsc::Receiver<Message>,
     chatter_mailbox: ChatterMailbox,
+    supervisor: Supervisor,
+    peer_sets: mpsc::Sender<(u64, Vec<PublicKey>)>,
+    metrics: Metrics,
+    /// view -> when we first proposed or verified it, for finalization latency
+    started: BTreeMap<u64, SystemTime>,
+    /// latest view consensus asked us about
+    view: u64,
+    /// latest finalized view
+    finalized: u64,
+    /// recently nullified views
+    nullified: VecDeque<u64>,
+    /// nullified views not yet published, as views before them may still be finalized
+    unsettled: BTreeSet<View>,
+    /// root -> (view, proto-block) proposed by us or relayed by the leader of the
+    /// view, pruned once the view is finalized
+    blocks: HashMap<Digest, (View, ProtoBlock)>,
+    /// root -> verifications waiting for the proto-block to arrive
+    waiters: HashMap<Digest, Vec<(View, oneshot::Sender<bool>)>>,
+    /// view -> (view, root) of proposals committing to the state after it, whose
+    /// verification waits for us to execute it
+    deferred: BTreeMap<View, Vec<(View, Digest, oneshot::Sender<bool>)>>,
+    /// view -> state after executing it, for recent views
+    commitments: BTreeMap<View, StateCommitment>,
+    /// view -> (finalization, parent view, proto-block if received) of finalized views
+    /// waiting for the views before them
+    pending: BTreeMap<View, (Proof, View, Option<ProtoBlock>)>,
+    /// views we saw notarized, which may be the parent of a finalized view without
+    /// being finalized themselves
+    prepared: BTreeSet<View>,
+    /// latest view up to which a peer told us every finalized proto-block
+    covered: View,
+    /// requests for missing views since the last progress
+    attempts: usize,
+    sync_batch: usize,
+    sync_attempts: usize,
+    max_message_size: usize,
+    /// snapshot to restore when starting
+    snapshot: Option<Snapshot>,
+    /// snapshot waiting for a finalized proposal to commit to its state
+    unproven: Option<Snapshot>,
+    /// latest proven snapshot, served to validators starting from it
+    proven: Option<Snapshot>,
+    /// finalized proto-blocks and snapshots on disk
+    history: History<B, E>,
+    /// what the history held when starting
+    recovered: Option<Recovered>,
+    /// view -> (finalization, proto-block) of recently finalized views
+    archive: BTreeMap<View, (Proof, ProtoBlock)>,
+    /// the archive holds every finalized view after this one
+    archive_start: View,
+    /// chat digest -> finalized view executing it
+    chats: HashMap<Digest, View>,
+    /// nonce of the last executed transaction of each sender
+    nonces: Nonces,
+    retained_blocks: usize,
+    /// receivers of finalizations and nullifications
+    subscribers: Vec<mpsc::Sender<Event>>,
 }
 
-impl<R: Rng, H: Hasher> Application<R, H> {
+impl<B: Blob, E: Storage<B>, R: Rng + Clock, H: Hasher, S: StateMachine> Application<B, E, R, H, S> {
     /// Create a new application actor.
-    pub fn new(runtime: R, config: Config<H>, chatter_mailbox: ChatterMailbox) -> (Self, Supervisor, Mailbox) {
+    pub fn new(runtime: R, config: Config<B, E, H, S>, chatter_mailbox: ChatterMailbox) -> (Self, Supervisor, Mailbox) {
         let (sender, mailbox) = mpsc::channel(config.mailbox_size);
+        let public = poly::public(&config.identity).serialize();
+        let supervisor = Supervisor::new(
+            config.identity,
+            config.participants,
+            config.share,
+            config.epoch_shares,
+        );
         (
             Self {
                 runtime,
-                prover: config.prover,
-                public: poly::public(&config.identity).serialize(),
+                namespace: config.namespace,
+                provers: HashMap::new(),
+                public,
                 hasher: config.hasher,
+                state_machine: config.state_machine,
+                state_root: Vec::new(),
                 mailbox,
                 chatter_mailbox: chatter_mailbox,
+                supervisor: supervisor.clone(),
+                peer_sets: config.peer_sets,
+                metrics: Metrics::init(&config.registry),
+                started: BTreeMap::new(),
+                view: 0,
+                finalized: 0,
+                nullified: VecDeque::new(),
+                unsettled: BTreeSet::new(),
+                blocks: HashMap::new(),
+                waiters: HashMap::new(),
+                deferred: BTreeMap::new(),
+                commitments: BTreeMap::new(),
+                pending: BTreeMap::new(),
+                prepared: BTreeSet::new(),
+                covered: 0,
+                attempts: 0,
+                sync_batch: config.sync_batch,
+                sync_attempts: config.sync_attempts,
+                max_message_size: config.max_message_size,
+                snapshot: config.snapshot,
+                unproven: None,
+                proven: None,
+                history: config.history,
+                recovered: Some(config.recovered),
+                archive: BTreeMap::new(),
+                archive_start: 0,
+                chats: HashMap::new(),
+                nonces: Nonces::default(),
+                retained_blocks: config.retained_blocks,
+                subscribers: Vec::new(),
             },
-            Supervisor::new(config.identity, config.participants, config.share),
+            supervisor,
             Mailbox::new(sender),
         )
     }
 
     /// Run the application actor.
-    pub async fn run(mut self) {
-        while let Some(message) = self.mailbox.next().await {
-            match message {
-                Message::Genesis { response } => {
-                    // Use the digest of the genesis message as the initial
-                    // payload.
-                    self.hasher.update(GENESIS);
-                    let digest = self.hasher.finalize();
-                    let _ = response.send(digest);
-                }
-                Message::Propose { index, response } => {
-                    // Generate a random message
-                    // bytes has to be power of 2, because consensus assume it has hash
-
-                    // TODO use chatter_mailbox to request data
-                    let chatter_response = self.chatter_mailbox.get_proto_block(index).await;
-                    
-                    match chatter_response.await {
-                        Ok(proto_block) => {
-                            info!("application with sufficient mini blocksx");
-                            // TODO use more efficient format
-                            let proto_block_json = serde_json::to_vec(&proto_block).unwrap();
-
-                            let mut msg: Vec<u8> = vec![0; 32];
-                            self.runtime.fill(&mut msg[1..]);
-
-                            // TODO this is super hacky. The voter.propose from consensus expect an digest.
-                            // Right now it is using Bytes, so we actually can stuff anydata into it.
-                            // But it is very inefficient since, Proposal is included in all notarization and
-                            // finalization. Need a separate channel to send the actual block
-                            let _ = response.send(proto_block_json.into());
-                        },
-                        Err(e) => info!("insuficient miniblock {:?}", e),
+    ///
+    /// `sender`/`receiver` carry proto-blocks between validators, as consensus
+    /// only agrees on their root, and `sync_sender`/`sync_receiver` carry the
+    /// finalized proto-blocks requested by validators catching up (see [`sync`]).
+    pub async fn run(
+        mut self,
+        mut sender: impl Sender,
+        mut receiver: impl Receiver,
+        mut sync_sender: impl Sender,
+        mut sync_receiver: impl Receiver,
+    ) {
+        // Resume from the given snapshot or the latest one we stored, whichever is newer
+        let recovered = self.recovered.take().unwrap();
+        let snapshot = match (self.snapshot.take(), recovered.snapshot) {
+            (Some(given), Some(stored)) if stored.view >= given.view => Some(stored),
+            (Some(given), _) => Some(given),
+            (None, stored) => stored,
+        };
+        if let Some(snapshot) = snapshot {
+            match self.restore(snapshot).await {
+                Ok(()) => {}
+                Err(sync::Error::Restore(reason)) => warn!("snapshot not restored: {}", reason),
+                Err(e) => panic!("Failed to restore snapshot: {}", e),
+            }
+        }
+
+        // Re-execute what was finalized after it, we executed all of it before
+        for (parent, block) in recovered.blocks {
+            if block.view <= self.finalized {
+                continue;
+            }
+            self.covered = self.covered.max(block.view);
+            self.pending.insert(block.view, (block.finalization.into(), parent, Some(block.proto_block)));
+        }
+        self.execute_pending().await;
+        if self.commitments.is_empty() {
+            let commitment = self.commitment(self.finalized);
+            self.commitments.insert(self.finalized, commitment);
+        }
+
+        loop {
+            select! {
+                message = self.mailbox.next() => {
+                    let Some(message) = message else {
+                        break;
+                    };
+                    match message {
+                        Message::Genesis { response } => {
+                            // Use the digest of the genesis state as the initial
+                            // payload.
+                            let genesis = self.state_machine.genesis();
+                            self.hasher.update(&genesis);
+                            let digest = self.hasher.finalize();
+                            let _ = response.send(digest);
+                        }
+                        Message::Propose { index, response } => {
+                            self.view = self.view.max(index);
+                            let start = self.runtime.current();
+                            self.started.entry(index).or_insert(start);
+                            let chatter_response = self.chatter_mailbox.get_proto_block(index).await;
+
+                            match chatter_response.await {
+                                Ok(proto_block) => {
+                                    info!("application with sufficient mini blocksx");
+                                    self.metrics.quorum_wait.observe(self.elapsed(start));
+
+                                    // Commit to the state after the latest view we executed
+                                    let mut proto_block = proto_block;
+                                    proto_block.state = self.commitments.get(&self.finalized).cloned();
+
+                                    // Peers could not receive a proto-block larger than a message
+                                    let size = serde_json::to_vec(&proto_block).unwrap().len();
+                                    if size > self.max_message_size {
+                                        warn!(view = index, size, "proto-block exceeds network.max_message_size, not proposing");
+                                        self.metrics.proposals_abandoned.inc();
+                                        continue;
+                                    }
+
+                                    // Consensus agrees on the root, the proto-block itself is
+                                    // sent to the other validators once consensus broadcasts it
+                                    let root = proto_block.root(&mut self.hasher);
+                                    self.blocks.insert(root.clone(), (index, proto_block));
+                                    let _ = response.send(root);
+                                },
+                                Err(e) => {
+                                    info!("insuficient miniblock {:?}", e);
+                                    self.metrics.proposals_abandoned.inc();
+                                },
+                            }
+                        }
+                        Message::Verify { index, payload, response } => {
+                            let view = index;
+                            self.view = self.view.max(view);
+                            let start = self.runtime.current();
+                            self.started.entry(view).or_insert(start);
+                            info!("validator sent miniblock while verify the data");
+                            let chatter_response = self.chatter_mailbox.send_mini_block(view).await;
+                            // TODO can probably remove the need to wait for sent
+                            match chatter_response.await {
+                                Ok(_) => info!("chatter response ok"),
+                                Err(e) => info!("errr {:?}", e),
+                            }
+
+                            // Wait for the leader's proto-block if it has not arrived yet
+                            if !self.blocks.contains_key(&payload) {
+                                self.waiters.entry(payload).or_default().push((view, response));
+                                continue;
+                            }
+                            self.answer(view, payload, response).await;
+                        }
+                        Message::Broadcast { payload } => {
+                            let Some((view, proto_block)) = self.blocks.get(&payload) else {
+                                warn!("no proto-block to broadcast for {}", hex(&payload));
+                                continue;
+                            };
+                            let proposal = Proposal { view: *view, proto_block: proto_block.clone() };
+                            let proto_block_json = serde_json::to_vec(&proposal).unwrap();
+                            if proto_block_json.len() > self.max_message_size {
+                                warn!(size = proto_block_json.len(), "proto-block {} exceeds network.max_message_size", hex(&payload));
+                                continue;
+                            }
+                            if let Err(e) = sender.send(Recipients::All, proto_block_json.into(), false).await {
+                                warn!("failed to broadcast proto-block {:?}", e);
+                            }
+                        }
+                        Message::Nullify { index } => {
+                            // When there is some gap in the state transition,
+                            // either because GST or a malicious leader
+                            // We let the chatter to send its mini-block to the next leader
+                            // so it is ready to propose when ready
+                            let view = index;
+                            info!("Nullfy took place received by application validator");
+                            self.view = self.view.max(view);
+                            self.nullified.push_back(view);
+                            if self.nullified.len() > HISTORY {
+                                self.nullified.pop_front();
+                            }
+                            if view > self.finalized {
+                                self.unsettled.insert(view);
+                            }
+                            // sed the current view, the +1 is performed inside the chatter
+                            let chatter_response = self.chatter_mailbox.send_mini_block(view).await;
+                            // TODO can probably remove the need to wait for sent
+                            match chatter_response.await {
+                                Ok(_) => info!("chatter response ok"),
+                                Err(e) => info!("errr {:?}", e),
+                            }
+                        }
+                        Message::Prepared { proof: _, payload } => {
+                            info!(root = hex(&payload), "prepared");
+                            if let Some((view, _)) = self.blocks.get(&payload) {
+                                self.prepared.insert(*view);
+                            }
+                        }
+                        Message::Finalized { proof, payload } => {
+                            info!(root = hex(&payload), "finalized");
+                            let Some((view, parent)) = self.deserialize_finalization(&proof) else {
+                                warn!("undecodable finalization of {}", hex(&payload));
+                                continue;
+                            };
+                            if view <= self.finalized {
+                                continue;
+                            }
+
+                            // Hold it until the views before it are executed
+                            let proto_block = self.blocks.remove(&payload).map(|(_, proto_block)| proto_block);
+                            if proto_block.is_none() {
+                                warn!(view, "finalized proto-block {} was never received, syncing", hex(&payload));
+                            }
+                            let held = self.pending.entry(view).or_insert((proof, parent, None));
+                            if held.2.is_none() {
+                                held.2 = proto_block;
+                            }
+                            self.execute_pending().await;
+                            self.request(&mut sync_sender).await;
+                        }
+                        Message::Validate { transaction, response } => {
+                            let _ = response.send(self.state_machine.validate(&transaction));
+                        }
+                        Message::Query { key, response } => {
+                            let value = self.state_machine.query(&key);
+                            let _ = response.send((self.finalized, self.state_root.clone(), value));
+                        }
+                        Message::Nonce { sender, response } => {
+                            let _ = response.send((self.finalized, self.nonces.next(&sender)));
+                        }
+                        Message::Receipt { chat, response } => {
+                            let receipt = self.chats.get(&chat).and_then(|view| {
+                                let (proof, proto_block) = self.archive.get(view)?;
+                                let (mini_block, index) = proto_block.find_chat(&mut self.hasher, &chat)?;
+                                Receipt::new(&mut self.hasher, *view, proof.clone(), proto_block, mini_block, index)
+                            });
+                            let _ = response.send(receipt);
+                        }
+                        Message::Snapshot { response } => {
+                            let _ = response.send(self.proven.clone());
+                        }
+                        Message::Finalization { view, response } => {
+                            let finalization = match view {
+                                Some(view) => self.archive.get_key_value(&view),
+                                None => self.archive.last_key_value(),
+                            };
+                            let _ = response.send(finalization.map(|(view, (proof, _))| (*view, proof.clone())));
+                        }
+                        Message::Subscribe { from, response } => {
+                            let (mut subscriber, receiver) =
+                                mpsc::channel(self.retained_blocks + HISTORY + SUBSCRIBER_BACKLOG);
+                            if let Some(from) = from {
+                                for event in self.replay(from) {
+                                    let _ = subscriber.try_send(event);
+                                }
+                            }
+                            self.subscribers.push(subscriber);
+                            let _ = response.send(receiver);
+                        }
+                        Message::Status { response } => {
+                            let leader = TSu::leader(&self.supervisor, self.view, group::Signature::one())
+                                .map(|leader| hex(&leader))
+                                .unwrap_or_default();
+                            let _ = response.send(status::Consensus {
+                                view: self.view,
+                                leader,
+                                finalized: self.finalized,
+                                state_root: hex(&self.state_root),
+                                epoch: self.supervisor.epoch(self.view).index,
+                                nullified: self.nullified.iter().cloned().collect(),
+                            });
+                        }
                     }
-                }
-                Message::Verify { index, payload, response } => {
-                    // Ensure payload is a valid digest
-                    let view = index;
-                    info!("validator sent miniblock while verify the data");
-                    let chatter_response = self.chatter_mailbox.send_mini_block(view).await;
-                    // TODO can probably remove the need to wait for sent
-                    match chatter_response.await {
-                        Ok(_) => info!("chatter response ok"),
-                        Err(e) => info!("errr {:?}", e),
+                },
+                block = receiver.recv() => {
+                    let Ok((pubkey, block)) = block else {
+                        break;
+                    };
+                    if self.supervisor.is_participant(self.view, &pubkey).is_none() {
+                        continue;
                     }
-                
-                    let proto_block: ProtoBlock = serde_json::from_slice(&payload).unwrap();
-                    
-                    // check mini_blocks comes from unique particiants and verify against their sigs
-                    let chatter_response = self.chatter_mailbox.check_sufficient_mini_blocks(view, proto_block).await;
-
-                    // TODO can probably remove the need to wait for sent
-                    let result = match chatter_response.await {
-                        Ok(r) => r,
-                        Err(e) => {
-                            info!("verify insufficient mini-blocks errr {:?}", e);
-                            false
-                        },
+                    let Ok(Proposal { view, proto_block }) = serde_json::from_slice::<Proposal>(&block) else {
+                        warn!("undecodable proto-block from {}", hex(&pubkey));
+                        continue;
                     };
-                    info!("verify sufficient mini-blocks result {:?}", result);
+                    if view <= self.finalized || view > self.view + PROPOSAL_WINDOW {
+                        continue;
+                    }
 
-                    // TODO always correct for now
-                    let _ = response.send(result);
-                }
-                Message::Nullify { index } => {
-                    // When there is some gap in the state transition,
-                    // either because GST or a malicious leader
-                    // We let the chatter to send its mini-block to the next leader
-                    // so it is ready to propose when ready
-                    let view = index;
-                    info!("Nullfy took place received by application validator");
-                    // sed the current view, the +1 is performed inside the chatter
-                    let chatter_response = self.chatter_mailbox.send_mini_block(view).await;
-                    // TODO can probably remove the need to wait for sent
-                    match chatter_response.await {
-                        Ok(_) => info!("chatter response ok"),
-                        Err(e) => info!("errr {:?}", e),
+                    // Only the leader of the view relays its proto-block, so nobody
+                    // else can fill the view with copies or other blocks
+                    if TSu::leader(&self.supervisor, view, group::Signature::one()).as_ref() != Some(&pubkey) {
+                        warn!(view, "proto-block from {}, who does not lead the view", hex(&pubkey));
+                        continue;
+                    }
+
+                    // One per view, unless consensus waits for another one the leader
+                    // equivocated with
+                    let root = proto_block.root(&mut self.hasher);
+                    if !self.waiters.contains_key(&root) && self.blocks.values().any(|(held, _)| *held == view) {
+                        continue;
+                    }
+                    self.blocks.entry(root.clone()).or_insert((view, proto_block));
+
+                    // Answer verifications that were waiting for it
+                    for (view, response) in self.waiters.remove(&root).unwrap_or_default() {
+                        self.answer(view, root.clone(), response).await;
                     }
+                },
+                message = sync_receiver.recv() => {
+                    let Ok((pubkey, message)) = message else {
+                        break;
+                    };
+                    if self.supervisor.is_participant(self.view, &pubkey).is_none() {
+                        continue;
+                    }
+                    match sync::Message::decode(&message) {
+                        Some(sync::Message::Request { from, limit }) => {
+                            let response = self.serve(from, limit);
+                            if let Err(e) = sync_sender.send(Recipients::One(pubkey), response.encode().into(), false).await {
+                                warn!("failed to serve finalized proto-blocks {:?}", e);
+                            }
+                        }
+                        Some(sync::Message::Response { from, through, blocks }) => {
+                            let finalized = self.finalized;
+                            if !self.synced(&pubkey, from, through, blocks) {
+                                continue;
+                            }
+                            self.execute_pending().await;
+
+                            // Keep going while peers fill the gap
+                            if self.finalized > finalized {
+                                self.attempts = 0;
+                                self.request(&mut sync_sender).await;
+                            }
+                        }
+                        None => warn!("undecodable sync message from {}", hex(&pubkey)),
+                    }
+                },
+            }
+        }
+    }
+
+    /// Prover checking finalizations of `view` against the group key of its epoch.
+    fn prover(&mut self, view: View) -> Prover<H> {
+        let epoch = self.supervisor.epoch(view);
+        let namespace = &self.namespace;
+        self.provers
+            .entry(epoch.index)
+            .or_insert_with(|| Prover::new(poly::public(&epoch.identity), namespace))
+            .clone()
+    }
+
+    /// Decode and check a finalization from consensus, returning its view and parent.
+    /// The view is only known once decoded, so epochs are tried latest first and the
+    /// one whose key signed it must govern the view.
+    fn deserialize_finalization(&mut self, proof: &Proof) -> Option<(View, View)> {
+        for epoch in self.supervisor.epochs() {
+            let prover = self.prover(epoch.start);
+            let Some((view, parent, _, _, _)) = prover.deserialize_finalization(proof.clone()) else {
+                continue;
+            };
+            if self.supervisor.epoch(view).index == epoch.index {
+                return Some((view, parent));
+            }
+        }
+        None
+    }
+
+    /// Restore the state and epoch schedule of a snapshot, so execution resumes after
+    /// its view.
+    async fn restore(&mut self, snapshot: Snapshot) -> Result<(), sync::Error> {
+        // The schedule is only proven by the certificate, so it cannot tell which key
+        // to check it with: resharing keeps the group key we start from
+        let prover = self.prover(snapshot.committed);
+        snapshot.verify(&prover, &mut self.hasher)?;
+        let state_root = self.state_machine
+            .restore(snapshot.view, &snapshot.state)
+            .await
+            .map_err(sync::Error::Restore)?;
+        if state_root != snapshot.state_root {
+            return Err(sync::Error::StateRootMismatch(hex(&state_root), hex(&snapshot.state_root)));
+        }
+
+        // Follow the validator sets scheduled before it
+        for (finalized, reconfiguration) in snapshot.epochs.iter() {
+            match self.supervisor.schedule(*finalized, reconfiguration) {
+                Ok(Some(epoch)) => {
+                    info!(epoch = epoch.index, start = epoch.start, "restored validator set");
+                    let _ = self.peer_sets.send((epoch.index, epoch.participants.clone())).await;
                 }
-                Message::Prepared { proof, payload } => {
-                    // TODO remove restriction for threshold consensus such that payload has to be size of 32 bytes
-                    // the notarizatio inside the consensus assume verification from the another thresh
-                    /*
-                    let (view, _, _, signature, seed) =
-                        self.prover.deserialize_notarization(proof).unwrap();
-                    let signature = signature.serialize();
-                    let seed = seed.serialize();
-                    info!(
-                        view,
-                        payload = hex(&payload),
-                        signature = hex(&signature),
-                        seed = hex(&seed),
-                        "prepared"
-                    )
-                    */
-                    info!("prepared");
+                Ok(None) => {}
+                Err(e) => return Err(sync::Error::Restore(format!("invalid reconfiguration: {}", e))),
+            }
+        }
+        for (sender, nonce) in snapshot.nonces.iter() {
+            self.nonces.update(sender.clone(), *nonce);
+        }
+
+        // The chatter only includes transactions following executed nonces
+        if !snapshot.nonces.is_empty() {
+            self.chatter_mailbox.executed(snapshot.nonces.clone()).await;
+        }
+        let commitment = snapshot.commitment(&mut self.hasher);
+        self.commitments.insert(snapshot.view, commitment);
+        self.state_root = state_root;
+        self.view = snapshot.view;
+        self.finalized = snapshot.view;
+        self.covered = snapshot.view;
+        self.archive_start = snapshot.view;
+        info!(view = snapshot.view, state_root = hex(&self.state_root), "restored snapshot");
+        self.proven = Some(snapshot);
+        Ok(())
+    }
+
+    /// Whether a finalized view whose parent is `parent` may execute next: its parent
+    /// was executed, only notarized, or known not to hide a finalized view we lack.
+    fn follows(&self, parent: View) -> bool {
+        parent <= self.finalized || parent <= self.covered || self.prepared.contains(&parent)
+    }
+
+    /// Execute held finalized views in order, up to the first gap.
+    async fn execute_pending(&mut self) {
+        while let Some((&view, (_, parent, proto_block))) = self.pending.first_key_value() {
+            if proto_block.is_none() || !self.follows(*parent) {
+                return;
+            }
+            let (proof, parent, proto_block) = self.pending.remove(&view).unwrap();
+            self.finalize(view, parent, proof, proto_block.unwrap()).await;
+        }
+    }
+
+    /// Ask a random peer for the finalized proto-blocks after our latest executed view
+    /// if a gap holds views back. Finalized views are never skipped, so every
+    /// `sync_attempts` unsuccessful requests we warn that execution is stalled and go on.
+    async fn request(&mut self, sender: &mut impl Sender) {
+        let Some((&view, _)) = self.pending.first_key_value() else {
+            self.attempts = 0;
+            return;
+        };
+        self.attempts += 1;
+        if self.attempts % self.sync_attempts == 0 {
+            warn!(
+                finalized = self.finalized,
+                view,
+                attempts = self.attempts,
+                "no peer served the missing views, execution is stalled until one does (or restart from a snapshot)"
+            );
+            self.metrics.sync_stalled.inc();
+        }
+
+        // We are a participant too, so try until the request reaches someone else
+        let mut peers = self.supervisor.participants(self.view).cloned().unwrap_or_default();
+        peers.shuffle(&mut self.runtime);
+        let request = sync::Message::Request {
+            from: self.finalized,
+            limit: self.sync_batch,
+        }
+        .encode();
+        for peer in peers {
+            match sender.send(Recipients::One(peer), request.clone().into(), false).await {
+                Ok(sent) if !sent.is_empty() => {
+                    debug!(from = self.finalized, peer = hex(&sent[0]), "requested finalized proto-blocks");
+                    return;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("failed to request finalized proto-blocks {:?}", e);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Retained finalized proto-blocks after `from`, for a peer catching up, as many as
+    /// fit in a message.
+    fn serve(&mut self, from: View, limit: usize) -> sync::Message {
+        // Without the views right after `from` we cannot tell what was finalized
+        if from < self.archive_start {
+            return sync::Message::Response { from, through: from, blocks: Vec::new() };
+        }
+        let limit = limit.clamp(1, self.sync_batch);
+        let mut blocks = Vec::new();
+        let mut size = sync::FINALIZED_OVERHEAD;
+        let mut truncated = false;
+        for (view, (proof, proto_block)) in self.archive.range(from + 1..) {
+            if blocks.len() == limit {
+                truncated = true;
+                break;
+            }
+            let block = Finalized {
+                view: *view,
+                finalization: proof.to_vec(),
+                proto_block: proto_block.clone(),
+            };
+            size += serde_json::to_vec(&block).unwrap().len() + 1;
+            if size > self.max_message_size {
+                truncated = true;
+                break;
+            }
+            blocks.push(block);
+        }
+
+        // The peer asks again after the last one we sent
+        let through = match (truncated, blocks.last()) {
+            (true, Some(last)) => last.view,
+            (true, None) => from,
+            (false, _) => self.finalized,
+        };
+        self.metrics.blocks_served.inc_by(blocks.len() as u64);
+        sync::Message::Response { from, through, blocks }
+    }
+
+    /// Hold the verified finalized proto-blocks a peer sent, returning false if any
+    /// is invalid.
+    fn synced(&mut self, peer: &PublicKey, from: View, through: View, blocks: Vec<Finalized>) -> bool {
+        let mut verified = Vec::new();
+        for block in blocks {
+            if block.view <= from || block.view > through.max(from) {
+                warn!(peer = hex(peer), view = block.view, "synced proto-block outside the range served");
+                return false;
+            }
+            let prover = self.prover(block.view);
+            match block.verify(&prover, &mut self.hasher) {
+                Ok(parent) => verified.push((block, parent)),
+                Err(e) => {
+                    warn!(peer = hex(peer), view = block.view, "invalid synced proto-block: {}", e);
+                    return false;
+                }
+            }
+        }
+        let mut synced = 0;
+        for (block, parent) in verified {
+            if block.view <= self.finalized {
+                continue;
+            }
+            if let Some((_, _, Some(_))) = self.pending.get(&block.view) {
+                continue;
+            }
+            synced += 1;
+            self.pending.insert(block.view, (block.finalization.into(), parent, Some(block.proto_block)));
+        }
+        self.metrics.blocks_synced.get_or_create(&Peer { peer: hex(peer) }).inc_by(synced);
+        self.covered = self.covered.max(through);
+        true
+    }
+
+    /// State after executing `view`, the latest view we executed.
+    fn commitment(&mut self, view: View) -> StateCommitment {
+        StateCommitment {
+            view,
+            state_root: self.state_root.clone(),
+            nonces_root: self.nonces.root(&mut self.hasher).to_vec(),
+            epochs_root: schedule_root(&mut self.hasher, &self.supervisor.reconfigurations()).to_vec(),
+        }
+    }
+
+    /// Snapshot of the state after `view`, the latest view we executed, to be proven
+    /// by a proposal committing to it. None if the state machine does not support them.
+    fn capture(&self, view: View) -> Option<Snapshot> {
+        let state = self.state_machine.snapshot()?;
+        Some(Snapshot {
+            view,
+            state_root: self.state_root.clone(),
+            state,
+            nonces: self.nonces.entries(),
+            epochs: self.supervisor.reconfigurations(),
+            committed: 0,
+            finalization: Vec::new(),
+            proof: merkle::Proof::default(),
+        })
+    }
+
+    /// Keep the state commitment of a finalized view and check it against the one its
+    /// proto-block made to an earlier view. Validators check commitments before voting,
+    /// so a mismatch means our state diverged and executing further would only spread it.
+    fn commit(&mut self, view: View, proto_block: &ProtoBlock) {
+        let commitment = self.commitment(view);
+        self.commitments.insert(view, commitment);
+        while self.commitments.len() > self.retained_blocks.max(1) {
+            self.commitments.pop_first();
+        }
+        let Some(state) = &proto_block.state else {
+            return;
+        };
+        if let Some(ours) = self.commitments.get(&state.view) {
+            if ours != state {
+                panic!(
+                    "state after view {} diverged from the one finalized at view {}: {} != {}",
+                    state.view,
+                    view,
+                    hex(&ours.state_root),
+                    hex(&state.state_root)
+                );
+            }
+        }
+    }
+
+    /// Answer the verification of a proposal once the state it commits to is known.
+    async fn answer(&mut self, view: View, root: Digest, response: oneshot::Sender<bool>) {
+        match self.committed(view, &root) {
+            Err(executing) => self.deferred.entry(executing).or_default().push((view, root, response)),
+            Ok(false) => {
+                warn!(view, "proposal {} does not commit to our state", hex(&root));
+                self.metrics.verify_outcomes.get_or_create(&Outcome { outcome: "rejected".to_string() }).inc();
+                let _ = response.send(false);
+            }
+            Ok(true) => {
+                let result = self.verify(view, &root).await;
+                let _ = response.send(result);
+            }
+        }
+    }
+
+    /// Whether the proposal for `view` with `root` commits to the state we reached
+    /// after an earlier view, or the view we must execute before telling.
+    fn committed(&self, view: View, root: &Digest) -> Result<bool, View> {
+        let Some((_, proto_block)) = self.blocks.get(root) else {
+            return Ok(false);
+        };
+        let Some(state) = &proto_block.state else {
+            return Ok(false);
+        };
+        if state.view >= view {
+            return Ok(false);
+        }
+        if state.view > self.finalized {
+            return Err(state.view);
+        }
+        Ok(self.commitments.get(&state.view) == Some(state))
+    }
+
+    /// Execute a finalized proto-block, retain it and snapshot the state when due.
+    async fn finalize(&mut self, view: View, parent: View, proof: Proof, proto_block: ProtoBlock) {
+        self.finalized = self.finalized.max(view);
+        if let Some(start) = self.started.get(&view) {
+            self.metrics.finalization_latency.observe(self.elapsed(*start));
+        }
+        self.started = self.started.split_off(&(view + 1));
+
+        // Drop proto-blocks (and verifications waiting on them) that can no longer be finalized
+        let finalized = self.finalized;
+        self.blocks.retain(|_, (view, _)| *view > finalized);
+        self.waiters.retain(|_, waiters| {
+            waiters.retain(|(view, _)| *view > finalized);
+            !waiters.is_empty()
+        });
+        self.prepared = self.prepared.split_off(&(view + 1));
+
+        self.apply_reconfigurations(view, &proto_block).await;
+        let executed = self.execute(view, &proto_block).await;
+        self.commit(view, &proto_block);
+
+        // Views before it can no longer be finalized, publish their nullifications first
+        let later = self.unsettled.split_off(&(view + 1));
+        for nullified in std::mem::replace(&mut self.unsettled, later) {
+            if nullified != view {
+                self.publish(Event::Nullified { view: nullified });
+            }
+        }
+        self.publish(Event::Finalized {
+            view,
+            root: proto_block.root(&mut self.hasher),
+            proto_block: proto_block.clone(),
+            executed: executed.clone(),
+        });
+        self.history.append(parent, &Finalized {
+            view,
+            finalization: proof.to_vec(),
+            proto_block: proto_block.clone(),
+        }).await;
+
+        // A snapshot is stored once a finalized proposal commits to its state, unless a
+        // later commitment shows none will
+        if let (Some(unproven), Some(state)) = (&self.unproven, &proto_block.state) {
+            if unproven.view == state.view {
+                let mut snapshot = self.unproven.take().unwrap();
+                snapshot.committed = view;
+                snapshot.finalization = proof.to_vec();
+                snapshot.proof = proto_block.prove_state(&mut self.hasher).unwrap();
+                self.history.snapshot(&snapshot).await;
+                self.state_machine.prune(snapshot.view).await;
+                self.proven = Some(snapshot);
+            } else if unproven.view < state.view {
+                self.unproven = None;
+            }
+        }
+        if self.unproven.is_none() && self.history.snapshot_due(view) {
+            self.unproven = self.capture(view);
+        }
+        self.archive(view, proof, proto_block, executed);
+
+        // Answer the verifications that waited for this view's state
+        let later = self.deferred.split_off(&(view + 1));
+        for (view, root, response) in std::mem::replace(&mut self.deferred, later).into_values().flatten() {
+            if view > self.finalized {
+                self.answer(view, root, response).await;
+            }
+        }
+    }
+
+    /// Check the proto-block committed to by `root` carries sufficient mini-blocks for `view`.
+    async fn verify(&mut self, view: u64, root: &Digest) -> bool {
+        let Some((block_view, proto_block)) = self.blocks.get_mut(root) else {
+            return false;
+        };
+        // the view consensus verified it at is authoritative
+        *block_view = view;
+
+        // check mini_blocks comes from unique particiants and verify against their sigs
+        let chatter_response = self.chatter_mailbox.check_sufficient_mini_blocks(view, proto_block.clone()).await;
+
+        // TODO can probably remove the need to wait for sent
+        let result = match chatter_response.await {
+            Ok(r) => r,
+            Err(e) => {
+                info!("verify insufficient mini-blocks errr {:?}", e);
+                false
+            },
+        };
+        info!("verify sufficient mini-blocks result {:?}", result);
+        let outcome = if result { "accepted" } else { "rejected" };
+        self.metrics.verify_outcomes.get_or_create(&Outcome { outcome: outcome.to_string() }).inc();
+        result
+    }
+
+    /// Send an event to every subscriber, dropping those that fell behind or left.
+    /// Events must be published in view order, as subscribers resume after the view of
+    /// the last one they received.
+    fn publish(&mut self, event: Event) {
+        self.subscribers.retain_mut(|subscriber| subscriber.try_send(event.clone()).is_ok());
+    }
+
+    /// Retained events after `from`, in view order.
+    fn replay(&mut self, from: View) -> Vec<Event> {
+        let mut events = Vec::new();
+        if let Some(oldest) = self.archive.keys().next() {
+            if from + 1 < *oldest {
+                events.push(Event::Pruned { view: oldest - 1 });
+            }
+        }
+        for (view, (_, proto_block)) in self.archive.range(from + 1..) {
+            let executed = self.chats.iter()
+                .filter(|(_, executed_at)| *executed_at == view)
+                .map(|(digest, _)| digest.clone())
+                .collect();
+            events.push(Event::Finalized {
+                view: *view,
+                root: proto_block.root(&mut self.hasher),
+                proto_block: proto_block.clone(),
+                executed,
+            });
+        }
+        for view in self.nullified.iter().filter(|view| **view > from && !self.unsettled.contains(view)) {
+            events.push(Event::Nullified { view: *view });
+        }
+        events.sort_by_key(|event| event.view());
+        events
+    }
+
+    /// Execute the transactions of a finalized proto-block, each sender's in nonce
+    /// order, skipping forged ones, replays (including copies of a transaction in
+    /// several mini-blocks) and gaps, and return the digests of those executed.
+    async fn execute(&mut self, view: u64, proto_block: &ProtoBlock) -> Vec<Digest> {
+        // Other chats (e.g. reconfigurations) are not transactions
+        let mut signed = Vec::new();
+        let mut chats = Vec::new();
+        for mini_block in proto_block.mini_blocks.iter() {
+            for chat in mini_block.chats.iter() {
+                let Some(transaction) = Transaction::decode(chat) else {
+                    continue;
+                };
+                if !transaction.verify() {
+                    self.reject(&transaction, TransactionError::InvalidSignature);
+                    continue;
                 }
-                Message::Finalized { proof, payload } => {
-                    // TODO remove unnecessary parts for threshold consensus
-
-                    /*
-                    let (view, _, _, signature, seed) =
-                        self.prover.deserialize_finalization(proof.clone()).unwrap();
-                    let signature = signature.serialize();
-                    let seed = seed.serialize();
-                    info!(
-                        view,
-                        payload = hex(&payload),
-                        signature = hex(&signature),
-                        seed = hex(&seed),
-                        "finalized"
-                    );
-                     */
-                    info!("finalized");
+                signed.push(transaction);
+                chats.push(chat);
+            }
+        }
+
+        let mut executed = Vec::new();
+        let mut transactions = Vec::new();
+        let mut nonces = Vec::new();
+        for index in nonce_order(&signed) {
+            let transaction = &signed[index];
+            if let Err(e) = self.nonces.execute(transaction) {
+                self.reject(transaction, e);
+                continue;
+            }
+            self.metrics.transactions_executed.inc();
+            executed.push(chat_digest(&mut self.hasher, chats[index]));
+            nonces.push((transaction.sender.clone(), transaction.nonce));
+            transactions.push(transaction.clone());
+        }
+
+        // Apply them to the state machine
+        self.state_root = self.state_machine.execute(view, &transactions).await;
+        info!(view, state_root = hex(&self.state_root), transactions = transactions.len(), "executed");
+
+        // Let the chatter drop queued transactions that can no longer execute
+        if !nonces.is_empty() {
+            self.chatter_mailbox.executed(nonces).await;
+        }
+        executed
+    }
+
+    /// Count a finalized transaction that is not executed. Only validators breaking the
+    /// nonce order of a sender include gaps, so those are worth a warning.
+    fn reject(&mut self, transaction: &Transaction, error: TransactionError) {
+        let reason = match error {
+            TransactionError::InvalidSignature => "invalid_signature",
+            TransactionError::Gap(_, _) => "gap",
+            _ => "replayed",
+        };
+        match reason {
+            "gap" => warn!(sender = hex(&transaction.sender), nonce = transaction.nonce, "skipped transaction: {}", error),
+            _ => debug!(sender = hex(&transaction.sender), nonce = transaction.nonce, "skipped transaction: {}", error),
+        }
+        self.metrics.transactions_rejected.get_or_create(&Reason { reason: reason.to_string() }).inc();
+    }
+
+    /// Keep a finalized proto-block and its certificate to answer receipt queries for
+    /// the chats it executed, evicting the oldest beyond `retained_blocks`.
+    fn archive(&mut self, view: u64, proof: Proof, proto_block: ProtoBlock, executed: Vec<Digest>) {
+        for digest in executed {
+            self.chats.insert(digest, view);
+        }
+        self.archive.insert(view, (proof, proto_block));
+        while self.archive.len() > self.retained_blocks {
+            let (evicted, _) = self.archive.pop_first().unwrap();
+            self.chats.retain(|_, view| *view != evicted);
+            self.archive_start = self.archive_start.max(evicted);
+        }
+    }
+
+    /// Seconds since `start`.
+    fn elapsed(&self, start: SystemTime) -> f64 {
+        self.runtime
+            .current()
+            .duration_since(start)
+            .unwrap_or_default()
+            .as_secs_f64()
+    }
+
+    /// Schedule any validator set change that mini-blocks holding a stake quorum
+    /// of the current epoch agreed on in a finalized proto-block. Only mini-blocks for
+    /// `view` signed by their creator count, each creator once.
+    async fn apply_reconfigurations(&mut self, view: u64, proto_block: &ProtoBlock) {
+        // chat -> (reconfiguration, stake behind it), ordered so every validator
+        // schedules competing reconfigurations alike
+        let mut supporters: BTreeMap<Vec<u8>, (Reconfiguration, u64)> = BTreeMap::new();
+        let mut creators = HashSet::new();
+        for mini_block in proto_block.mini_blocks.iter() {
+            if mini_block.view != view || creators.contains(&mini_block.pubkey) {
+                continue;
+            }
+            let reconfigurations: Vec<(&Vec<u8>, Reconfiguration)> = mini_block.chats.iter()
+                .filter_map(|chat| Some((chat, Reconfiguration::decode(chat)?)))
+                .collect();
+            if reconfigurations.is_empty() {
+                continue;
+            }
+            let Some(weight) = mini_block.weight(view, &self.supervisor) else {
+                continue;
+            };
+            if !self.signed(view, mini_block) {
+                warn!(view, creator = hex(&mini_block.pubkey), "ignored reconfiguration with an invalid signature");
+                continue;
+            }
+            creators.insert(mini_block.pubkey.clone());
+
+            // A creator repeating a chat in its mini-block still counts once
+            let chats: BTreeMap<&Vec<u8>, Reconfiguration> = reconfigurations.into_iter().collect();
+            for (chat, reconfiguration) in chats {
+                let (_, support) = supporters
+                    .entry(chat.clone())
+                    .or_insert_with(|| (reconfiguration, 0));
+                *support += weight;
+            }
+        }
+
+        for (_, (reconfiguration, support)) in supporters.into_iter() {
+            if support < self.supervisor.quorum_weight(view) {
+                continue;
+            }
+            match self.supervisor.schedule(view, &reconfiguration) {
+                Ok(Some(epoch)) => {
+                    info!(epoch = epoch.index, start = epoch.start, participants = epoch.participants.len(), "scheduled validator set");
+                    let _ = self.peer_sets.send((epoch.index, epoch.participants.clone())).await;
                 }
+                Ok(None) => {}
+                Err(e) => warn!("rejected reconfiguration {:?}", e),
             }
+
+            // Whatever the outcome, voting for it again cannot change it
+            self.chatter_mailbox.settled(reconfiguration).await;
+        }
+    }
+
+    /// Whether the creator of a mini-block of the proto-block finalized at `view` signed
+    /// it. Signatures aggregated into an attestation are left empty: the attestation
+    /// covered every mini-block and was checked by the validators finalizing them.
+    fn signed(&mut self, view: u64, mini_block: &MiniBlock) -> bool {
+        if mini_block.sig.is_empty() {
+            return true;
+        }
+        let header = mini_block.header(&mut self.hasher);
+        if header.verify() {
+            return true;
+        }
+        let pubkey = PublicKey::copy_from_slice(&mini_block.pubkey);
+        let Some(index) = self.supervisor.is_participant(view, &pubkey) else {
+            return false;
+        };
+        attestation::verify(&self.supervisor.epoch(view).public_share(index), &header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::{
+        history,
+        kv::{self, Operation, Store},
+    };
+    use commonware_cryptography::{bls12381::dkg::ops, Ed25519, Scheme, Sha256};
+    use commonware_p2p::simulated;
+    use commonware_runtime::{deterministic::Executor, Runner, Spawner};
+    use prometheus_client::registry::Registry;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::sync::{Arc, Mutex};
+
+    fn registry() -> Arc<Mutex<Registry>> {
+        Arc::new(Mutex::new(Registry::default()))
+    }
+
+    fn kv_config() -> kv::Config {
+        kv::Config {
+            partition: "kv".to_string(),
+            registry: registry(),
+            replay_concurrency: 1,
+        }
+    }
+
+    fn history_config() -> history::Config {
+        history::Config {
+            block_partition: "blocks".to_string(),
+            snapshot_partition: "snapshots".to_string(),
+            registry: registry(),
+            replay_concurrency: 1,
+            snapshot_interval: 1000,
+            retained_snapshots: 1,
+            retention_views: 1000,
         }
     }
+
+    #[test]
+    fn test_restart_without_snapshot() {
+        let (executor, runtime, _) = Executor::default();
+        executor.start(async move {
+            // A validator finalized views 1 to 3 before stopping, each proposal
+            // committing to the state after the previous view
+            let mut signer = Ed25519::from_seed(0);
+            let mut store = Store::init(runtime.clone(), kv_config()).await.unwrap();
+            let (mut history, _) = History::init(runtime.clone(), history_config()).await.unwrap();
+            let mut hasher = Sha256::default();
+            let mut nonces = Nonces::default();
+            let mut state = None;
+            let mut roots = Vec::new();
+            for view in 1..=3u64 {
+                let operation = Operation::Set { key: "key".to_string(), value: view.to_string() };
+                let transaction = Transaction::sign(&mut signer, view - 1, 0, operation.encode());
+                nonces.execute(&transaction).unwrap();
+                let state_root = store.execute(view, &[transaction.clone()]).await;
+                let mini_block = MiniBlock::new(view, vec![transaction.encode()], signer.public_key().to_vec());
+                let proto_block = ProtoBlock { mini_blocks: vec![mini_block], attestation: None, state: state.take() };
+                history.append(view - 1, &Finalized { view, finalization: vec![view as u8], proto_block }).await;
+                state = Some(StateCommitment {
+                    view,
+                    state_root: state_root.clone(),
+                    nonces_root: nonces.root(&mut hasher).to_vec(),
+                    epochs_root: schedule_root(&mut hasher, &[]).to_vec(),
+                });
+                roots.push(state_root);
+            }
+            drop(store);
+            drop(history);
+
+            // Restarting re-executes them through the application, which checks each
+            // commitment against the state it reached again
+            let store = Store::init(runtime.clone(), kv_config()).await.unwrap();
+            let (history, recovered) = History::init(runtime.clone(), history_config()).await.unwrap();
+            let (identity, shares) = ops::generate_shares(&mut StdRng::seed_from_u64(0), None, 1, 1);
+            let (peer_sets, _peer_sets) = mpsc::channel(16);
+            let (chatter_sender, _chatter_receiver) = mpsc::channel(1024);
+            let (application, _, mut mailbox) = Application::new(
+                runtime.clone(),
+                Config {
+                    hasher: Sha256::default(),
+                    state_machine: store,
+                    namespace: b"namespace".to_vec(),
+                    identity,
+                    participants: vec![(signer.public_key(), 1)],
+                    share: shares[0].clone(),
+                    epoch_shares: Vec::new(),
+                    peer_sets,
+                    mailbox_size: 1024,
+                    retained_blocks: 16,
+                    snapshot: None,
+                    history,
+                    recovered,
+                    sync_batch: 16,
+                    sync_attempts: 4,
+                    max_message_size: 1024 * 1024,
+                    registry: registry(),
+                },
+                ChatterMailbox::new(chatter_sender),
+            );
+            let (network, mut oracle) = simulated::Network::new(
+                runtime.clone(),
+                simulated::Config { registry: registry(), max_size: 1024 * 1024 },
+            );
+            runtime.spawn("network", network.run());
+            let (sender, receiver) = oracle.register(signer.public_key(), 0).await.unwrap();
+            let (sync_sender, sync_receiver) = oracle.register(signer.public_key(), 1).await.unwrap();
+            runtime.spawn("application", application.run(sender, receiver, sync_sender, sync_receiver));
+
+            let (finalized, state_root, value) = mailbox.query(b"key".to_vec()).await.await.unwrap();
+            assert_eq!(finalized, 3);
+            assert_eq!(state_root, roots[2]);
+            assert_eq!(value, Some(b"3".to_vec()));
+        });
+    }
 }
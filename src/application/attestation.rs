@@ -0,0 +1,319 @@
+//! BLS attestations of mini-blocks, an alternative to an Ed25519 signature on each.
+//!
+//! With [`Kind::Bls`], creators sign their mini-block headers with their share of the
+//! group key instead of their Ed25519 key. The leader checks each signature when the
+//! mini-block arrives, then replaces them in its proposal by their sum and a bitmap of
+//! the participants who signed ([`Attestation`]). A proposal then carries one 96-byte
+//! signature and a bit per participant instead of 64 bytes per mini-block, and is checked
+//! with a single aggregate verification (a Miller loop per mini-block, one final
+//! exponentiation) rather than one verification per mini-block.
+//!
+//! Public keys of the shares are evaluated from the group polynomial dealt to the
+//! participants, so no participant can choose a key cancelling out the others'.
+//!
+//! Signatures are made and checked with commonware's BLS12-381 ops, under the same
+//! namespace as Ed25519 mini-block signatures (and so apart from consensus messages).
+//! Only the aggregate check calls into `blst` directly: every mini-block has its own
+//! signer and header, while commonware only verifies aggregates sharing one of them.
+//!
+//! The attestation is a leaf of the proto-block tree, so a copy of the proposal with
+//! another attestation has another root and cannot stand in for the leader's. Attested
+//! headers carry no signature, so receipts for their chats cannot be checked offline
+//! and are refused (see [`crate::application::receipt`]).
+
+use blst::{
+    min_pk::{PublicKey as BlsPublicKey, Signature as BlsSignature},
+    BLST_ERROR,
+};
+use commonware_consensus::Supervisor as Su;
+use commonware_cryptography::{
+    bls12381::primitives::{
+        group::{self, Element},
+        ops,
+    },
+    Digest, Hasher, PublicKey,
+};
+use commonware_utils::union_unique;
+use serde::{Deserialize, Serialize};
+
+use crate::application::merkle;
+use crate::application::mini_block::{MiniBlockHeader, ProtoBlock};
+use crate::application::supervisor::Supervisor;
+use crate::APPLICATION_P2P_NAMESPACE;
+
+/// Prefix of the attestation leaf, telling it apart from mini-block headers
+const ATTESTATION_PREFIX: &[u8] = b"_ATTESTATION";
+
+/// Signature scheme of mini-blocks named in the validator config. All validators must
+/// use the same one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// Every mini-block carries the Ed25519 signature of its creator.
+    #[default]
+    Ed25519,
+    /// Creators sign with their BLS share and proposals carry an [`Attestation`].
+    Bls,
+}
+
+/// Aggregated signatures of the mini-blocks of a proto-block.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attestation {
+    /// bit `i` is set when participant `i` of the epoch signed one of the mini-blocks
+    pub signers: Vec<u8>,
+    /// sum of the signatures of the mini-blocks
+    pub signature: Vec<u8>,
+}
+
+/// Sign a mini-block header with our share of the group key.
+pub fn sign(share: &group::Share, header: &MiniBlockHeader) -> Vec<u8> {
+    ops::sign(&share.private, Some(APPLICATION_P2P_NAMESPACE), &header.non_sig_bytes()).serialize()
+}
+
+/// Check a header is signed by the share behind `public`.
+pub fn verify(public: &group::Public, header: &MiniBlockHeader) -> bool {
+    let Some(signature) = group::Signature::deserialize(&header.sig) else {
+        return false;
+    };
+    ops::verify(public, Some(APPLICATION_P2P_NAMESPACE), &header.non_sig_bytes(), &signature).is_ok()
+}
+
+fn is_set(bitmap: &[u8], index: u32) -> bool {
+    bitmap[index as usize / 8] & (1 << (index % 8)) != 0
+}
+
+fn set(bitmap: &mut [u8], index: u32) {
+    bitmap[index as usize / 8] |= 1 << (index % 8);
+}
+
+impl Attestation {
+    /// Merkle leaf of the attestation in the proto-block tree
+    pub fn leaf<H: Hasher>(&self, hasher: &mut H) -> Digest {
+        let mut v: Vec<u8> = ATTESTATION_PREFIX.to_vec();
+        v.extend_from_slice(&(self.signers.len() as u32).to_be_bytes());
+        v.extend_from_slice(&self.signers);
+        v.extend_from_slice(&self.signature);
+        merkle::leaf(hasher, &v)
+    }
+
+    /// Replace the signatures of the mini-blocks by their aggregate, dropping those that
+    /// cannot be attested: mini-blocks of other views, from non-participants or from
+    /// creators already included. Signatures must have been verified beforehand.
+    pub fn aggregate(view: u64, proto_block: &mut ProtoBlock, supervisor: &Supervisor) -> Option<Self> {
+        let participants = supervisor.participants(view)?.len();
+        let mut signers = vec![0u8; participants.div_ceil(8)];
+        let mut signature = group::Signature::zero();
+        let mut aggregated = 0;
+        proto_block.mini_blocks.retain(|mini_block| {
+            if mini_block.view != view {
+                return false;
+            }
+            let pubkey = PublicKey::copy_from_slice(&mini_block.pubkey);
+            let Some(index) = supervisor.is_participant(view, &pubkey) else {
+                return false;
+            };
+            if is_set(&signers, index) {
+                return false;
+            }
+            let Some(mini_block_signature) = group::Signature::deserialize(&mini_block.sig) else {
+                return false;
+            };
+            set(&mut signers, index);
+            signature.add(&mini_block_signature);
+            aggregated += 1;
+            true
+        });
+        if aggregated == 0 {
+            return None;
+        }
+        for mini_block in proto_block.mini_blocks.iter_mut() {
+            mini_block.sig.clear();
+        }
+        Some(Self {
+            signers,
+            signature: signature.serialize(),
+        })
+    }
+
+    /// Check every mini-block of the proto-block is for `view` and from a distinct
+    /// participant marked in the bitmap, and the aggregate signs all their headers.
+    pub fn verify<H: Hasher>(
+        &self,
+        hasher: &mut H,
+        view: u64,
+        proto_block: &ProtoBlock,
+        supervisor: &Supervisor,
+    ) -> bool {
+        let Some(participants) = supervisor.participants(view) else {
+            return false;
+        };
+        if self.signers.len() != participants.len().div_ceil(8) {
+            return false;
+        }
+        let epoch = supervisor.epoch(view);
+        let mut signers = vec![0u8; self.signers.len()];
+        let mut publics = Vec::with_capacity(proto_block.mini_blocks.len());
+        let mut messages = Vec::with_capacity(proto_block.mini_blocks.len());
+        for mini_block in proto_block.mini_blocks.iter() {
+            if mini_block.view != view || !mini_block.sig.is_empty() {
+                return false;
+            }
+            let pubkey = PublicKey::copy_from_slice(&mini_block.pubkey);
+            let Some(index) = supervisor.is_participant(view, &pubkey) else {
+                return false;
+            };
+            if is_set(&signers, index) {
+                return false;
+            }
+            set(&mut signers, index);
+            let Ok(public) = BlsPublicKey::from_bytes(&epoch.public_share(index).serialize()) else {
+                return false;
+            };
+            publics.push(public);
+            let header = mini_block.header(hasher);
+            messages.push(union_unique(APPLICATION_P2P_NAMESPACE, &header.non_sig_bytes()));
+        }
+        if signers != self.signers || publics.is_empty() {
+            return false;
+        }
+
+        // Decoded by commonware (which checks the point), then handed to blst: both use
+        // the compressed encoding and hash namespaced messages to G2 with the same tag
+        let Some(signature) = group::Signature::deserialize(&self.signature) else {
+            return false;
+        };
+        let Ok(signature) = BlsSignature::from_bytes(&signature.serialize()) else {
+            return false;
+        };
+        let publics: Vec<&BlsPublicKey> = publics.iter().collect();
+        let messages: Vec<&[u8]> = messages.iter().map(|message| message.as_slice()).collect();
+        signature.aggregate_verify(false, &messages, group::MESSAGE, &publics, false) == BLST_ERROR::BLST_SUCCESS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::{mini_block::MiniBlock, supervisor::share_counts};
+    use commonware_cryptography::{bls12381::dkg, Ed25519, Scheme, Sha256};
+    use commonware_utils::quorum;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    /// Supervisor of validators with the given stakes, along with their Ed25519 signers
+    /// and the BLS shares at their participant index
+    fn setup(weights: &[u64]) -> (Supervisor, Vec<(Ed25519, group::Share)>) {
+        let n = weights.len() as u32;
+        let mut signers: Vec<Ed25519> = (0..n as u64).map(Ed25519::from_seed).collect();
+        signers.sort_by_key(|signer| signer.public_key());
+        let participants = signers.iter().zip(weights).map(|(signer, weight)| (signer.public_key(), *weight)).collect();
+        let total = share_counts(weights).unwrap().iter().sum();
+        let (identity, shares) =
+            dkg::ops::generate_shares(&mut StdRng::seed_from_u64(0), None, total, quorum(total).unwrap());
+        let supervisor = Supervisor::new(identity, participants, shares[0].clone(), Vec::new());
+        (supervisor, signers.into_iter().zip(shares).collect())
+    }
+
+    fn proto_block(validators: &[(Ed25519, group::Share)]) -> ProtoBlock {
+        let mut hasher = Sha256::default();
+        let mini_blocks = validators
+            .iter()
+            .map(|(signer, share)| {
+                let mut mini_block = MiniBlock::new(1, vec![b"chat".to_vec()], signer.public_key().to_vec());
+                mini_block.sign_bls(&mut hasher, share);
+                mini_block
+            })
+            .collect();
+        ProtoBlock { mini_blocks, attestation: None, state: None }
+    }
+
+    #[test]
+    fn test_aggregate_and_verify() {
+        let mut hasher = Sha256::default();
+        let (supervisor, validators) = setup(&[1; 4]);
+        let public = supervisor.epoch(1).public_share(1);
+        let mut proto_block = proto_block(&validators[1..]);
+        assert!(verify(&public, &proto_block.mini_blocks[0].header(&mut hasher)));
+
+        // A repeated creator is dropped
+        proto_block.mini_blocks.push(proto_block.mini_blocks[0].clone());
+        let attestation = Attestation::aggregate(1, &mut proto_block, &supervisor).unwrap();
+        assert_eq!(proto_block.mini_blocks.len(), 3);
+        assert_eq!(attestation.signers, vec![0b1110]);
+        assert!(proto_block.mini_blocks.iter().all(|mini_block| mini_block.sig.is_empty()));
+        assert!(attestation.verify(&mut hasher, 1, &proto_block, &supervisor));
+
+        // Tampered chats, a wrong bitmap or a missing mini-block are refused
+        let mut tampered = proto_block.clone();
+        tampered.mini_blocks[2].chats.push(b"forged".to_vec());
+        assert!(!attestation.verify(&mut hasher, 1, &tampered, &supervisor));
+        let mut wrong = attestation.clone();
+        wrong.signers = vec![0b1111];
+        assert!(!wrong.verify(&mut hasher, 1, &proto_block, &supervisor));
+        let mut missing = proto_block.clone();
+        missing.mini_blocks.pop();
+        assert!(!attestation.verify(&mut hasher, 1, &missing, &supervisor));
+    }
+
+    #[test]
+    fn test_weighted_validators() {
+        let mut hasher = Sha256::default();
+        let weights: Vec<u64> = (1..=10).collect();
+        let (supervisor, validators) = setup(&weights);
+
+        // Validators 2 and 9 stay silent, the rest hold a stake quorum
+        let present: Vec<_> = validators
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != 2 && *index != 9)
+            .map(|(_, validator)| validator.clone())
+            .collect();
+        let mut proto_block = proto_block(&present);
+        let attestation = Attestation::aggregate(1, &mut proto_block, &supervisor).unwrap();
+        assert_eq!(attestation.signers, vec![0b1111_1011, 0b01]);
+        assert!(attestation.verify(&mut hasher, 1, &proto_block, &supervisor));
+        let weight: u64 = proto_block
+            .mini_blocks
+            .iter()
+            .filter_map(|mini_block| mini_block.weight(1, &supervisor))
+            .sum();
+        assert_eq!(weight, 55 - 3 - 10);
+        assert!(weight >= supervisor.quorum_weight(1));
+
+        // Reordering mini-blocks keeps the same signers and aggregate
+        let mut reordered = proto_block.clone();
+        reordered.mini_blocks.reverse();
+        assert!(attestation.verify(&mut hasher, 1, &reordered, &supervisor));
+
+        // The aggregate does not stand for another view's mini-blocks
+        assert!(!attestation.verify(&mut hasher, 2, &proto_block, &supervisor));
+    }
+
+    #[test]
+    fn test_wrong_share_or_namespace() {
+        let mut hasher = Sha256::default();
+        let (supervisor, validators) = setup(&[3, 1, 1, 2, 5, 1, 1]);
+        let epoch = supervisor.epoch(1);
+
+        // A creator signing with another participant's share is refused, alone and aggregated
+        let (signer, _) = &validators[1];
+        let (_, share) = &validators[4];
+        let mut mini_block = MiniBlock::new(1, vec![b"chat".to_vec()], signer.public_key().to_vec());
+        mini_block.sign_bls(&mut hasher, share);
+        let header = mini_block.header(&mut hasher);
+        assert!(!verify(&epoch.public_share(1), &header));
+        assert!(verify(&epoch.public_share(4), &header));
+        let mut forged = proto_block(&validators[2..]);
+        forged.mini_blocks.push(mini_block);
+        let attestation = Attestation::aggregate(1, &mut forged, &supervisor).unwrap();
+        assert!(!attestation.verify(&mut hasher, 1, &forged, &supervisor));
+
+        // Signatures of the same header outside the mini-block namespace are refused
+        let (signer, share) = &validators[0];
+        let mut mini_block = MiniBlock::new(1, vec![b"chat".to_vec()], signer.public_key().to_vec());
+        let header = mini_block.header(&mut hasher);
+        mini_block.sig = ops::sign(&share.private, None, &header.non_sig_bytes()).serialize();
+        assert!(!verify(&epoch.public_share(0), &mini_block.header(&mut hasher)));
+        mini_block.sign_bls(&mut hasher, share);
+        assert!(verify(&epoch.public_share(0), &mini_block.header(&mut hasher)));
+    }
+}
@@ -0,0 +1,89 @@
+//! Chat messages carried in mini-blocks.
+//!
+//! Chats are opaque bytes to consensus. Clients that want their chats to show up in
+//! the api's decoded stream encode them as JSON [`ChatMessage`]s, signed as the payload
+//! of a [`Transaction`](crate::application::transaction::Transaction); anything else
+//! (e.g. reconfigurations) is still finalized but only visible in raw proto-blocks.
+
+use commonware_cryptography::{Digest, Hasher, Sha256};
+use serde::{Deserialize, Serialize};
+
+use crate::application::state_machine::StateMachine;
+use crate::application::transaction::Transaction;
+
+/// Genesis message to use during initialization.
+const GENESIS: &[u8] = b"commonware is neat";
+
+/// A message posted to a room.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub room: String,
+    /// Name the sender chose, not authenticated.
+    pub sender: String,
+    pub text: String,
+}
+
+impl ChatMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap()
+    }
+
+    /// Decode a chat, none if it is not a chat message.
+    pub fn decode(chat: &[u8]) -> Option<Self> {
+        serde_json::from_slice(chat).ok()
+    }
+}
+
+/// The chat application. Messages are not interpreted beyond checking they decode,
+/// and the state root chains every executed message, so validators agree on it only
+/// if they executed the same messages in the same order.
+pub struct Chat {
+    hasher: Sha256,
+    root: Digest,
+}
+
+impl Default for Chat {
+    fn default() -> Self {
+        let mut hasher = Sha256::default();
+        hasher.update(GENESIS);
+        let root = hasher.finalize();
+        Self { hasher, root }
+    }
+}
+
+impl StateMachine for Chat {
+    fn genesis(&mut self) -> Vec<u8> {
+        GENESIS.to_vec()
+    }
+
+    fn validate(&self, transaction: &Transaction) -> Result<(), String> {
+        ChatMessage::decode(&transaction.payload)
+            .map(|_| ())
+            .ok_or_else(|| "payload is not a chat message".to_string())
+    }
+
+    async fn execute(&mut self, _view: u64, transactions: &[Transaction]) -> Vec<u8> {
+        for transaction in transactions {
+            if self.validate(transaction).is_err() {
+                continue;
+            }
+            self.hasher.update(&self.root);
+            self.hasher.update(&transaction.non_sig_bytes());
+            self.root = self.hasher.finalize();
+        }
+        self.root.to_vec()
+    }
+
+    fn snapshot(&self) -> Option<Vec<u8>> {
+        // The chain of executed messages is all there is
+        Some(self.root.to_vec())
+    }
+
+    async fn restore(&mut self, _view: u64, state: &[u8]) -> Result<Vec<u8>, String> {
+        if state.len() != self.root.len() {
+            return Err(format!("chat state must be a {} byte root", self.root.len()));
+        }
+        self.root = Digest::copy_from_slice(state);
+        Ok(self.root.to_vec())
+    }
+}
@@ -1,36 +1,100 @@
-use commonware_consensus::Supervisor;
-use commonware_cryptography::{Ed25519, Scheme};
-use commonware_utils::quorum;
-use futures::{channel::mpsc, StreamExt};
+use commonware_consensus::{Supervisor as Su, ThresholdSupervisor as TSu};
+use commonware_cryptography::{bls12381::primitives::group::{self, Element}, Ed25519, Hasher, PublicKey, Scheme};
+use commonware_runtime::{Blob, Storage};
+use commonware_storage::journal::Journal;
+use commonware_macros::select;
+use futures::{channel::{mpsc, oneshot}, pin_mut, StreamExt};
 
-use tracing::info;
-use std::collections::{BTreeMap, HashSet, VecDeque};
+use tracing::{error, info};
+use std::collections::{BTreeMap, VecDeque};
 use bytes::Bytes;
 
 use crate::application::{p2p::ingress::Mailbox as P2PMailbox, supervisor::Supervisor as SupervisorImpl};
+use crate::application::attestation::{Attestation, Kind};
 use crate::application::mini_block::{MiniBlock, ProtoBlock};
-use crate::application::chatter::ingress::{Message, Mailbox};
-
+use crate::application::chatter::{ingress::{Message, Mailbox}, mempool::Mempool, metrics::Metrics, verifier::Verifier, votes::Votes, Config};
+use crate::application::metrics::{Peer, Reason};
+use crate::application::status::{self, HISTORY};
+use commonware_utils::hex;
 
+/// Mini-block traffic with a peer
+#[derive(Default)]
+struct Activity {
+    last_received: Option<u64>,
+    last_delivered: Option<u64>,
+}
 
+/// Mini-blocks whose signatures the verifier checked
+enum Verified {
+    /// received from a peer
+    MiniBlock {
+        pubkey: PublicKey,
+        mini_block: MiniBlock,
+        valid: bool,
+    },
+    /// proposed for `view`, to check for sufficiency
+    ProtoBlock {
+        view: u64,
+        proto_block: ProtoBlock,
+        valid: Vec<bool>,
+        response: oneshot::Sender<bool>,
+    },
+}
 
-pub struct Actor {
+pub struct Actor<B: Blob, E: Storage<B>, H: Hasher> {
+    hasher: H,
     /// for receiving message from other actors who have its mailbox
     control: mpsc::Receiver<Message>,
-    /// view -> pubkey -> mini-block
+    /// how mini-blocks are signed
+    attestation: Kind,
+    /// checks mini-block signatures off this task
+    verifier: Verifier<H>,
+    verified: mpsc::UnboundedSender<Verified>,
+    verified_receiver: mpsc::UnboundedReceiver<Verified>,
+    /// view -> pubkey -> verified mini-block
     mini_blocks_cache: BTreeMap<u64, BTreeMap<Bytes, MiniBlock>>,  
     /// used to create local mini-block for some view
-    chat_queue: VecDeque<Bytes>,
+    mempool: Mempool,
+    /// our signed mini-blocks, one section per view, written before they are used
+    journal: Journal<B, E>,
+    replay_concurrency: usize,
+    retention_views: u64,
+    /// view -> our signed mini-block
+    local_mini_blocks: BTreeMap<u64, MiniBlock>,
+    /// reconfigurations we vote for in each of our mini-blocks
+    votes: Votes,
+    /// latest view we were asked to build a mini-block for
+    view: u64,
+    metrics: Metrics,
+    /// pubkey -> mini-block traffic, for the api
+    activity: BTreeMap<PublicKey, Activity>,
+    /// proposals rejected for insufficient mini-blocks, for the api
+    incidents: VecDeque<status::Incident>,
 }
 
-impl Actor {
-    pub fn new() -> (Self, Mailbox) {
-        let (control_sender, control_receiver) = mpsc::channel(100);
+impl<B: Blob, E: Storage<B>, H: Hasher> Actor<B, E, H> {
+    pub fn new(config: Config<B, E, H>) -> (Self, Mailbox) {
+        let (control_sender, control_receiver) = mpsc::channel(config.mailbox_size);
+        let (verified, verified_receiver) = mpsc::unbounded();
         (
             Self {
+                attestation: config.attestation,
+                verifier: Verifier::new(config.hasher.clone(), config.attestation, config.verifier),
+                verified,
+                verified_receiver,
+                hasher: config.hasher,
                 control: control_receiver,
                 mini_blocks_cache: BTreeMap::new(),
-                chat_queue: VecDeque::new(),
+                mempool: Mempool::new(config.mempool),
+                journal: config.journal,
+                replay_concurrency: config.replay_concurrency,
+                retention_views: config.retention_views,
+                local_mini_blocks: BTreeMap::new(),
+                votes: Votes::default(),
+                view: 0,
+                metrics: Metrics::init(&config.registry),
+                activity: BTreeMap::new(),
+                incidents: VecDeque::new(),
             },
             Mailbox::new(control_sender),
         )
@@ -42,146 +106,312 @@ impl Actor {
         supervisor: SupervisorImpl,
         mut crypto: Ed25519,
     ) {
+        // Recover the mini-blocks signed before a restart, so we never sign another one
+        // for the same view
+        {
+            let stream = self.journal.replay(self.replay_concurrency).await.expect("Failed to replay mini-blocks");
+            pin_mut!(stream);
+            while let Some(result) = stream.next().await {
+                let (view, _, _, item) = result.expect("Failed to read mini-block");
+                let mini_block: MiniBlock = serde_json::from_slice(&item).expect("Failed to decode mini-block");
+                self.local_mini_blocks.insert(view, mini_block);
+            }
+        }
+        info!("recovered {} signed mini-blocks", self.local_mini_blocks.len());
+
         // TODO need to periodically purge mini-blocks
-        while let Some(msg) = self.control.next().await {
-            match msg {
-                // validator sends the msg to the chatter for getting the next
-                // block containing sufficient mini-blocks
-                Message::GetProtoBlock { view, response } => {
-                    // Create a local mini-block, TODO the content should come from data received
-                    // from Message::LoadChat, that should be connected to a tcp server listening
-                    // from users
-                    let data = view.to_be_bytes();
-                    let mut local_mini_block = MiniBlock::new(view, data.into(), crypto.public_key().into());
-
-                    // sign message
-                    local_mini_block.sign(&mut crypto);
-
-                    // TODO should have taken all the mini-blocks to remove mem issue
-                    let proto_block: ProtoBlock = match self.mini_blocks_cache.get(&view) {
-                        Some(m) => {
-                            // convert to ProtoBlock
-                            let mut mini_blocks: Vec<MiniBlock> = vec![local_mini_block];
-                            for (_, mini_block) in m.into_iter() {
-                                if mini_block.verify() {
-                                    mini_blocks.push(mini_block.clone());
-                                }         
+        loop {
+            select! {
+                msg = self.control.next() => {
+                    let Some(msg) = msg else {
+                        break;
+                    };
+                    match msg {
+                        // validator sends the msg to the chatter for getting the next
+                        // block containing sufficient mini-blocks
+                        Message::GetProtoBlock { view, response } => {
+                            // Create a local mini-block from chats received by Message::LoadChat
+                            let local_mini_block = self.local_mini_block(view, &mut crypto, &supervisor).await;
+
+                            // TODO should have taken all the mini-blocks to remove mem issue
+                            let mut proto_block: ProtoBlock = match self.mini_blocks_cache.get(&view) {
+                                Some(m) => {
+                                    // convert to ProtoBlock, cached mini-blocks were verified on arrival
+                                    let mut mini_blocks: Vec<MiniBlock> = local_mini_block.into_iter().collect();
+                                    mini_blocks.extend(m.values().cloned());
+                                    ProtoBlock{
+                                        mini_blocks: mini_blocks,
+                                        attestation: None,
+                                        state: None,
+                                    }
+                                },
+                                None => {
+                                    info!("hello GetProtoBlock no cached mini block at view {:?}", view);
+                                    ProtoBlock {
+                                        mini_blocks: local_mini_block.into_iter().collect(),
+                                        attestation: None,
+                                        state: None,
+                                    }
+                                }
+                            };
+
+                            // uncomment to simulate attack // mini_blocks.mini_blocks.pop();
+
+                            // Replace the BLS signatures by their aggregate
+                            if self.attestation == Kind::Bls {
+                                proto_block.attestation = Attestation::aggregate(view, &mut proto_block, &supervisor);
                             }
-                            ProtoBlock{
-                                mini_blocks: mini_blocks,
+
+                            // TODO add a timeline to wait for peers about their mini-block for this view
+                            // if they cannot get sufficient number of them
+                            // if view is 1, it is ok, since it is starting
+                            let collected_weight: u64 = proto_block.mini_blocks.iter()
+                                .filter_map(|mini_block| mini_block.weight(view, &supervisor))
+                                .sum();
+                            let quorum_weight_at_view = supervisor.quorum_weight(view);
+                            if collected_weight >= quorum_weight_at_view || view==1 {
+                                self.metrics.proto_block_mini_blocks.observe(proto_block.mini_blocks.len() as f64);
+                                let chats: usize = proto_block.mini_blocks.iter().map(|m| m.chats.len()).sum();
+                                self.metrics.proto_block_chats.observe(chats as f64);
+                                response.send(proto_block).unwrap();
+                            } else {
+                                info!("insufficint mini block at view {:?}. not respond anything. num miniblock {}, weight {}, quorum {}", view, proto_block.mini_blocks.len(), collected_weight, quorum_weight_at_view);
+                                // uncomment to simulate leader attack //response.send(mini_blocks);
+                            }                
+                        }
+                        // Used by consensus Verify to check if sufficient mini-blocks are proposed  
+                        // TODO it is very inefficient to send the entire ProtoBlock struct. Should have a proposal struct
+                        // that derives some smaller struct for sending over data
+                        Message::CheckSufficientProtoBlock { view, proto_block, response } => {
+                            let verified = self.verified.clone();
+                            if proto_block.attestation.is_some() {
+                                self.verifier.verify_attested(view, proto_block, supervisor.clone(), move |proto_block, valid| {
+                                    let _ = verified.unbounded_send(Verified::ProtoBlock { view, proto_block, valid, response });
+                                });
+                                continue;
                             }
-                        },
-                        None => {
-                            info!("hello GetProtoBlock no cached mini block at view {:?}", view);
-                            ProtoBlock {
-                                mini_blocks: vec![local_mini_block],
+
+                            // Only attested mini-blocks may go without a signature
+                            if proto_block.mini_blocks.iter().any(|mini_block| mini_block.sig.is_empty()) {
+                                let _ = response.send(false);
+                                continue;
                             }
-                        }
-                    };
 
-                    // uncomment to simulate attack // mini_blocks.mini_blocks.pop();
-
-                    // TODO add a timeline to wait for peers about their mini-block for this view
-                    // if they cannot get sufficient number of them
-                    // if view is 1, it is ok, since it is starting
-                    let quorum_participants_at_view = quorum(supervisor.participants(view).unwrap().len() as u32).unwrap() as usize;
-                    if proto_block.mini_blocks.len() >= quorum_participants_at_view || view==1 {
-                        response.send(proto_block).unwrap();
-                    } else {
-                        info!("insufficint mini block at view {:?}. not respond anything. num miniblock {}, quorum {}", view, proto_block.mini_blocks.len(), quorum_participants_at_view);
-                        // uncomment to simulate leader attack //response.send(mini_blocks);
-                    }                
-                }
-                // Used by consensus Verify to check if sufficient mini-blocks are proposed  
-                // TODO it is very inefficient to send the entire ProtoBlock struct. Should have a proposal struct
-                // that derives some smaller struct for sending over data
-                Message::CheckSufficientProtoBlock { view, proto_block, response } => {                
-                    let mut participants = HashSet::new();                        
-
-                    for mini_block in proto_block.mini_blocks.into_iter() {                               
-                        // make sure mini block is for curreent view
-                        if mini_block.view != view {
-                            continue
+                            // Mini-blocks of other views do not count, leave them out of verification
+                            let mini_blocks = proto_block.mini_blocks.into_iter()
+                                .filter(|mini_block| mini_block.view == view)
+                                .collect();
+                            self.verifier.verify(mini_blocks, supervisor.clone(), move |mini_blocks, valid| {
+                                let proto_block = ProtoBlock { mini_blocks, attestation: None, state: None };
+                                let _ = verified.unbounded_send(Verified::ProtoBlock { view, proto_block, valid, response });
+                            });
                         }
-                        // if participant already sent a mini-block skip it
-                        if participants.contains(&mini_block.pubkey) {
-                            continue
+                        // Used by the chatter to tell api server that mini-blocks are finished
+                        Message::PutProtoBlock { view, proto_block, response } => {
+                            unimplemented!()
                         }
+                        // Used by all non-leader validator to send mini-block to the leader in the next view. It is triggered by
+                        // either Verify request from the consensus logics, or nullify signal from the consensus 
+                        // Behave like a traffic generator
+                        Message::SendMiniBlock { view, response } => {
+                            info!("chatter SendMiniBlock over P2P to leader");
 
-                        if mini_block.is_participant(view, &supervisor)  {
-                            if mini_block.verify() {                                
-                                participants.insert(mini_block.pubkey);
+                            // This mini block is for the next view, the one signed before if we
+                            // already sent it (e.g. on both Verify and nullify, or before a restart)
+                            let Some(mini_block) = self.local_mini_block(view+1, &mut crypto, &supervisor).await else {
+                                response.send(false).unwrap();
+                                continue;
+                            };
+
+                            // tell p2p server to send the mini-block for next view
+                            let p2p_response = p2p_mailbox.send_mini_block_to_leader(view, mini_block).await;
+                            self.metrics.mini_blocks_sent.inc();
+                            // TODO not having the response is probably ok
+                            let delivered = p2p_response.await.unwrap_or(false);
+                            if delivered {
+                                let leader = TSu::leader(&supervisor, view+1, group::Signature::one()).unwrap();
+                                self.activity.entry(leader).or_default().last_delivered = Some(view+1);
                             }
+                            response.send(delivered).unwrap();
                         }
-                    }
+                        // used by p2p server to receive mini blocks from peers 
+                        Message::LoadMiniBlockFromP2P {pubkey, mini_block, response } => {
+                            info!("chatter LoadMiniBlockFromP2P for view {}", mini_block.view);
+                            self.metrics.mini_blocks_received.get_or_create(&Peer { peer: hex(&pubkey) }).inc();
+
+                            let view = mini_block.view;
+                            let activity = self.activity.entry(pubkey.clone()).or_default();
+                            activity.last_received = activity.last_received.max(Some(view));
+                            let alreay_has = self.mini_blocks_cache.get(&view)
+                                .map_or(false, |m| m.contains_key(&pubkey));
 
-                    info!("num_valid_mini_block {} at view {}", participants.len(), view);
-                    let quorum_participants_at_view = quorum(supervisor.participants(view).unwrap().len() as u32).unwrap() as usize;
-                    if participants.len() >= quorum_participants_at_view || view == 1 {
-                        response.send(true).unwrap();
-                    } else {
-                        response.send(false).unwrap();
-                    }    
-                }
-                // Used by the chatter to tell api server that mini-blocks are finished
-                Message::PutProtoBlock { view, proto_block, response } => {
-                    unimplemented!()
-                }
-                // Used by all non-leader validator to send mini-block to the leader in the next view. It is triggered by
-                // either Verify request from the consensus logics, or nullify signal from the consensus 
-                // Behave like a traffic generator
-                Message::SendMiniBlock { view, response } => {
-                    info!("chatter SendMiniBlock over P2P to leader");
-                    // TODO Generate mini-blocks from local cache received from the server                    
-
-                    // tell p2p server to send the mini-block for next view
-                    let data = view.to_be_bytes();
-            
-                    // This mini block is for the next view
-                    let mut mini_block = MiniBlock::new(view+1, data.into(), crypto.public_key().into())                    ;
-
-                    // sign message
-                    mini_block.sign(&mut crypto);                
-                        
-                    let p2p_response = p2p_mailbox.send_mini_block_to_leader(view, mini_block).await;
-                    // TODO not having the response is probably ok
-                    match p2p_response.await {
-                        Ok(r) => response.send(r).unwrap(),
-                        Err(_) => response.send(false).unwrap(),
-                    }                            
-                }
-                // used by p2p server to receive mini blocks from peers 
-                Message::LoadMiniBlockFromP2P {pubkey, mini_block, response } => {
-                    info!("chatter LoadMiniBlockFromP2P for view {}", mini_block.view);
-
-                    let view = mini_block.view;
-                    let mut alreay_has = false;
-
-                    // TODO cache those mini-blocks to be used in the next proposal
-
-                    match self.mini_blocks_cache.get_mut(&view) {
-                        Some(m) => {
-                            if let Some(_) = m.get(&pubkey) {
-                                alreay_has = true;
+                            // cached for the next proposal once its signature is checked
+                            let verified = self.verified.clone();
+                            self.verifier.verify(vec![mini_block], supervisor.clone(), move |mut mini_blocks, valid| {
+                                let mini_block = mini_blocks.pop().unwrap();
+                                let _ = verified.unbounded_send(Verified::MiniBlock { pubkey, mini_block, valid: valid[0] });
+                            });
+
+                            let _ = response.send(alreay_has);
+                        }
+                        // used by server to receive chat from users
+                        Message::LoadChat { transaction, response } => {
+                            let result = self.mempool.insert(transaction, self.view);
+                            match &result {
+                                Ok(evicted) => self.metrics.mempool_evicted.inc_by(*evicted as u64),
+                                Err(e) => {
+                                    self.metrics.mempool_rejected.get_or_create(&Reason { reason: e.reason().to_string() }).inc();
+                                }
                             }
-                            // update anyway
-                            m.insert(pubkey, mini_block);
-                        },
-                        None => {
-                            self.mini_blocks_cache.insert(view, BTreeMap::new());                        
-                            self.mini_blocks_cache.get_mut(&view).unwrap().insert(pubkey, mini_block);
-                        },
-                    };
+                            let _ = response.send(result.map(|_| ()));
+                        }
+                        // used by the validator to vote for a validator set change
+                        Message::LoadReconfiguration { data, response } => {
+                            let _ = response.send(self.votes.insert(&data));
+                        }
+                        // used by the application to report a reconfiguration voted for in a
+                        // finalized proto-block was scheduled (or never will be)
+                        Message::Settled { reconfiguration } => {
+                            self.votes.settled(&reconfiguration);
+                        }
+                        // used by the application to report nonces executed in a finalized proto-block,
+                        // queued transactions they replay can no longer be executed
+                        Message::Executed { nonces } => {
+                            self.mempool.executed(nonces);
+                        }
+                        // used by the api server to report the chatter state
+                        Message::Status { view, response } => {
+                            let views = self.mini_blocks_cache.iter().rev().take(HISTORY).rev()
+                                .map(|(view, mini_blocks)| status::View {
+                                    view: *view,
+                                    mini_blocks: mini_blocks.len(),
+                                    weight: mini_blocks.keys()
+                                        .filter_map(|pubkey| supervisor.weight(*view, pubkey))
+                                        .sum(),
+                                    quorum_weight: supervisor.quorum_weight(*view),
+                                })
+                                .collect();
+                            let peers = supervisor.participants(view).unwrap().iter()
+                                .filter(|peer| **peer != crypto.public_key())
+                                .map(|peer| {
+                                    let activity = self.activity.get(peer);
+                                    status::Peer {
+                                        peer: hex(peer),
+                                        weight: supervisor.weight(view, peer).unwrap_or_default(),
+                                        last_received: activity.and_then(|a| a.last_received),
+                                        last_delivered: activity.and_then(|a| a.last_delivered),
+                                    }
+                                })
+                                .collect();
+                            let _ = response.send(status::Chatter {
+                                chat_queue: self.mempool.len(),
+                                views,
+                                peers,
+                                incidents: self.incidents.iter().cloned().collect(),
+                            });
+                        }
+                    }
+                },
+                // signatures checked by the verifier
+                verified = self.verified_receiver.next() => {
+                    match verified.unwrap() {
+                        Verified::MiniBlock { pubkey, mini_block, valid } => {
+                            if !valid {
+                                self.metrics.mini_blocks_rejected.get_or_create(&Peer { peer: hex(&pubkey) }).inc();
+                                continue;
+                            }
+                            self.mini_blocks_cache.entry(mini_block.view).or_default().insert(pubkey, mini_block);
+                        }
+                        Verified::ProtoBlock { view, proto_block, valid, response } => {
+                            let sufficient = self.sufficient(view, &proto_block, &valid, &supervisor);
+                            let _ = response.send(sufficient);
+                        }
+                    }
+                },
+            }
+        }
+    }
 
-                    let _ = response.send(alreay_has);
-                }
-                // used by server to receive chat from users
-                Message::LoadChat { data, response } => {
-                    self.chat_queue.push_back(data);
-                    // TODO if chat queue is too large, ask other end to stop
-                    response.send(true).unwrap();
-                }
+    /// Whether the verified mini-blocks of a proposal for `view` hold a stake quorum,
+    /// recording an incident against the leader if not
+    fn sufficient(&mut self, view: u64, proto_block: &ProtoBlock, valid: &[bool], supervisor: &SupervisorImpl) -> bool {
+        let collected = proto_block.tally(view, supervisor, valid);
+        for pubkey in collected.rejected.iter() {
+            self.metrics.mini_blocks_rejected.get_or_create(&Peer { peer: hex(pubkey) }).inc();
+        }
+        info!("num_valid_mini_block {} with weight {} at view {}", collected.participants.len(), collected.weight, view);
+        let quorum_weight = supervisor.quorum_weight(view);
+        if collected.weight >= quorum_weight || view == 1 {
+            return true;
+        }
+        let leader = TSu::leader(supervisor, view, group::Signature::one()).unwrap();
+        self.incidents.push_back(status::Incident {
+            view,
+            leader: hex(&leader),
+            weight: collected.weight,
+            quorum_weight,
+        });
+        if self.incidents.len() > HISTORY {
+            self.incidents.pop_front();
+        }
+        false
+    }
+
+    /// Our signed mini-block for `view`, created from the mempool and journaled before it
+    /// is returned the first time. None when BLS signing is configured but we hold no
+    /// share for the view: an Ed25519 signature would not be attested, so the chats stay
+    /// queued instead of going into a mini-block every proposal drops.
+    async fn local_mini_block(&mut self, view: u64, crypto: &mut Ed25519, supervisor: &SupervisorImpl) -> Option<MiniBlock> {
+        if let Some(mini_block) = self.local_mini_blocks.get(&view) {
+            self.metrics.mini_blocks_resent.inc();
+            return Some(mini_block.clone());
+        }
+        let share = match self.attestation {
+            Kind::Ed25519 => None,
+            Kind::Bls => {
+                let Some(share) = TSu::share(supervisor, view) else {
+                    // Mini-blocks of non-participants are not attested either
+                    if supervisor.is_participant(view, &crypto.public_key()).is_some() {
+                        error!(view, "no share of the group key to sign a mini-block with (missing from --epoch-shares?)");
+                        self.metrics.mini_blocks_unsigned.inc();
+                    }
+                    return None;
+                };
+                Some(share)
             }
+        };
+        let chats = self.drain_chats(view);
+        let mut mini_block = MiniBlock::new(view, chats, crypto.public_key().into());
+        match share {
+            Some(share) => mini_block.sign_bls(&mut self.hasher, share),
+            None => mini_block.sign(&mut self.hasher, crypto),
+        }
+
+        // Persist it before anyone sees it
+        let item = serde_json::to_vec(&mini_block).unwrap();
+        self.journal.append(view, Bytes::from(item)).await.expect("Failed to append mini-block");
+        self.journal.sync(view).await.expect("Failed to sync mini-block");
+        self.local_mini_blocks.insert(view, mini_block.clone());
+
+        // Forget those of views consensus no longer revisits
+        if view > self.retention_views {
+            let oldest = view - self.retention_views;
+            self.local_mini_blocks = self.local_mini_blocks.split_off(&oldest);
+            self.journal.prune(oldest).await.expect("Failed to prune mini-blocks");
+        }
+        Some(mini_block)
+    }
+
+    /// Take the chats for our local mini-block at `view`, dropping expired ones
+    fn drain_chats(&mut self, view: u64) -> Vec<Vec<u8>> {
+        self.view = self.view.max(view);
+        let expired = self.mempool.expire(self.view);
+        self.metrics.mempool_expired.inc_by(expired as u64);
+        let (mut chats, late) = self.votes.chats(view);
+        if late > 0 {
+            error!(view, late, "dropped reconfiguration votes too late to activate");
         }
+        chats.extend(self.mempool.select());
+        chats
     }
 }
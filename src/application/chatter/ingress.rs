@@ -3,7 +3,11 @@ use futures:: {
     channel::{mpsc, oneshot},
     SinkExt,
 };
+use crate::application::epoch::Reconfiguration;
 use crate::application::mini_block::{MiniBlock, ProtoBlock};
+use crate::application::status;
+use crate::application::chatter::mempool::Error;
+use crate::application::transaction::Transaction;
 use commonware_cryptography::PublicKey;
 
 
@@ -30,14 +34,30 @@ pub enum Message {
         response: oneshot::Sender<bool>,
     },
     LoadChat {
+        transaction: Transaction,
+        response: oneshot::Sender<Result<(), Error>>,
+    },
+    LoadReconfiguration {
         data: Bytes,
         response: oneshot::Sender<bool>,
     },
+    // communication with application actor
+    Executed {
+        nonces: Vec<(Vec<u8>, u64)>,
+    },
+    Settled {
+        reconfiguration: Reconfiguration,
+    },
     CheckSufficientProtoBlock {
         view: u64,
         proto_block: ProtoBlock,
         response: oneshot::Sender<bool>,
     },
+    // communication with api server
+    Status {
+        view: u64,
+        response: oneshot::Sender<status::Chatter>,
+    },
 }
 
 
@@ -94,16 +114,47 @@ impl Mailbox {
         receiver
     }
 
-    pub async fn load_chat(&mut self, data: Bytes) -> oneshot::Receiver<bool> {
+    /// queue a user transaction whose signature was checked, returning why it was
+    /// refused (e.g. a replay or a full mempool)
+    pub async fn load_chat(&mut self, transaction: Transaction) -> oneshot::Receiver<Result<(), Error>> {
         let (response, receiver) = oneshot::channel();
         self.sender
             .send(Message::LoadChat
-                { data, response })
+                { transaction, response })
             .await
             .expect("Failed to send get mini blocks");
         receiver
     }
 
+    /// vote for a reconfiguration in each of our mini-blocks until it is settled,
+    /// bypassing transaction checks. Returns false if the chat is not one
+    pub async fn load_reconfiguration(&mut self, data: Bytes) -> oneshot::Receiver<bool> {
+        let (response, receiver) = oneshot::channel();
+        self.sender
+            .send(Message::LoadReconfiguration
+                { data, response })
+            .await
+            .expect("Failed to send reconfiguration");
+        receiver
+    }
+
+    /// notify the chatter of the nonces executed in a finalized proto-block
+    pub async fn executed(&mut self, nonces: Vec<(Vec<u8>, u64)>) {
+        self.sender
+            .send(Message::Executed { nonces })
+            .await
+            .expect("Failed to send executed nonces");
+    }
+
+    /// notify the chatter a reconfiguration was scheduled, or can no longer be, so we
+    /// stop voting for it
+    pub async fn settled(&mut self, reconfiguration: Reconfiguration) {
+        self.sender
+            .send(Message::Settled { reconfiguration })
+            .await
+            .expect("Failed to send settled reconfiguration");
+    }
+
     pub async fn check_sufficient_mini_blocks(&mut self, view: u64, proto_block: ProtoBlock) -> oneshot::Receiver<bool> {
         let (response, receiver) = oneshot::channel();
         self.sender
@@ -112,4 +163,14 @@ impl Mailbox {
             .expect("Failed to send get mini blocks");
         receiver
     }
+
+    /// Snapshot of the chatter, reporting peers active at `view`
+    pub async fn status(&mut self, view: u64) -> oneshot::Receiver<status::Chatter> {
+        let (response, receiver) = oneshot::channel();
+        self.sender
+            .send(Message::Status { view, response })
+            .await
+            .expect("Failed to send status");
+        receiver
+    }
 }
@@ -0,0 +1,491 @@
+//! Transactions waiting for our next mini-block.
+//!
+//! The mempool is bounded by a number of transactions and of bytes, so users flooding
+//! a validator are refused instead of exhausting its memory. When it is full, a new
+//! transaction evicts the newest one of the sender holding the most, as long as that
+//! sender holds more than the newcomer's sender would: heavy senders make room for
+//! light ones, but nobody can push out a fair share. Transactions not included within
+//! `expiry` views are dropped, as are those replayed by an executed nonce.
+//!
+//! Each mini-block takes at most a budget of transactions, chosen by the configured
+//! [`Policy`]; the others wait for the next one. A sender's transactions are only
+//! included as a run of consecutive nonces starting at its next executable one: users
+//! fan transactions out to several validators, so a later nonce may reach us before
+//! the earlier ones, and including it before they are executed would make it a gap.
+//! It waits (until it expires) for the earlier ones to be executed, whichever
+//! validator included them.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use thiserror::Error;
+
+use crate::application::chatter::selection::{Head, Policy};
+use crate::application::transaction::{self, Nonces, Transaction};
+
+/// Reasons a transaction is not queued.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum Error {
+    #[error(transparent)]
+    Transaction(#[from] transaction::Error),
+    #[error("transaction of {0} bytes exceeds the mempool or mini-block limit")]
+    TooLarge(usize),
+    #[error("sender already has {0} queued transactions")]
+    SenderLimit(usize),
+    #[error("mempool is full")]
+    Full,
+}
+
+impl Error {
+    /// Short label for metrics.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Error::Transaction(transaction::Error::Malformed) => "malformed",
+            Error::Transaction(transaction::Error::InvalidSignature) => "invalid_signature",
+            Error::Transaction(transaction::Error::Replayed(_, _)) => "replayed",
+            Error::Transaction(transaction::Error::Gap(_, _)) => "gap",
+            Error::Transaction(transaction::Error::Duplicate) => "duplicate",
+            Error::TooLarge(_) => "too_large",
+            Error::SenderLimit(_) => "sender_limit",
+            Error::Full => "full",
+        }
+    }
+}
+
+/// Limits of the mempool.
+pub struct Config {
+    /// Transactions held at once.
+    pub max_transactions: usize,
+
+    /// Encoded bytes of the transactions held at once.
+    pub max_bytes: usize,
+
+    /// Transactions held at once from a single sender.
+    pub max_per_sender: usize,
+
+    /// Views a transaction waits for a mini-block before being dropped.
+    pub expiry: u64,
+
+    /// Transactions included in a single mini-block.
+    pub mini_block_transactions: usize,
+
+    /// Encoded bytes of the transactions included in a single mini-block.
+    pub mini_block_bytes: usize,
+
+    /// Chooses the transactions included when the mempool exceeds the budget.
+    pub policy: Box<dyn Policy>,
+}
+
+/// A queued transaction.
+struct Entry {
+    transaction: Transaction,
+    /// Transaction as included in mini-blocks.
+    chat: Vec<u8>,
+    /// View at which it was queued.
+    view: u64,
+    /// Arrival order.
+    arrival: u64,
+}
+
+/// Bounded queue of transactions, in arrival order.
+pub struct Mempool {
+    config: Config,
+    entries: VecDeque<Entry>,
+    bytes: usize,
+    /// sender -> queued transactions
+    senders: HashMap<Vec<u8>, usize>,
+    /// nonces executed in finalized proto-blocks
+    nonces: Nonces,
+    /// arrival of the next transaction
+    arrivals: u64,
+}
+
+impl Mempool {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            entries: VecDeque::new(),
+            bytes: 0,
+            senders: HashMap::new(),
+            nonces: Nonces::default(),
+            arrivals: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Queue a transaction received at `view`, returning the number of transactions
+    /// evicted to make room for it. Transactions skipping nonces are queued too, as the
+    /// ones filling the gap may have been sent to other validators.
+    pub fn insert(&mut self, transaction: Transaction, view: u64) -> Result<usize, Error> {
+        match self.nonces.check(&transaction) {
+            Ok(()) | Err(transaction::Error::Gap(_, _)) => {}
+            Err(e) => return Err(e.into()),
+        }
+        let duplicate = self.entries.iter().any(|entry| {
+            entry.transaction.sender == transaction.sender && entry.transaction.nonce == transaction.nonce
+        });
+        if duplicate {
+            return Err(transaction::Error::Duplicate.into());
+        }
+        let chat = transaction.encode();
+        // A transaction that cannot fit in a mini-block would wait until it expires
+        if chat.len() > self.config.max_bytes.min(self.config.mini_block_bytes) {
+            return Err(Error::TooLarge(chat.len()));
+        }
+        let own = self.senders.get(&transaction.sender).cloned().unwrap_or_default();
+        if own >= self.config.max_per_sender {
+            return Err(Error::SenderLimit(own));
+        }
+
+        // Pick the transactions to evict before touching the queue, so a refused
+        // transaction never costs anyone else theirs
+        let mut victims = HashSet::new();
+        let mut counts = self.senders.clone();
+        let mut len = self.entries.len();
+        let mut bytes = self.bytes;
+        while len + 1 > self.config.max_transactions || bytes + chat.len() > self.config.max_bytes {
+            let (heaviest, count) = counts
+                .iter()
+                .filter(|(sender, _)| **sender != transaction.sender)
+                .max_by_key(|(_, count)| **count)
+                .map(|(sender, count)| (sender.clone(), *count))
+                .ok_or(Error::Full)?;
+            if count <= own + 1 {
+                return Err(Error::Full);
+            }
+            let (index, entry) = self
+                .entries
+                .iter()
+                .enumerate()
+                .rev()
+                .find(|(index, entry)| entry.transaction.sender == heaviest && !victims.contains(index))
+                .unwrap();
+            victims.insert(index);
+            *counts.get_mut(&heaviest).unwrap() -= 1;
+            len -= 1;
+            bytes -= entry.chat.len();
+        }
+        let mut index = 0;
+        self.entries.retain(|_| {
+            index += 1;
+            !victims.contains(&(index - 1))
+        });
+        self.bytes = bytes;
+        self.senders = counts;
+        self.senders.retain(|_, count| *count > 0);
+
+        // Queue the transaction
+        self.bytes += chat.len();
+        *self.senders.entry(transaction.sender.clone()).or_default() += 1;
+        self.entries.push_back(Entry {
+            transaction,
+            chat,
+            view,
+            arrival: self.arrivals,
+        });
+        self.arrivals += 1;
+        Ok(victims.len())
+    }
+
+    /// Take the transactions for our next mini-block, within its budget and in the
+    /// order the policy picked them.
+    pub fn select(&mut self) -> Vec<Vec<u8>> {
+        // Queue of each sender, in nonce order and up to the first gap
+        let mut lanes: BTreeMap<&[u8], Vec<usize>> = BTreeMap::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            lanes.entry(&entry.transaction.sender).or_default().push(index);
+        }
+        let mut lanes: Vec<(VecDeque<usize>, usize)> = lanes
+            .into_iter()
+            .filter_map(|(sender, mut lane)| {
+                lane.sort_by_key(|index| self.entries[*index].transaction.nonce);
+                let run = lane
+                    .iter()
+                    .zip(self.nonces.next(sender)..)
+                    .take_while(|(index, nonce)| self.entries[**index].transaction.nonce == *nonce)
+                    .count();
+                lane.truncate(run);
+                (!lane.is_empty()).then(|| (lane.into(), 0))
+            })
+            .collect();
+
+        // Pick heads until the budget is spent, dropping senders whose next
+        // transaction does not fit
+        let mut selected = Vec::new();
+        let mut bytes = 0;
+        while selected.len() < self.config.mini_block_transactions && !lanes.is_empty() {
+            let heads: Vec<Head> = lanes
+                .iter()
+                .map(|(lane, picked)| {
+                    let entry = &self.entries[lane[0]];
+                    Head {
+                        sender: &entry.transaction.sender,
+                        fee: entry.transaction.fee,
+                        arrival: entry.arrival,
+                        picked: *picked,
+                    }
+                })
+                .collect();
+            let choice = self.config.policy.pick(&heads);
+            let (lane, picked) = &mut lanes[choice];
+            let index = lane.pop_front().unwrap();
+            let size = self.entries[index].chat.len();
+            if bytes + size > self.config.mini_block_bytes {
+                lanes.swap_remove(choice);
+                continue;
+            }
+            bytes += size;
+            *picked += 1;
+            selected.push(index);
+            if lane.is_empty() {
+                lanes.swap_remove(choice);
+            }
+        }
+
+        // Take them out of the mempool
+        let mut entries: Vec<Option<Entry>> = self.entries.drain(..).map(Some).collect();
+        let mut chats = Vec::with_capacity(selected.len());
+        for index in selected {
+            let entry = entries[index].take().unwrap();
+            self.bytes -= entry.chat.len();
+            let count = self.senders.get_mut(&entry.transaction.sender).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                self.senders.remove(&entry.transaction.sender);
+            }
+            chats.push(entry.chat);
+        }
+        self.entries = entries.into_iter().flatten().collect();
+        chats
+    }
+
+    /// Record nonces executed in a finalized proto-block and drop the queued
+    /// transactions they replay, returning how many were dropped.
+    pub fn executed(&mut self, nonces: Vec<(Vec<u8>, u64)>) -> usize {
+        for (sender, nonce) in nonces {
+            self.nonces.update(sender, nonce);
+        }
+        let nonces = std::mem::take(&mut self.nonces);
+        let removed = self.remove(|entry| {
+            matches!(nonces.check(&entry.transaction), Err(transaction::Error::Replayed(_, _)))
+        });
+        self.nonces = nonces;
+        removed
+    }
+
+    /// Drop transactions queued more than `expiry` views before `view`, returning
+    /// how many were dropped.
+    pub fn expire(&mut self, view: u64) -> usize {
+        let expiry = self.config.expiry;
+        self.remove(|entry| entry.view + expiry < view)
+    }
+
+    fn remove(&mut self, mut predicate: impl FnMut(&Entry) -> bool) -> usize {
+        let before = self.entries.len();
+        let bytes = &mut self.bytes;
+        let senders = &mut self.senders;
+        self.entries.retain(|entry| {
+            if !predicate(entry) {
+                return true;
+            }
+            *bytes -= entry.chat.len();
+            let count = senders.get_mut(&entry.transaction.sender).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                senders.remove(&entry.transaction.sender);
+            }
+            false
+        });
+        before - self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::chatter::selection::Kind;
+    use commonware_cryptography::{Ed25519, Scheme};
+
+    fn transaction(sender: u64, nonce: u64, fee: u64) -> Transaction {
+        let mut signer = Ed25519::from_seed(sender);
+        Transaction::sign(&mut signer, nonce, fee, vec![sender as u8; 8])
+    }
+
+    fn mempool(policy: Kind, mini_block_transactions: usize) -> Mempool {
+        Mempool::new(Config {
+            max_transactions: 16,
+            max_bytes: 64 * 1024,
+            max_per_sender: 8,
+            expiry: 10,
+            mini_block_transactions,
+            mini_block_bytes: 64 * 1024,
+            policy: policy.policy(),
+        })
+    }
+
+    /// (sender payload byte, nonce) of each selected transaction, in order.
+    fn select(mempool: &mut Mempool) -> Vec<(u8, u64)> {
+        mempool
+            .select()
+            .iter()
+            .map(|chat| {
+                let transaction = Transaction::decode(chat).unwrap();
+                (transaction.payload[0], transaction.nonce)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fifo_keeps_arrival_order() {
+        let mut mempool = mempool(Kind::Fifo, 3);
+        for (sender, nonce) in [(1, 0), (2, 0), (1, 1), (3, 0)] {
+            mempool.insert(transaction(sender, nonce, 0), 0).unwrap();
+        }
+        assert_eq!(select(&mut mempool), vec![(1, 0), (2, 0), (1, 1)]);
+        assert_eq!(select(&mut mempool), vec![(3, 0)]);
+        assert!(mempool.is_empty());
+        assert_eq!(mempool.bytes(), 0);
+    }
+
+    #[test]
+    fn test_fee_priority_respects_nonce_order() {
+        let mut mempool = mempool(Kind::Fee, 3);
+        mempool.insert(transaction(1, 1, 100), 0).unwrap();
+        mempool.insert(transaction(1, 0, 1), 0).unwrap();
+        mempool.insert(transaction(2, 0, 50), 0).unwrap();
+        mempool.insert(transaction(3, 0, 10), 0).unwrap();
+
+        // Sender 1's high bid waits for its lower nonce, which only bids 1, and then
+        // for it to be executed as it did not fit in the same mini-block
+        assert_eq!(select(&mut mempool), vec![(2, 0), (3, 0), (1, 0)]);
+        assert!(select(&mut mempool).is_empty());
+        mempool.executed(vec![(transaction(1, 0, 0).sender, 0)]);
+        assert_eq!(select(&mut mempool), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn test_round_robin_shares_mini_blocks() {
+        let mut mempool = mempool(Kind::RoundRobin, 4);
+        for nonce in 0..4 {
+            mempool.insert(transaction(1, nonce, 0), 0).unwrap();
+        }
+        mempool.insert(transaction(2, 0, 0), 0).unwrap();
+        mempool.insert(transaction(3, 0, 0), 0).unwrap();
+        assert_eq!(select(&mut mempool), vec![(1, 0), (2, 0), (3, 0), (1, 1)]);
+        mempool.executed(vec![(transaction(1, 0, 0).sender, 1)]);
+        assert_eq!(select(&mut mempool), vec![(1, 2), (1, 3)]);
+    }
+
+    #[test]
+    fn test_byte_budget() {
+        let mut mempool = mempool(Kind::Fifo, 16);
+        let sizes: Vec<usize> = (1..=3).map(|sender| transaction(sender, 0, 0).encode().len()).collect();
+        mempool.config.mini_block_bytes = sizes[0] + sizes[1];
+        for sender in 1..=3 {
+            mempool.insert(transaction(sender, 0, 0), 0).unwrap();
+        }
+        assert_eq!(select(&mut mempool).len(), 2);
+        assert_eq!(mempool.len(), 1);
+    }
+
+    #[test]
+    fn test_larger_than_mini_block() {
+        let mut mempool = mempool(Kind::Fifo, 16);
+        let size = transaction(1, 0, 0).encode().len();
+        mempool.config.mini_block_bytes = size - 1;
+        assert_eq!(mempool.insert(transaction(1, 0, 0), 0), Err(Error::TooLarge(size)));
+        assert!(mempool.is_empty());
+
+        mempool.config.mini_block_bytes = size;
+        mempool.insert(transaction(1, 0, 0), 0).unwrap();
+        assert_eq!(select(&mut mempool), vec![(1, 0)]);
+    }
+
+    #[test]
+    fn test_rejections() {
+        let mut mempool = mempool(Kind::Fifo, 16);
+        mempool.insert(transaction(1, 5, 0), 0).unwrap();
+        assert_eq!(
+            mempool.insert(transaction(1, 5, 0), 0),
+            Err(Error::Transaction(transaction::Error::Duplicate))
+        );
+
+        // Executed nonces drop queued replays and refuse new ones
+        assert_eq!(mempool.executed(vec![(transaction(1, 5, 0).sender, 5)]), 1);
+        assert_eq!(
+            mempool.insert(transaction(1, 4, 0), 0),
+            Err(Error::Transaction(transaction::Error::Replayed(4, 6)))
+        );
+        for nonce in 6..14 {
+            mempool.insert(transaction(1, nonce, 0), 0).unwrap();
+        }
+        assert_eq!(mempool.insert(transaction(1, 14, 0), 0), Err(Error::SenderLimit(8)));
+    }
+
+    #[test]
+    fn test_full_mempool_evicts_heaviest_sender() {
+        let mut mempool = mempool(Kind::Fifo, 16);
+        for nonce in 1..=8 {
+            mempool.insert(transaction(1, nonce, 0), 0).unwrap();
+            mempool.insert(transaction(2, nonce, 0), 0).unwrap();
+        }
+
+        // A new sender evicts the newest transaction of a heavy one
+        assert_eq!(mempool.insert(transaction(3, 1, 0), 0), Ok(1));
+        assert_eq!(mempool.len(), 16);
+
+        // Until it would hold as many as the others
+        for nonce in 2..=5 {
+            mempool.insert(transaction(3, nonce, 0), 0).unwrap();
+        }
+        assert_eq!(mempool.insert(transaction(3, 6, 0), 0), Err(Error::Full));
+    }
+
+    #[test]
+    fn test_expiry() {
+        let mut mempool = mempool(Kind::Fifo, 16);
+        mempool.insert(transaction(1, 0, 0), 0).unwrap();
+        mempool.insert(transaction(2, 0, 0), 5).unwrap();
+        assert_eq!(mempool.expire(10), 0);
+        assert_eq!(mempool.expire(11), 1);
+        assert_eq!(select(&mut mempool), vec![(2, 0)]);
+    }
+
+    #[test]
+    fn test_out_of_order_arrival() {
+        // The user fanned nonces 0 to 3 out, 1 reaches us last
+        let mut ours = mempool(Kind::Fee, 16);
+        for nonce in [3, 2, 0] {
+            ours.insert(transaction(1, nonce, 10 - nonce), 0).unwrap();
+        }
+        ours.insert(transaction(2, 0, 0), 0).unwrap();
+
+        // Only the run before the gap is included, the rest waits
+        assert_eq!(select(&mut ours), vec![(1, 0), (2, 0)]);
+        assert_eq!(ours.len(), 2);
+        ours.insert(transaction(1, 1, 0), 1).unwrap();
+        assert!(select(&mut ours).is_empty());
+
+        // Once 0 is executed the rest follows in nonce order, however it arrived
+        ours.executed(vec![(transaction(1, 0, 0).sender, 0)]);
+        assert_eq!(select(&mut ours), vec![(1, 1), (1, 2), (1, 3)]);
+
+        // Had another validator included 0 and 1, only what follows is left
+        let mut other = mempool(Kind::Fifo, 16);
+        for nonce in [2, 1, 3] {
+            other.insert(transaction(1, nonce, 0), 0).unwrap();
+        }
+        assert!(select(&mut other).is_empty());
+        assert_eq!(other.executed(vec![(transaction(1, 0, 0).sender, 1)]), 1);
+        assert_eq!(select(&mut other), vec![(1, 2), (1, 3)]);
+    }
+}
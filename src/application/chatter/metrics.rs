@@ -0,0 +1,106 @@
+use prometheus_client::{
+    metrics::{
+        counter::Counter,
+        family::Family,
+        histogram::{exponential_buckets, Histogram},
+    },
+    registry::Registry,
+};
+use std::sync::{Arc, Mutex};
+
+use crate::application::metrics::{Peer, Reason};
+
+/// Metrics recorded by the chatter.
+#[derive(Clone)]
+pub struct Metrics {
+    /// Mini-blocks we signed and sent to the next leader.
+    pub mini_blocks_sent: Counter,
+    /// Mini-blocks reused because we already signed one for the view.
+    pub mini_blocks_resent: Counter,
+    /// Views we could not sign a mini-block for, lacking a share of the group key.
+    pub mini_blocks_unsigned: Counter,
+    /// Mini-blocks cached from peers, by creator.
+    pub mini_blocks_received: Family<Peer, Counter>,
+    /// Mini-blocks discarded for a bad signature or creator, by creator.
+    pub mini_blocks_rejected: Family<Peer, Counter>,
+    /// Mini-blocks in each proto-block we propose.
+    pub proto_block_mini_blocks: Histogram,
+    /// Chats in each proto-block we propose.
+    pub proto_block_chats: Histogram,
+    /// Transactions refused by the mempool, by reason.
+    pub mempool_rejected: Family<Reason, Counter>,
+    /// Transactions evicted from the full mempool by lighter senders.
+    pub mempool_evicted: Counter,
+    /// Transactions dropped after waiting too long for a mini-block.
+    pub mempool_expired: Counter,
+}
+
+impl Metrics {
+    pub fn init(registry: &Arc<Mutex<Registry>>) -> Self {
+        let metrics = Self {
+            mini_blocks_sent: Counter::default(),
+            mini_blocks_resent: Counter::default(),
+            mini_blocks_unsigned: Counter::default(),
+            mini_blocks_received: Family::default(),
+            mini_blocks_rejected: Family::default(),
+            proto_block_mini_blocks: Histogram::new(exponential_buckets(1.0, 2.0, 12)),
+            proto_block_chats: Histogram::new(exponential_buckets(1.0, 2.0, 16)),
+            mempool_rejected: Family::default(),
+            mempool_evicted: Counter::default(),
+            mempool_expired: Counter::default(),
+        };
+        let mut registry = registry.lock().unwrap();
+        let registry = registry.sub_registry_with_prefix("chatter");
+        registry.register(
+            "mini_blocks_sent",
+            "mini-blocks sent to the next leader",
+            metrics.mini_blocks_sent.clone(),
+        );
+        registry.register(
+            "mini_blocks_resent",
+            "mini-blocks reused instead of signing another one for the view",
+            metrics.mini_blocks_resent.clone(),
+        );
+        registry.register(
+            "mini_blocks_unsigned",
+            "views without a mini-block for lack of a share of the group key",
+            metrics.mini_blocks_unsigned.clone(),
+        );
+        registry.register(
+            "mini_blocks_received",
+            "mini-blocks received from peers",
+            metrics.mini_blocks_received.clone(),
+        );
+        registry.register(
+            "mini_blocks_rejected",
+            "mini-blocks rejected for a bad signature or creator",
+            metrics.mini_blocks_rejected.clone(),
+        );
+        registry.register(
+            "proto_block_mini_blocks",
+            "mini-blocks per proposed proto-block",
+            metrics.proto_block_mini_blocks.clone(),
+        );
+        registry.register(
+            "proto_block_chats",
+            "chats per proposed proto-block",
+            metrics.proto_block_chats.clone(),
+        );
+        registry.register(
+            "mempool_rejected",
+            "transactions refused by the mempool by reason",
+            metrics.mempool_rejected.clone(),
+        );
+        registry.register(
+            "mempool_evicted",
+            "transactions evicted from the full mempool",
+            metrics.mempool_evicted.clone(),
+        );
+        registry.register(
+            "mempool_expired",
+            "transactions expired in the mempool",
+            metrics.mempool_expired.clone(),
+        );
+        metrics
+    }
+}
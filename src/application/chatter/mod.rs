@@ -1,6 +1,46 @@
+use commonware_cryptography::Hasher;
+use commonware_runtime::{Blob, Storage};
+use commonware_storage::journal::Journal;
+use prometheus_client::registry::Registry;
+use std::sync::{Arc, Mutex};
+
+use crate::application::attestation;
+
 pub mod ingress;
 pub mod actor;
+pub mod mempool;
+pub mod selection;
+pub mod verifier;
+pub mod votes;
+mod metrics;
+
+/// Configuration for the chatter.
+pub struct Config<B: Blob, E: Storage<B>, H: Hasher> {
+    /// Hashing scheme used to commit to the chats of mini-blocks.
+    pub hasher: H,
+
+    /// Journal of the mini-blocks we signed, so a restart re-sends them instead of
+    /// signing conflicting ones.
+    pub journal: Journal<B, E>,
+
+    /// Number of journal sections replayed at once on startup.
+    pub replay_concurrency: usize,
+
+    /// Views of signed mini-blocks kept, older ones are pruned.
+    pub retention_views: u64,
+
+    /// Number of messages from other actors to hold before blocking.
+    pub mailbox_size: usize,
+
+    /// Limits of the queue of transactions waiting for our next mini-block.
+    pub mempool: mempool::Config,
+
+    /// Signature scheme of mini-blocks, the same for all validators.
+    pub attestation: attestation::Kind,
+
+    /// Threads and batches used to check mini-block signatures.
+    pub verifier: verifier::Config,
 
-pub struct Config {
-    mailbox_size: usize,
+    /// Registry for chatter metrics.
+    pub registry: Arc<Mutex<Registry>>,
 }
@@ -0,0 +1,84 @@
+//! Policies choosing which queued transactions go into our next mini-block.
+//!
+//! A mini-block only holds a budget of transactions, so when the mempool holds more
+//! a policy decides who goes first. Transactions of a sender are always included in
+//! nonce order and without gaps (a nonce executed before the earlier ones would be
+//! rejected), so policies only choose between the lowest-nonce transaction of each
+//! sender, its [`Head`], one pick at a time.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+/// Lowest-nonce queued transaction of a sender.
+#[derive(Clone, Debug)]
+pub struct Head<'a> {
+    pub sender: &'a [u8],
+    pub fee: u64,
+    /// Arrival order in the mempool, lower arrived first.
+    pub arrival: u64,
+    /// Transactions of the sender already picked for this mini-block.
+    pub picked: usize,
+}
+
+/// Chooses the transaction to include next.
+pub trait Policy: Send {
+    /// Index into `heads` (never empty) of the transaction to include next.
+    fn pick(&self, heads: &[Head]) -> usize;
+}
+
+/// First come, first served.
+pub struct Fifo;
+
+impl Policy for Fifo {
+    fn pick(&self, heads: &[Head]) -> usize {
+        index_of_min(heads, |head| head.arrival)
+    }
+}
+
+/// Highest fee first, ties broken by arrival.
+pub struct FeePriority;
+
+impl Policy for FeePriority {
+    fn pick(&self, heads: &[Head]) -> usize {
+        index_of_min(heads, |head| (Reverse(head.fee), head.arrival))
+    }
+}
+
+/// One transaction per sender in turn, senders served in arrival order, so a sender
+/// flooding the mempool cannot crowd the others out of a mini-block.
+pub struct RoundRobin;
+
+impl Policy for RoundRobin {
+    fn pick(&self, heads: &[Head]) -> usize {
+        index_of_min(heads, |head| (head.picked, head.arrival))
+    }
+}
+
+fn index_of_min<K: Ord>(heads: &[Head], key: impl Fn(&Head) -> K) -> usize {
+    heads
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, head)| key(head))
+        .map(|(index, _)| index)
+        .unwrap()
+}
+
+/// Policy named in the validator config.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    #[default]
+    Fifo,
+    Fee,
+    RoundRobin,
+}
+
+impl Kind {
+    pub fn policy(self) -> Box<dyn Policy> {
+        match self {
+            Kind::Fifo => Box::new(Fifo),
+            Kind::Fee => Box::new(FeePriority),
+            Kind::RoundRobin => Box::new(RoundRobin),
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Mini-block signature checks, run on a thread pool so the chatter keeps serving
+//! messages while large proto-blocks are verified.
+//!
+//! Mini-blocks are split in batches verified in parallel, see
+//! [`MiniBlockHeader::verify_batch`]. The leaves of valid mini-blocks are remembered, so
+//! one seen before (e.g. cached from a peer, then proposed) is not verified again.
+//! Mini-blocks signed with BLS are verified one by one, and proposals attesting them
+//! with a single aggregate, see [`attestation`].
+
+use commonware_consensus::Supervisor as Su;
+use commonware_cryptography::{Digest, Hasher, PublicKey};
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use crate::application::{
+    attestation::{self, Kind},
+    mini_block::{MiniBlock, MiniBlockHeader, ProtoBlock},
+    supervisor::Supervisor,
+};
+
+/// Configuration for the verifier.
+pub struct Config {
+    /// Threads verifying signatures.
+    pub threads: usize,
+
+    /// Signatures verified together in a batch.
+    pub batch_size: usize,
+
+    /// Valid mini-blocks remembered.
+    pub cache_size: usize,
+}
+
+/// Leaves of valid mini-blocks, oldest evicted first
+struct Cache {
+    leaves: HashSet<Digest>,
+    order: VecDeque<Digest>,
+    capacity: usize,
+}
+
+impl Cache {
+    fn insert(&mut self, leaf: Digest) {
+        if !self.leaves.insert(leaf.clone()) {
+            return;
+        }
+        self.order.push_back(leaf);
+        while self.order.len() > self.capacity {
+            let oldest = self.order.pop_front().unwrap();
+            self.leaves.remove(&oldest);
+        }
+    }
+}
+
+/// Checks mini-block signatures off the calling task.
+#[derive(Clone)]
+pub struct Verifier<H: Hasher> {
+    hasher: H,
+    kind: Kind,
+    pool: Arc<ThreadPool>,
+    batch_size: usize,
+    cache: Arc<Mutex<Cache>>,
+}
+
+impl<H: Hasher> Verifier<H> {
+    pub fn new(hasher: H, kind: Kind, config: Config) -> Self {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(config.threads)
+            .thread_name(|index| format!("verifier-{}", index))
+            .build()
+            .expect("Failed to start verifier threads");
+        Self {
+            hasher,
+            kind,
+            pool: Arc::new(pool),
+            batch_size: config.batch_size,
+            cache: Arc::new(Mutex::new(Cache {
+                leaves: HashSet::new(),
+                order: VecDeque::new(),
+                capacity: config.cache_size,
+            })),
+        }
+    }
+
+    /// Verify the mini-blocks in the background, then call `done` with them and
+    /// whether each is signed by its creator, in order.
+    pub fn verify<F>(&self, mini_blocks: Vec<MiniBlock>, supervisor: Supervisor, done: F)
+    where
+        F: FnOnce(Vec<MiniBlock>, Vec<bool>) + Send + 'static,
+    {
+        let verifier = self.clone();
+        self.pool.spawn(move || {
+            let valid = verifier.check(&mini_blocks, &supervisor);
+            done(mini_blocks, valid);
+        });
+    }
+
+    /// Verify the attestation of a proposal for `view` in the background, then call `done`
+    /// with it and whether each mini-block is attested (all or none are).
+    pub fn verify_attested<F>(&self, view: u64, proto_block: ProtoBlock, supervisor: Supervisor, done: F)
+    where
+        F: FnOnce(ProtoBlock, Vec<bool>) + Send + 'static,
+    {
+        let mut hasher = self.hasher.clone();
+        self.pool.spawn(move || {
+            let attested = proto_block.attestation.as_ref().map_or(false, |attestation| {
+                attestation.verify(&mut hasher, view, &proto_block, &supervisor)
+            });
+            let valid = vec![attested; proto_block.mini_blocks.len()];
+            done(proto_block, valid);
+        });
+    }
+
+    /// Verify batches in parallel on the pool, skipping the mini-blocks known valid
+    fn check(&self, mini_blocks: &[MiniBlock], supervisor: &Supervisor) -> Vec<bool> {
+        let batches: Vec<(H, &[MiniBlock])> = mini_blocks
+            .chunks(self.batch_size)
+            .map(|batch| (self.hasher.clone(), batch))
+            .collect();
+        let cache = &self.cache;
+        let kind = self.kind;
+        batches
+            .into_par_iter()
+            .map(|(mut hasher, batch)| {
+                let headers: Vec<MiniBlockHeader> =
+                    batch.iter().map(|mini_block| mini_block.header(&mut hasher)).collect();
+                let leaves: Vec<Digest> =
+                    headers.iter().map(|header| header.leaf(&mut hasher)).collect();
+
+                // Only verify the mini-blocks not seen before
+                let mut valid = vec![true; batch.len()];
+                let unknown: Vec<usize> = {
+                    let cache = cache.lock().unwrap();
+                    (0..batch.len()).filter(|index| !cache.leaves.contains(&leaves[*index])).collect()
+                };
+                if unknown.is_empty() {
+                    return valid;
+                }
+                let unknown_headers: Vec<MiniBlockHeader> =
+                    unknown.iter().map(|index| headers[*index].clone()).collect();
+                let results = match kind {
+                    Kind::Ed25519 => MiniBlockHeader::verify_batch(&unknown_headers),
+                    Kind::Bls => unknown_headers.iter().map(|header| verify_bls(supervisor, header)).collect(),
+                };
+
+                let mut cache = cache.lock().unwrap();
+                for (index, result) in unknown.into_iter().zip(results) {
+                    valid[index] = result;
+                    if result {
+                        cache.insert(leaves[index].clone());
+                    }
+                }
+                valid
+            })
+            .collect::<Vec<Vec<bool>>>()
+            .concat()
+    }
+}
+
+/// Check a header is signed by the share of its creator at its view
+fn verify_bls(supervisor: &Supervisor, header: &MiniBlockHeader) -> bool {
+    let pubkey = PublicKey::copy_from_slice(&header.pubkey);
+    let Some(index) = supervisor.is_participant(header.view, &pubkey) else {
+        return false;
+    };
+    let public = supervisor.epoch(header.view).public_share(index);
+    attestation::verify(&public, header)
+}
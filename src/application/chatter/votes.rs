@@ -0,0 +1,87 @@
+//! Reconfigurations we vote for.
+//!
+//! A reconfiguration is only scheduled once a single finalized proto-block holds it in
+//! mini-blocks carrying a stake quorum, so a vote goes in every one of our mini-blocks
+//! until the application reports the reconfiguration was scheduled (or superseded),
+//! or it is too late for it to activate.
+
+use bytes::Bytes;
+
+use crate::application::epoch::{Reconfiguration, ACTIVATION_DELAY};
+
+/// Queue of the reconfigurations we vote for.
+#[derive(Default)]
+pub struct Votes {
+    reconfigurations: Vec<Reconfiguration>,
+}
+
+impl Votes {
+    /// Vote for a reconfiguration, false if the chat is not one.
+    pub fn insert(&mut self, chat: &Bytes) -> bool {
+        let Some(reconfiguration) = Reconfiguration::decode(chat) else {
+            return false;
+        };
+        if !self.reconfigurations.contains(&reconfiguration) {
+            self.reconfigurations.push(reconfiguration);
+        }
+        true
+    }
+
+    /// Chats of the votes for our mini-block at `view`, dropping (and returning how
+    /// many) reconfigurations that could no longer activate if finalized at `view`.
+    pub fn chats(&mut self, view: u64) -> (Vec<Vec<u8>>, usize) {
+        let before = self.reconfigurations.len();
+        self.reconfigurations
+            .retain(|reconfiguration| reconfiguration.activation >= view + ACTIVATION_DELAY);
+        let chats = self.reconfigurations.iter().map(Reconfiguration::encode).collect();
+        (chats, before - self.reconfigurations.len())
+    }
+
+    /// Stop voting for a reconfiguration the application scheduled or can no longer
+    /// schedule.
+    pub fn settled(&mut self, reconfiguration: &Reconfiguration) {
+        self.reconfigurations.retain(|vote| vote != reconfiguration);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reconfigurations.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reconfiguration(activation: u64) -> Reconfiguration {
+        Reconfiguration {
+            activation,
+            participants: vec![(vec![1; 32], 1)],
+            identity: vec![2; 48],
+        }
+    }
+
+    #[test]
+    fn test_votes_until_settled() {
+        let mut votes = Votes::default();
+        assert!(!votes.insert(&Bytes::from_static(b"hello")));
+        assert!(votes.insert(&reconfiguration(30).encode().into()));
+        assert!(votes.insert(&reconfiguration(30).encode().into()));
+        assert!(votes.insert(&reconfiguration(40).encode().into()));
+
+        // Every mini-block carries them, once each
+        for view in [1, 2] {
+            let (chats, dropped) = votes.chats(view);
+            assert_eq!(chats, vec![reconfiguration(30).encode(), reconfiguration(40).encode()]);
+            assert_eq!(dropped, 0);
+        }
+
+        // Until scheduled
+        votes.settled(&reconfiguration(40));
+        assert_eq!(votes.chats(3).0, vec![reconfiguration(30).encode()]);
+
+        // Or too late to activate
+        assert_eq!(votes.chats(20).1, 0);
+        assert_eq!(votes.chats(21), (Vec::new(), 1));
+        assert!(votes.is_empty());
+    }
+}
@@ -0,0 +1,320 @@
+//! Validator sets that change over time.
+//!
+//! A validator set change is submitted as a chat carrying a [`Reconfiguration`]. Once a
+//! finalized proto-block contains the same reconfiguration in mini-blocks holding a stake
+//! quorum of the current epoch, the new participant list and group key take effect at
+//! `activation`.
+//!
+//! The schedule is part of the state: state commitments include its [`schedule_root`]
+//! and snapshots carry the reconfigurations behind it, so a validator restoring one
+//! follows the same validator sets as those who executed every view.
+
+use commonware_cryptography::{
+    bls12381::primitives::{
+        group,
+        poly::{self, Poly},
+    },
+    Digest, Hasher, PublicKey,
+};
+use std::{collections::HashMap, sync::RwLock};
+use thiserror::Error;
+
+use crate::application::supervisor::{total_weight, weighted_quorum, weighted_threshold};
+
+/// Prefix distinguishing a reconfiguration from a regular chat.
+pub const RECONFIGURATION_PREFIX: &[u8] = b"_LITTLEDIPPER_RECONFIGURATION";
+
+/// Minimum number of views between the finalization of a reconfiguration and its
+/// activation, so no view in flight changes its validator set.
+pub const ACTIVATION_DELAY: u64 = 10;
+
+/// Errors that can occur when scheduling a new epoch.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("activation view {0} is not after view {1}")]
+    ActivationTooEarly(u64, u64),
+    #[error("reconfiguration has no participants")]
+    NoParticipants,
+    #[error("participant stakes are zero, overflow or too skewed to reach the threshold")]
+    InvalidWeights,
+    #[error("identity not well-formed")]
+    InvalidIdentity,
+}
+
+/// Validator set change carried in a chat.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Reconfiguration {
+    /// First view governed by the new validator set.
+    pub activation: u64,
+    /// Ed25519 public keys of the new participants with their stake.
+    pub participants: Vec<(Vec<u8>, u64)>,
+    /// Serialized group polynomial dealt to the new participants.
+    pub identity: Vec<u8>,
+}
+
+impl Reconfiguration {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut chat = RECONFIGURATION_PREFIX.to_vec();
+        chat.extend_from_slice(&serde_json::to_vec(self).unwrap());
+        chat
+    }
+
+    /// Decode a chat, none if it is not a reconfiguration.
+    pub fn decode(chat: &[u8]) -> Option<Self> {
+        let body = chat.strip_prefix(RECONFIGURATION_PREFIX)?;
+        serde_json::from_slice(body).ok()
+    }
+}
+
+/// Root committing to the reconfigurations scheduled so far, each with the view that
+/// finalized it, in order.
+pub fn schedule_root<H: Hasher>(hasher: &mut H, reconfigurations: &[(u64, Reconfiguration)]) -> Digest {
+    for (finalized, reconfiguration) in reconfigurations {
+        let encoded = reconfiguration.encode();
+        hasher.update(&finalized.to_be_bytes());
+        hasher.update(&(encoded.len() as u32).to_be_bytes());
+        hasher.update(&encoded);
+    }
+    hasher.finalize()
+}
+
+/// Validator set active from `start` (inclusive) until the next epoch starts.
+pub struct Epoch {
+    pub index: u64,
+    pub start: u64,
+    pub identity: poly::Public,
+    pub participants: Vec<PublicKey>,
+    pub participants_map: HashMap<PublicKey, u32>,
+    pub weights: HashMap<PublicKey, u64>,
+    pub quorum_weight: u64,
+    /// Our share of `identity`, none if we are not part of this epoch.
+    pub share: Option<group::Share>,
+    /// View finalizing the reconfiguration the epoch was built from, and the
+    /// reconfiguration, none for genesis.
+    pub reconfiguration: Option<(u64, Reconfiguration)>,
+    /// participant index -> public key of its share, evaluated from `identity` on first use
+    public_shares: RwLock<HashMap<u32, group::Public>>,
+}
+
+impl Epoch {
+    pub fn new(
+        index: u64,
+        start: u64,
+        identity: poly::Public,
+        mut participants: Vec<(PublicKey, u64)>,
+        share: Option<group::Share>,
+    ) -> Result<Self, Error> {
+        if participants.is_empty() {
+            return Err(Error::NoParticipants);
+        }
+
+        // Setup participants
+        participants.sort();
+        let mut participants_map = HashMap::new();
+        let mut weights = HashMap::new();
+        for (index, (validator, weight)) in participants.iter().enumerate() {
+            participants_map.insert(validator.clone(), index as u32);
+            weights.insert(validator.clone(), *weight);
+        }
+        let stakes: Vec<u64> = participants.iter().map(|(_, weight)| *weight).collect();
+        let total_weight = total_weight(&stakes).ok_or(Error::InvalidWeights)?;
+        let quorum_weight = weighted_quorum(total_weight).ok_or(Error::InvalidWeights)?;
+        let participants = participants
+            .into_iter()
+            .map(|(validator, _)| validator)
+            .collect();
+
+        Ok(Self {
+            index,
+            start,
+            identity,
+            participants,
+            participants_map,
+            weights,
+            quorum_weight,
+            share,
+            reconfiguration: None,
+            public_shares: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Build the epoch described by a finalized reconfiguration, picking the share
+    /// (if any) among `shares` that belongs to its group polynomial. Of several shares
+    /// dealt to the same validator, the one at its participant index comes first.
+    pub fn from_reconfiguration(
+        index: u64,
+        finalized: u64,
+        reconfiguration: &Reconfiguration,
+        shares: &[group::Share],
+    ) -> Result<Self, Error> {
+        if reconfiguration.activation < finalized + ACTIVATION_DELAY {
+            return Err(Error::ActivationTooEarly(
+                reconfiguration.activation,
+                finalized + ACTIVATION_DELAY,
+            ));
+        }
+        let participants: Vec<(PublicKey, u64)> = reconfiguration
+            .participants
+            .iter()
+            .map(|(pubkey, weight)| (PublicKey::copy_from_slice(pubkey), *weight))
+            .collect();
+        let weights: Vec<u64> = participants.iter().map(|(_, weight)| *weight).collect();
+        let threshold = weighted_threshold(&weights).ok_or(Error::InvalidWeights)?;
+        let identity: Poly<group::Public> = Poly::deserialize(&reconfiguration.identity, threshold)
+            .ok_or(Error::InvalidIdentity)?;
+        let share = shares
+            .iter()
+            .filter(|share| identity.evaluate(share.index).value == share.public())
+            .min_by_key(|share| share.index)
+            .cloned();
+        let mut epoch = Self::new(
+            index,
+            reconfiguration.activation,
+            identity,
+            participants,
+            share,
+        )?;
+        epoch.reconfiguration = Some((finalized, reconfiguration.clone()));
+        Ok(epoch)
+    }
+
+    /// Public key of the share of participant `index`, which signs its mini-blocks
+    /// when they are attested with BLS.
+    pub fn public_share(&self, index: u32) -> group::Public {
+        if let Some(public) = self.public_shares.read().unwrap().get(&index) {
+            return public.clone();
+        }
+        let public = self.identity.evaluate(index).value;
+        self.public_shares.write().unwrap().insert(index, public.clone());
+        public
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::supervisor::share_indices;
+    use commonware_cryptography::{bls12381::dkg::ops, Ed25519, Scheme};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    /// Reconfiguration to `n` validators of equal stake and the shares dealt to them
+    fn reconfiguration(n: u32, activation: u64) -> (Reconfiguration, Vec<group::Share>) {
+        let participants = (0..n as u64)
+            .map(|seed| (Ed25519::from_seed(100 + seed).public_key().to_vec(), 1))
+            .collect::<Vec<_>>();
+        let threshold = weighted_threshold(&vec![1; n as usize]).unwrap();
+        let (identity, shares) =
+            ops::generate_shares(&mut StdRng::seed_from_u64(n as u64), None, n, threshold);
+        let reconfiguration = Reconfiguration {
+            activation,
+            participants,
+            identity: identity.serialize(),
+        };
+        (reconfiguration, shares)
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let (reconfiguration, _) = reconfiguration(4, 20);
+        let chat = reconfiguration.encode();
+        assert_eq!(Reconfiguration::decode(&chat), Some(reconfiguration));
+        assert_eq!(Reconfiguration::decode(b"hello"), None);
+        assert_eq!(Reconfiguration::decode(RECONFIGURATION_PREFIX), None);
+    }
+
+    #[test]
+    fn test_from_reconfiguration() {
+        let (reconfiguration, shares) = reconfiguration(4, 20);
+        let epoch = Epoch::from_reconfiguration(1, 10, &reconfiguration, &shares[2..3]).unwrap();
+        assert_eq!(epoch.index, 1);
+        assert_eq!(epoch.start, 20);
+        assert_eq!(epoch.participants.len(), 4);
+        assert_eq!(epoch.quorum_weight, 3);
+        assert_eq!(epoch.share.as_ref().map(|share| share.index), Some(2));
+        assert_eq!(epoch.public_share(2), shares[2].public());
+
+        // Shares of another polynomial are not ours
+        let (_, others) = reconfiguration_shares(4);
+        let epoch = Epoch::from_reconfiguration(1, 10, &reconfiguration, &others).unwrap();
+        assert!(epoch.share.is_none());
+    }
+
+    #[test]
+    fn test_from_weighted_reconfiguration() {
+        // The heavier validator holds two of the 7 shares
+        let weights = [2, 1, 1, 1, 1, 1];
+        let participants = (0..weights.len() as u64)
+            .map(|seed| Ed25519::from_seed(100 + seed).public_key())
+            .collect::<Vec<_>>();
+        let mut sorted = participants.clone();
+        sorted.sort();
+        let heavy = sorted.iter().position(|key| *key == participants[0]).unwrap();
+        let mut stakes = vec![1; weights.len()];
+        stakes[heavy] = 2;
+        let indices = share_indices(&stakes).unwrap();
+        let total = indices.iter().map(|owned| owned.len() as u32).sum();
+        let threshold = weighted_threshold(&stakes).unwrap();
+        assert_eq!((total, threshold), (7, 5));
+        let (identity, shares) = ops::generate_shares(&mut StdRng::seed_from_u64(0), None, total, threshold);
+        let reconfiguration = Reconfiguration {
+            activation: 20,
+            participants: participants
+                .iter()
+                .zip(weights)
+                .map(|(key, weight)| (key.to_vec(), weight))
+                .collect(),
+            identity: identity.serialize(),
+        };
+
+        // Consensus signs with the share at the participant index
+        let owned = &indices[heavy];
+        assert_eq!(owned, &vec![heavy as u32, 6]);
+        let ours = vec![shares[6].clone(), shares[heavy].clone()];
+        let epoch = Epoch::from_reconfiguration(1, 10, &reconfiguration, &ours).unwrap();
+        assert_eq!(epoch.share.map(|share| share.index), Some(heavy as u32));
+        assert_eq!(epoch.quorum_weight, 5);
+    }
+
+    fn reconfiguration_shares(n: u32) -> (poly::Public, Vec<group::Share>) {
+        let threshold = weighted_threshold(&vec![1; n as usize]).unwrap();
+        ops::generate_shares(&mut StdRng::seed_from_u64(1000), None, n, threshold)
+    }
+
+    #[test]
+    fn test_from_reconfiguration_invalid() {
+        // Activation must leave time for views in flight
+        let (reconfiguration, _) = reconfiguration(4, 19);
+        assert!(matches!(
+            Epoch::from_reconfiguration(1, 10, &reconfiguration, &[]),
+            Err(Error::ActivationTooEarly(19, 20))
+        ));
+
+        // Stakes must not overflow
+        let (mut overflowing, _) = reconfiguration(4, 20);
+        overflowing.participants[0].1 = u64::MAX;
+        assert!(matches!(
+            Epoch::from_reconfiguration(1, 10, &overflowing, &[]),
+            Err(Error::InvalidWeights)
+        ));
+
+        // The identity must match the threshold of the participants
+        let (mut mismatched, _) = reconfiguration(4, 20);
+        mismatched.participants.truncate(1);
+        assert!(matches!(
+            Epoch::from_reconfiguration(1, 10, &mismatched, &[]),
+            Err(Error::InvalidIdentity)
+        ));
+
+        // Consensus could never gather enough shares from so few validators
+        let (mut skewed, _) = reconfiguration(4, 20);
+        skewed.participants[0].1 = 10;
+        assert!(matches!(
+            Epoch::from_reconfiguration(1, 10, &skewed, &[]),
+            Err(Error::InvalidWeights)
+        ));
+
+        let (mut empty, _) = reconfiguration(4, 20);
+        empty.participants.clear();
+        assert!(Epoch::from_reconfiguration(1, 10, &empty, &[]).is_err());
+    }
+}
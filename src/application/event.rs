@@ -0,0 +1,31 @@
+//! Events pushed to subscribers of the application, such as the api's stream.
+
+use commonware_consensus::threshold_simplex::View;
+use commonware_cryptography::Digest;
+
+use crate::application::mini_block::ProtoBlock;
+
+/// Something that happened at a view, delivered in view order.
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// Views up to `view` were requested but are no longer retained, replay starts after.
+    Pruned { view: View },
+    /// The proto-block with the given root was finalized.
+    Finalized {
+        view: View,
+        root: Digest,
+        proto_block: ProtoBlock,
+        /// Digests of the transactions it executed, forged and replayed ones are left out.
+        executed: Vec<Digest>,
+    },
+    /// The view was nullified, e.g. because the leader withheld a proposal.
+    Nullified { view: View },
+}
+
+impl Event {
+    pub fn view(&self) -> View {
+        match self {
+            Event::Pruned { view } | Event::Finalized { view, .. } | Event::Nullified { view } => *view,
+        }
+    }
+}
@@ -0,0 +1,317 @@
+//! Finalized proto-blocks and state snapshots kept in the storage directory.
+//!
+//! Every finalized proto-block is journaled with its certificate, and every
+//! `snapshot_interval` views a [`Snapshot`] of the state is written next to them once
+//! a finalized proposal commits to it. A restarted validator restores its latest
+//! snapshot and re-executes the proto-blocks finalized after it, instead of replaying
+//! everything from genesis.
+//!
+//! To bound disk usage, only the last `retained_snapshots` snapshots are kept, and
+//! proto-blocks are pruned once they are older than `retention_views` and covered by
+//! a snapshot.
+
+use bytes::Bytes;
+use commonware_consensus::threshold_simplex::View;
+use commonware_runtime::{Blob, Storage};
+use commonware_storage::journal::{self, Journal};
+use futures::{pin_mut, StreamExt};
+use prometheus_client::registry::Registry;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+use tracing::info;
+
+use crate::application::sync::{Finalized, Snapshot};
+
+/// Errors that can occur when opening the history.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("journal failed: {0}")]
+    Journal(#[from] journal::Error),
+    #[error("journal entry of view {0} is corrupt")]
+    Corrupt(View),
+}
+
+/// Configuration for the history.
+pub struct Config {
+    /// Journal partition holding finalized proto-blocks.
+    pub block_partition: String,
+
+    /// Journal partition holding snapshots.
+    pub snapshot_partition: String,
+
+    /// Registry for journal metrics.
+    pub registry: Arc<Mutex<Registry>>,
+
+    /// Number of journal sections replayed at once on startup.
+    pub replay_concurrency: usize,
+
+    /// Finalized views between snapshots.
+    pub snapshot_interval: u64,
+
+    /// Snapshots kept on disk.
+    pub retained_snapshots: usize,
+
+    /// Finalized views whose proto-blocks are kept on disk.
+    pub retention_views: u64,
+}
+
+/// What a restarted validator resumes from.
+pub struct Recovered {
+    /// Latest snapshot, none before the first one.
+    pub snapshot: Option<Snapshot>,
+    /// (parent view, proto-block) finalized after the snapshot, in view order.
+    pub blocks: Vec<(View, Finalized)>,
+}
+
+/// Journals of finalized proto-blocks and snapshots.
+pub struct History<B: Blob, E: Storage<B>> {
+    blocks: Journal<B, E>,
+    snapshots: Journal<B, E>,
+    snapshot_interval: u64,
+    retained_snapshots: usize,
+    retention_views: u64,
+    /// latest journaled proto-block
+    latest: View,
+    /// views of the snapshots on disk, oldest first
+    snapshot_views: VecDeque<View>,
+    /// oldest section not pruned from the block journal
+    pruned: View,
+}
+
+impl<B: Blob, E: Storage<B>> History<B, E> {
+    /// Open the history, returning what was journaled before a restart.
+    pub async fn init(runtime: E, config: Config) -> Result<(Self, Recovered), Error> {
+        let mut blocks = Journal::init(
+            runtime.clone(),
+            journal::Config {
+                registry: config.registry.clone(),
+                partition: config.block_partition,
+            },
+        )
+        .await?;
+        let mut snapshots = Journal::init(
+            runtime,
+            journal::Config {
+                registry: config.registry,
+                partition: config.snapshot_partition,
+            },
+        )
+        .await?;
+
+        // Find the latest snapshot
+        let mut snapshot: Option<Snapshot> = None;
+        let mut snapshot_views = VecDeque::new();
+        {
+            let stream = snapshots.replay(config.replay_concurrency).await?;
+            pin_mut!(stream);
+            while let Some(result) = stream.next().await {
+                let (view, _, _, item) = result?;
+                let item = Snapshot::decode(&item).ok_or(Error::Corrupt(view))?;
+                snapshot_views.push_back(view);
+                if snapshot.as_ref().map_or(true, |latest| latest.view < view) {
+                    snapshot = Some(item);
+                }
+            }
+        }
+        let from = snapshot.as_ref().map_or(0, |snapshot| snapshot.view);
+
+        // Collect the proto-blocks finalized after it
+        let mut recovered = Vec::new();
+        let mut latest = 0;
+        {
+            let stream = blocks.replay(config.replay_concurrency).await?;
+            pin_mut!(stream);
+            while let Some(result) = stream.next().await {
+                let (view, _, _, item) = result?;
+                latest = latest.max(view);
+                if view <= from {
+                    continue;
+                }
+                let block: (View, Finalized) =
+                    serde_json::from_slice(&item).map_err(|_| Error::Corrupt(view))?;
+                recovered.push(block);
+            }
+        }
+        recovered.sort_by_key(|(_, block)| block.view);
+        info!(snapshot = from, blocks = recovered.len(), "recovered history");
+
+        let history = Self {
+            blocks,
+            snapshots,
+            snapshot_interval: config.snapshot_interval,
+            retained_snapshots: config.retained_snapshots,
+            retention_views: config.retention_views,
+            latest,
+            snapshot_views,
+            pruned: 0,
+        };
+        let recovered = Recovered {
+            snapshot,
+            blocks: recovered,
+        };
+        Ok((history, recovered))
+    }
+
+    /// Journal a finalized proto-block, unless it already is.
+    pub async fn append(&mut self, parent: View, block: &Finalized) {
+        if block.view <= self.latest {
+            return;
+        }
+        let item = serde_json::to_vec(&(parent, block)).unwrap();
+        self.blocks
+            .append(block.view, Bytes::from(item))
+            .await
+            .expect("Failed to append finalized proto-block");
+        self.blocks
+            .sync(block.view)
+            .await
+            .expect("Failed to sync finalized proto-block");
+        self.latest = block.view;
+    }
+
+    /// Whether the state after executing `view` should be snapshotted.
+    pub fn snapshot_due(&self, view: View) -> bool {
+        let latest = self.snapshot_views.back().cloned().unwrap_or_default();
+        view >= latest + self.snapshot_interval
+    }
+
+    /// Journal a snapshot, then prune the snapshots and proto-blocks it makes
+    /// unnecessary.
+    pub async fn snapshot(&mut self, snapshot: &Snapshot) {
+        self.snapshots
+            .append(snapshot.view, Bytes::from(snapshot.encode()))
+            .await
+            .expect("Failed to append snapshot");
+        self.snapshots
+            .sync(snapshot.view)
+            .await
+            .expect("Failed to sync snapshot");
+        self.snapshot_views.push_back(snapshot.view);
+        info!(view = snapshot.view, "stored snapshot");
+
+        // Keep the latest snapshots
+        while self.snapshot_views.len() > self.retained_snapshots {
+            self.snapshot_views.pop_front();
+        }
+        let oldest = self.snapshot_views.front().cloned().unwrap_or_default();
+        self.snapshots
+            .prune(oldest)
+            .await
+            .expect("Failed to prune snapshots");
+
+        // Keep the proto-blocks within the retention window (always including those
+        // after the latest snapshot, which restarts re-execute)
+        let prune = snapshot.view.saturating_sub(self.retention_views);
+        if prune > self.pruned {
+            self.blocks
+                .prune(prune)
+                .await
+                .expect("Failed to prune finalized proto-blocks");
+            self.pruned = prune;
+            info!(view = prune, "pruned finalized proto-blocks");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::{merkle, mini_block::ProtoBlock};
+    use commonware_runtime::{deterministic::Executor, Runner};
+
+    fn config(retained_snapshots: usize) -> Config {
+        Config {
+            block_partition: "blocks".to_string(),
+            snapshot_partition: "snapshots".to_string(),
+            registry: Arc::new(Mutex::new(Registry::default())),
+            replay_concurrency: 1,
+            snapshot_interval: 4,
+            retained_snapshots,
+            retention_views: 0,
+        }
+    }
+
+    fn block(view: View) -> Finalized {
+        Finalized {
+            view,
+            finalization: vec![view as u8],
+            proto_block: ProtoBlock::default(),
+        }
+    }
+
+    fn snapshot(view: View) -> Snapshot {
+        Snapshot {
+            view,
+            state_root: vec![view as u8; 32],
+            state: Vec::new(),
+            nonces: Vec::new(),
+            epochs: Vec::new(),
+            committed: view + 1,
+            finalization: Vec::new(),
+            proof: merkle::Proof::default(),
+        }
+    }
+
+    fn views(recovered: &Recovered) -> Vec<(View, View)> {
+        recovered.blocks.iter().map(|(parent, block)| (*parent, block.view)).collect()
+    }
+
+    #[test]
+    fn test_recover() {
+        let (executor, runtime, _) = Executor::default();
+        executor.start(async move {
+            let (mut history, recovered) = History::init(runtime.clone(), config(2)).await.unwrap();
+            assert!(recovered.snapshot.is_none());
+            assert!(recovered.blocks.is_empty());
+
+            // View 3 was nullified, so view 4 builds on view 2
+            for (parent, view) in [(0, 1), (1, 2), (2, 4), (4, 5)] {
+                history.append(parent, &block(view)).await;
+            }
+            // Appending again is a no-op
+            history.append(4, &block(5)).await;
+            assert!(!history.snapshot_due(3));
+            assert!(history.snapshot_due(4));
+
+            // Without a snapshot every proto-block is re-executed
+            drop(history);
+            let (mut history, recovered) = History::init(runtime.clone(), config(2)).await.unwrap();
+            assert!(recovered.snapshot.is_none());
+            assert_eq!(views(&recovered), vec![(0, 1), (1, 2), (2, 4), (4, 5)]);
+
+            // Then only those finalized after the latest snapshot
+            history.snapshot(&snapshot(4)).await;
+            assert!(!history.snapshot_due(7));
+            assert!(history.snapshot_due(8));
+            history.append(5, &block(6)).await;
+            drop(history);
+            let (_, recovered) = History::init(runtime.clone(), config(2)).await.unwrap();
+            let recovered_snapshot = recovered.snapshot.as_ref().unwrap();
+            assert_eq!((recovered_snapshot.view, recovered_snapshot.committed), (4, 5));
+            assert_eq!(views(&recovered), vec![(4, 5), (5, 6)]);
+        });
+    }
+
+    #[test]
+    fn test_retained_snapshots() {
+        let (executor, runtime, _) = Executor::default();
+        executor.start(async move {
+            let (mut history, _) = History::init(runtime.clone(), config(1)).await.unwrap();
+            for view in 1..=9 {
+                history.append(view - 1, &block(view)).await;
+            }
+            history.snapshot(&snapshot(4)).await;
+            history.snapshot(&snapshot(8)).await;
+            drop(history);
+
+            // Only the latest snapshot is kept, and it is the one restored
+            let (history, recovered) = History::init(runtime.clone(), config(1)).await.unwrap();
+            assert_eq!(recovered.snapshot.map(|snapshot| snapshot.view), Some(8));
+            assert_eq!(views(&recovered), vec![(8, 9)]);
+            assert_eq!(history.snapshot_views, VecDeque::from([8]));
+        });
+    }
+}
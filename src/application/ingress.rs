@@ -3,6 +3,7 @@ use commonware_consensus::{
     Automaton as Au, Committer as Co, Proof, Relay as Re,
 };
 use commonware_cryptography::Digest;
+use crate::application::{event::Event, receipt::Receipt, status, sync::Snapshot, transaction::Transaction};
 use futures::{
     channel::{mpsc, oneshot},
     SinkExt,
@@ -32,6 +33,39 @@ pub enum Message {
     Nullify {
         index: View,
     },
+    Broadcast {
+        payload: Digest,
+    },
+    Validate {
+        transaction: Transaction,
+        response: oneshot::Sender<Result<(), String>>,
+    },
+    Query {
+        key: Vec<u8>,
+        response: oneshot::Sender<(View, Vec<u8>, Option<Vec<u8>>)>,
+    },
+    Nonce {
+        sender: Vec<u8>,
+        response: oneshot::Sender<(View, u64)>,
+    },
+    Receipt {
+        chat: Digest,
+        response: oneshot::Sender<Option<Receipt>>,
+    },
+    Snapshot {
+        response: oneshot::Sender<Option<Snapshot>>,
+    },
+    Finalization {
+        view: Option<View>,
+        response: oneshot::Sender<Option<(View, Proof)>>,
+    },
+    Subscribe {
+        from: Option<View>,
+        response: oneshot::Sender<mpsc::Receiver<Event>>,
+    },
+    Status {
+        response: oneshot::Sender<status::Consensus>,
+    },
 }
 
 /// Mailbox for the application.
@@ -44,6 +78,94 @@ impl Mailbox {
     pub(super) fn new(sender: mpsc::Sender<Message>) -> Self {
         Self { sender }
     }
+
+    /// Snapshot of consensus progress, for the api.
+    pub async fn status(&mut self) -> oneshot::Receiver<status::Consensus> {
+        let (response, receiver) = oneshot::channel();
+        self.sender
+            .send(Message::Status { response })
+            .await
+            .expect("Failed to send status");
+        receiver
+    }
+
+    /// Check a transaction against the state machine before it is queued, returning
+    /// the reason it is refused.
+    pub async fn validate(&mut self, transaction: Transaction) -> oneshot::Receiver<Result<(), String>> {
+        let (response, receiver) = oneshot::channel();
+        self.sender
+            .send(Message::Validate { transaction, response })
+            .await
+            .expect("Failed to send validate");
+        receiver
+    }
+
+    /// Value of `key` in the state machine, along with the latest finalized view and
+    /// the state root it was read at.
+    pub async fn query(&mut self, key: Vec<u8>) -> oneshot::Receiver<(View, Vec<u8>, Option<Vec<u8>>)> {
+        let (response, receiver) = oneshot::channel();
+        self.sender
+            .send(Message::Query { key, response })
+            .await
+            .expect("Failed to send query");
+        receiver
+    }
+
+    /// Nonce the next transaction of `sender` must carry, along with the latest
+    /// finalized view it was read at.
+    pub async fn nonce(&mut self, sender: Vec<u8>) -> oneshot::Receiver<(View, u64)> {
+        let (response, receiver) = oneshot::channel();
+        self.sender
+            .send(Message::Nonce { sender, response })
+            .await
+            .expect("Failed to send nonce");
+        receiver
+    }
+
+    /// Inclusion receipt for the chat with the given digest, if it was finalized
+    /// recently enough to be retained.
+    pub async fn receipt(&mut self, chat: Digest) -> oneshot::Receiver<Option<Receipt>> {
+        let (response, receiver) = oneshot::channel();
+        self.sender
+            .send(Message::Receipt { chat, response })
+            .await
+            .expect("Failed to send receipt");
+        receiver
+    }
+
+    /// Snapshot of the state after the latest finalized view, none if the state
+    /// machine does not support them.
+    pub async fn snapshot(&mut self) -> oneshot::Receiver<Option<Snapshot>> {
+        let (response, receiver) = oneshot::channel();
+        self.sender
+            .send(Message::Snapshot { response })
+            .await
+            .expect("Failed to send snapshot");
+        receiver
+    }
+
+    /// Finalization certificate of a retained view, or of the latest finalized view
+    /// when none is given.
+    pub async fn finalization(&mut self, view: Option<View>) -> oneshot::Receiver<Option<(View, Proof)>> {
+        let (response, receiver) = oneshot::channel();
+        self.sender
+            .send(Message::Finalization { view, response })
+            .await
+            .expect("Failed to send finalization");
+        receiver
+    }
+
+    /// Subscribe to finalizations and nullifications. Retained events after `from` are
+    /// replayed first so subscribers can resume without gaps. The subscription is
+    /// dropped if the subscriber falls behind.
+    pub async fn subscribe(&mut self, from: Option<View>) -> oneshot::Receiver<mpsc::Receiver<Event>> {
+        let (response, receiver) = oneshot::channel();
+        self.sender
+            .send(Message::Subscribe { from, response })
+            .await
+            .expect("Failed to send subscribe");
+        receiver
+    }
 }
 
 impl Au for Mailbox {
@@ -96,11 +218,13 @@ impl Au for Mailbox {
 }
 
 impl Re for Mailbox {
-    async fn broadcast(&mut self, _: Digest) {
-        // We don't broadcast our raw messages to other peers.
-        //
-        // If we were building an EVM blockchain, for example, we'd
-        // send the block to other peers here.
+    async fn broadcast(&mut self, payload: Digest) {
+        // Consensus only carries the root, so send the proto-block it commits to
+        // to the other validators.
+        self.sender
+            .send(Message::Broadcast { payload })
+            .await
+            .expect("Failed to send broadcast");
     }
 }
 
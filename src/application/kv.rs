@@ -0,0 +1,494 @@
+//! Replicated key-value store, the second reference [`StateMachine`].
+//!
+//! Transactions carry a JSON [`Operation`]: set a key, delete it, or compare-and-swap
+//! its value. Keys are shared by every user. The writes of each finalized view are
+//! appended to a journal (one section per view) before they are applied, along with
+//! the root they lead to, so a restarted validator rebuilds the store by replaying it.
+//! Views it already executed are skipped when the application re-executes them (or
+//! consensus finalizes them again), answering the root the store had after each of
+//! them. Once a snapshot is stored, the entries are checkpointed and older sections
+//! pruned.
+//!
+//! The state root is the Merkle root over the entries in key order (see [`merkle`]).
+
+use bytes::Bytes;
+use commonware_cryptography::{Digest, Hasher, Sha256};
+use commonware_runtime::{Blob, Storage};
+use commonware_storage::journal::{self, Journal};
+use futures::{pin_mut, StreamExt};
+use prometheus_client::registry::Registry;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+use tracing::info;
+
+use crate::application::merkle;
+use crate::application::state_machine::StateMachine;
+use crate::application::transaction::Transaction;
+
+/// Genesis message to use during initialization.
+const GENESIS: &[u8] = b"little dipper kv";
+
+/// Errors that can occur when opening the store.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("journal failed: {0}")]
+    Journal(#[from] journal::Error),
+    #[error("journal entry of view {0} is corrupt")]
+    Corrupt(u64),
+}
+
+/// Change requested by a transaction.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    Set {
+        key: String,
+        value: String,
+    },
+    Delete {
+        key: String,
+    },
+    /// Set `key` to `value` (or delete it when none) only if its current value is
+    /// `expected` (or it is absent when none).
+    CompareAndSwap {
+        key: String,
+        expected: Option<String>,
+        value: Option<String>,
+    },
+}
+
+impl Operation {
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap()
+    }
+
+    /// Decode a transaction payload, none if it is not an operation.
+    pub fn decode(payload: &[u8]) -> Option<Self> {
+        serde_json::from_slice(payload).ok()
+    }
+
+    fn key(&self) -> &str {
+        match self {
+            Operation::Set { key, .. }
+            | Operation::Delete { key }
+            | Operation::CompareAndSwap { key, .. } => key,
+        }
+    }
+}
+
+/// Writes of an executed view, as journaled.
+#[derive(Serialize, Deserialize)]
+struct Writes {
+    /// drop every entry first, when restoring a snapshot
+    #[serde(default)]
+    reset: bool,
+    /// key -> new value, none when deleted
+    writes: Vec<(String, Option<String>)>,
+    /// root of the entries once the writes are applied
+    #[serde(default)]
+    root: Vec<u8>,
+}
+
+/// Configuration for the store.
+pub struct Config {
+    /// Journal partition holding the writes of every executed view.
+    pub partition: String,
+
+    /// Registry for journal metrics.
+    pub registry: Arc<Mutex<Registry>>,
+
+    /// Number of journal sections replayed at once on startup.
+    pub replay_concurrency: usize,
+}
+
+/// Key-value store backed by a journal.
+pub struct Store<B: Blob, E: Storage<B>> {
+    journal: Journal<B, E>,
+    hasher: Sha256,
+    entries: BTreeMap<String, String>,
+    /// latest executed view
+    executed: u64,
+    root: Digest,
+    /// view -> root after it, for the executed views with writes
+    roots: BTreeMap<u64, Digest>,
+}
+
+impl<B: Blob, E: Storage<B>> Store<B, E> {
+    /// Open the store, replaying the views executed before a restart.
+    pub async fn init(runtime: E, config: Config) -> Result<Self, Error> {
+        let mut journal = Journal::init(
+            runtime,
+            journal::Config {
+                registry: config.registry,
+                partition: config.partition,
+            },
+        )
+        .await?;
+
+        // Collect the writes in view order (sections may be replayed concurrently)
+        let mut views: BTreeMap<u64, Vec<Writes>> = BTreeMap::new();
+        {
+            let stream = journal.replay(config.replay_concurrency).await?;
+            pin_mut!(stream);
+            while let Some(result) = stream.next().await {
+                let (view, _, _, item) = result?;
+                let writes: Writes =
+                    serde_json::from_slice(&item).map_err(|_| Error::Corrupt(view))?;
+                views.entry(view).or_default().push(writes);
+            }
+        }
+
+        // Rebuild the entries and the root after each view
+        let executed = views.keys().last().cloned().unwrap_or_default();
+        let mut store = Self {
+            journal,
+            hasher: Sha256::default(),
+            entries: BTreeMap::new(),
+            executed,
+            root: Digest::default(),
+            roots: BTreeMap::new(),
+        };
+        for (view, section) in views {
+            let mut root = Vec::new();
+            for writes in section {
+                if writes.reset {
+                    store.entries.clear();
+                }
+                for (key, value) in writes.writes {
+                    apply(&mut store.entries, key, value);
+                }
+                root = writes.root;
+            }
+            let root = match root.is_empty() {
+                true => store.root(),
+                false => Digest::from(root),
+            };
+            store.roots.insert(view, root);
+        }
+        store.root = store.root();
+        info!(executed, entries = store.entries.len(), "replayed kv store");
+        Ok(store)
+    }
+
+    /// Latest executed view.
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    /// Journal the writes of a view before they are applied.
+    async fn persist(&mut self, view: u64, writes: Writes) {
+        let item = serde_json::to_vec(&writes).unwrap();
+        self.journal
+            .append(view, Bytes::from(item))
+            .await
+            .expect("Failed to append kv writes");
+        self.journal.sync(view).await.expect("Failed to sync kv writes");
+    }
+
+    /// Merkle root over the entries in key order.
+    fn root(&mut self) -> Digest {
+        entries_root(&mut self.hasher, &self.entries)
+    }
+
+    /// Root after an executed view: the one after the latest view with writes up to
+    /// it, or of the empty store before any.
+    fn root_at(&mut self, view: u64) -> Digest {
+        if let Some((_, root)) = self.roots.range(..=view).next_back() {
+            return root.clone();
+        }
+        merkle::root(&mut self.hasher, &[])
+    }
+
+    /// Check an operation can apply to the given entries.
+    fn check(entries: &BTreeMap<String, String>, operation: &Operation) -> Result<(), String> {
+        if operation.key().is_empty() {
+            return Err("key must not be empty".to_string());
+        }
+        if let Operation::CompareAndSwap { key, expected, .. } = operation {
+            if entries.get(key) != expected.as_ref() {
+                return Err(format!("value of {} does not match the expected one", key));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<B: Blob, E: Storage<B>> StateMachine for Store<B, E> {
+    fn genesis(&mut self) -> Vec<u8> {
+        GENESIS.to_vec()
+    }
+
+    fn validate(&self, transaction: &Transaction) -> Result<(), String> {
+        let operation = Operation::decode(&transaction.payload)
+            .ok_or_else(|| "payload is not a kv operation".to_string())?;
+        Self::check(&self.entries, &operation)
+    }
+
+    async fn execute(&mut self, view: u64, transactions: &[Transaction]) -> Vec<u8> {
+        // Already applied before a restart
+        if view <= self.executed {
+            return self.root_at(view).to_vec();
+        }
+
+        // Collect the writes of valid operations, later ones seeing earlier ones
+        let mut pending = self.entries.clone();
+        let mut writes = Vec::new();
+        for transaction in transactions {
+            let Some(operation) = Operation::decode(&transaction.payload) else {
+                continue;
+            };
+            if Self::check(&pending, &operation).is_err() {
+                continue;
+            }
+            let (key, value) = match operation {
+                Operation::Set { key, value } => (key, Some(value)),
+                Operation::Delete { key } => (key, None),
+                Operation::CompareAndSwap { key, value, .. } => (key, value),
+            };
+            apply(&mut pending, key.clone(), value.clone());
+            writes.push((key, value));
+        }
+
+        // Persist them, with the root they lead to, before applying
+        let root = entries_root(&mut self.hasher, &pending);
+        if !writes.is_empty() {
+            self.persist(view, Writes { reset: false, writes, root: root.to_vec() }).await;
+            self.roots.insert(view, root.clone());
+        }
+        self.entries = pending;
+        self.executed = view;
+        self.root = root;
+        self.root.to_vec()
+    }
+
+    fn snapshot(&self) -> Option<Vec<u8>> {
+        Some(serde_json::to_vec(&self.entries).unwrap())
+    }
+
+    async fn restore(&mut self, view: u64, state: &[u8]) -> Result<Vec<u8>, String> {
+        let entries: BTreeMap<String, String> =
+            serde_json::from_slice(state).map_err(|e| format!("kv state is malformed: {}", e))?;
+        let writes = entries
+            .iter()
+            .map(|(key, value)| (key.clone(), Some(value.clone())))
+            .collect();
+        let root = entries_root(&mut self.hasher, &entries);
+        self.persist(view, Writes { reset: true, writes, root: root.to_vec() }).await;
+        self.entries = entries;
+        self.executed = view;
+        self.root = root;
+
+        // Views after it are executed again
+        self.roots.retain(|executed, _| *executed < view);
+        self.roots.insert(view, self.root.clone());
+        Ok(self.root.to_vec())
+    }
+
+    async fn prune(&mut self, view: u64) {
+        if view > self.executed {
+            return;
+        }
+        // Checkpoint the entries so replay no longer needs the older sections
+        let writes = self
+            .entries
+            .iter()
+            .map(|(key, value)| (key.clone(), Some(value.clone())))
+            .collect();
+        let root = self.root.to_vec();
+        self.persist(self.executed, Writes { reset: true, writes, root }).await;
+        self.journal
+            .prune(view)
+            .await
+            .expect("Failed to prune kv writes");
+
+        // Keep the root views from `view` on may still answer
+        let mut roots = self.roots.split_off(&view);
+        if let Some((before, root)) = self.roots.pop_last() {
+            roots.insert(before, root);
+        }
+        roots.insert(self.executed, self.root.clone());
+        self.roots = roots;
+    }
+
+    fn query(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let key = std::str::from_utf8(key).ok()?;
+        self.entries.get(key).map(|value| value.as_bytes().to_vec())
+    }
+}
+
+/// Merkle root over entries in key order.
+fn entries_root(hasher: &mut Sha256, entries: &BTreeMap<String, String>) -> Digest {
+    let leaves: Vec<Digest> = entries
+        .iter()
+        .map(|(key, value)| merkle::leaf(hasher, &entry_bytes(key, value)))
+        .collect();
+    merkle::root(hasher, &leaves)
+}
+
+fn apply(entries: &mut BTreeMap<String, String>, key: String, value: Option<String>) {
+    match value {
+        Some(value) => entries.insert(key, value),
+        None => entries.remove(&key),
+    };
+}
+
+/// Bytes of an entry hashed into its Merkle leaf, the key is length-prefixed so
+/// entries cannot be confused.
+pub fn entry_bytes(key: &str, value: &str) -> Vec<u8> {
+    let mut v: Vec<u8> = Vec::new();
+    v.extend_from_slice(&(key.len() as u32).to_be_bytes());
+    v.extend_from_slice(key.as_bytes());
+    v.extend_from_slice(value.as_bytes());
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commonware_cryptography::{Ed25519, Scheme};
+    use commonware_runtime::{deterministic::Executor, Runner};
+
+    fn transaction(nonce: u64, operation: Operation) -> Transaction {
+        Transaction::sign(&mut Ed25519::from_seed(0), nonce, 0, operation.encode())
+    }
+
+    fn config() -> Config {
+        Config {
+            partition: "kv".to_string(),
+            registry: Arc::new(Mutex::new(Registry::default())),
+            replay_concurrency: 1,
+        }
+    }
+
+    fn set(key: &str, value: &str) -> Operation {
+        Operation::Set {
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn cas(key: &str, expected: Option<&str>, value: Option<&str>) -> Operation {
+        Operation::CompareAndSwap {
+            key: key.to_string(),
+            expected: expected.map(str::to_string),
+            value: value.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_execute_and_replay() {
+        let (executor, runtime, _) = Executor::default();
+        executor.start(async move {
+            let mut store = Store::init(runtime.clone(), config()).await.unwrap();
+            let empty = store.root.to_vec();
+            let root = store
+                .execute(
+                    1,
+                    &[
+                        transaction(1, set("a", "1")),
+                        transaction(2, set("b", "2")),
+                        transaction(3, cas("a", Some("1"), Some("3"))),
+                        // Sees the swap above, so it does not apply
+                        transaction(4, cas("a", Some("1"), Some("4"))),
+                        transaction(5, Operation::Delete { key: "b".to_string() }),
+                    ],
+                )
+                .await;
+            assert_ne!(root, empty);
+            assert_eq!(store.query(b"a"), Some(b"3".to_vec()));
+            assert_eq!(store.query(b"b"), None);
+            assert!(store.validate(&transaction(6, cas("a", None, Some("5")))).is_err());
+            assert!(store.validate(&transaction(6, cas("b", None, Some("5")))).is_ok());
+
+            // Restarting replays the journal and skips executed views
+            drop(store);
+            let mut store = Store::init(runtime.clone(), config()).await.unwrap();
+            assert_eq!(store.executed(), 1);
+            assert_eq!(store.root.to_vec(), root);
+            assert_eq!(store.execute(1, &[transaction(7, set("c", "1"))]).await, root);
+            assert_eq!(store.query(b"c"), None);
+        });
+    }
+
+    #[test]
+    fn test_replay_roots() {
+        let (executor, runtime, _) = Executor::default();
+        executor.start(async move {
+            let mut store = Store::init(runtime.clone(), config()).await.unwrap();
+            let empty = store.root.to_vec();
+            let first = store.execute(1, &[transaction(1, set("a", "1"))]).await;
+            assert_eq!(store.execute(2, &[]).await, first);
+            let third = store.execute(3, &[transaction(2, set("b", "2"))]).await;
+
+            // Re-executing after a restart answers the root each view led to
+            drop(store);
+            let mut store = Store::init(runtime.clone(), config()).await.unwrap();
+            assert_eq!(store.execute(0, &[]).await, empty);
+            assert_eq!(store.execute(1, &[transaction(1, set("a", "1"))]).await, first);
+            assert_eq!(store.execute(2, &[]).await, first);
+            assert_eq!(store.execute(3, &[transaction(2, set("b", "2"))]).await, third);
+            assert_eq!(store.root.to_vec(), third);
+        });
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let (executor, runtime, _) = Executor::default();
+        executor.start(async move {
+            let mut store = Store::init(runtime.clone(), config()).await.unwrap();
+            let root = store.execute(3, &[transaction(1, set("a", "1"))]).await;
+            let snapshot = store.snapshot().unwrap();
+
+            // Restoring elsewhere yields the same root, and survives a restart
+            let mut restored = Store::init(
+                runtime.clone(),
+                Config {
+                    partition: "restored".to_string(),
+                    ..config()
+                },
+            )
+            .await
+            .unwrap();
+            restored.execute(1, &[transaction(1, set("b", "2"))]).await;
+            assert_eq!(restored.restore(3, &snapshot).await.unwrap(), root);
+            assert_eq!(restored.query(b"b"), None);
+            drop(restored);
+            let restored = Store::init(
+                runtime.clone(),
+                Config {
+                    partition: "restored".to_string(),
+                    ..config()
+                },
+            )
+            .await
+            .unwrap();
+            assert_eq!(restored.executed(), 3);
+            assert_eq!(restored.root.to_vec(), root);
+            assert_eq!(restored.query(b"a"), Some(b"1".to_vec()));
+        });
+    }
+
+    #[test]
+    fn test_prune() {
+        let (executor, runtime, _) = Executor::default();
+        executor.start(async move {
+            let mut store = Store::init(runtime.clone(), config()).await.unwrap();
+            store.execute(1, &[transaction(1, set("a", "1"))]).await;
+            store.execute(2, &[transaction(2, set("b", "2"))]).await;
+            let root = store.execute(3, &[transaction(3, Operation::Delete { key: "a".to_string() })]).await;
+            store.prune(3).await;
+
+            // Replay only needs the checkpoint
+            drop(store);
+            let store = Store::init(runtime.clone(), config()).await.unwrap();
+            assert_eq!(store.executed(), 3);
+            assert_eq!(store.root.to_vec(), root);
+            assert_eq!(store.query(b"a"), None);
+            assert_eq!(store.query(b"b"), Some(b"2".to_vec()));
+        });
+    }
+}
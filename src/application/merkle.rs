@@ -0,0 +1,209 @@
+//! Binary Merkle tree over ordered leaves.
+//!
+//! Leaves and inner nodes are hashed with distinct prefixes so a leaf can never be
+//! passed off as an inner node. A node without a sibling is promoted unchanged to the
+//! next level, so no leaf is ever duplicated.
+
+use commonware_cryptography::{Digest, Hasher};
+use serde::{Deserialize, Serialize};
+
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+/// Path from a leaf to the root.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Proof {
+    /// Position of the leaf.
+    pub index: u32,
+    /// Number of leaves in the tree.
+    pub leaves: u32,
+    /// Siblings from the leaf up, skipping levels where the node was promoted.
+    pub siblings: Vec<Vec<u8>>,
+}
+
+/// Hash data into a leaf.
+pub fn leaf<H: Hasher>(hasher: &mut H, data: &[u8]) -> Digest {
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(data);
+    hasher.finalize()
+}
+
+fn node<H: Hasher>(hasher: &mut H, left: &[u8], right: &[u8]) -> Digest {
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize()
+}
+
+/// Every level of the tree, from the leaves to the root.
+fn levels<H: Hasher>(hasher: &mut H, leaves: &[Digest]) -> Vec<Vec<Digest>> {
+    let mut levels = vec![leaves.to_vec()];
+    while levels.last().unwrap().len() > 1 {
+        let next = levels
+            .last()
+            .unwrap()
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => node(hasher, left, right),
+                [single] => single.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+/// Root over leaves produced by [`leaf`], the hash of nothing when empty.
+pub fn root<H: Hasher>(hasher: &mut H, leaves: &[Digest]) -> Digest {
+    if leaves.is_empty() {
+        return hasher.finalize();
+    }
+    levels(hasher, leaves).pop().unwrap().remove(0)
+}
+
+/// Path from the leaf at `index` to the root.
+pub fn prove<H: Hasher>(hasher: &mut H, leaves: &[Digest], index: usize) -> Option<Proof> {
+    if index >= leaves.len() {
+        return None;
+    }
+    let levels = levels(hasher, leaves);
+    let mut siblings = Vec::new();
+    let mut position = index;
+    for level in levels.iter().take(levels.len() - 1) {
+        if let Some(sibling) = level.get(position ^ 1) {
+            siblings.push(sibling.to_vec());
+        }
+        position /= 2;
+    }
+    Some(Proof {
+        index: index as u32,
+        leaves: leaves.len() as u32,
+        siblings,
+    })
+}
+
+impl Proof {
+    /// Root implied by the proof for `leaf`, none if the proof is malformed.
+    pub fn root<H: Hasher>(&self, hasher: &mut H, leaf: &[u8]) -> Option<Digest> {
+        if self.index >= self.leaves {
+            return None;
+        }
+        let mut digest = Digest::copy_from_slice(leaf);
+        let mut siblings = self.siblings.iter();
+        let mut position = self.index;
+        let mut width = self.leaves;
+        while width > 1 {
+            if position ^ 1 < width {
+                let sibling = siblings.next()?;
+                digest = match position % 2 {
+                    0 => node(hasher, &digest, sibling),
+                    _ => node(hasher, sibling, &digest),
+                };
+            }
+            position /= 2;
+            width = (width + 1) / 2;
+        }
+        if siblings.next().is_some() {
+            return None;
+        }
+        Some(digest)
+    }
+
+    /// Check the proof links `leaf` to `root`.
+    pub fn verify<H: Hasher>(&self, hasher: &mut H, leaf: &[u8], root: &[u8]) -> bool {
+        self.root(hasher, leaf)
+            .map(|computed| computed.as_ref() == root)
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commonware_cryptography::Sha256;
+
+    fn leaves(hasher: &mut Sha256, n: u8) -> Vec<Digest> {
+        (0..n).map(|i| leaf(hasher, &[i])).collect()
+    }
+
+    #[test]
+    fn test_prove_and_verify() {
+        let mut hasher = Sha256::default();
+        for n in 1..=9 {
+            let leaves = leaves(&mut hasher, n);
+            let root = root(&mut hasher, &leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = prove(&mut hasher, &leaves, index).unwrap();
+                assert!(proof.verify(&mut hasher, leaf, &root), "n={} index={}", n, index);
+            }
+            assert!(prove(&mut hasher, &leaves, n as usize).is_none());
+        }
+    }
+
+    #[test]
+    fn test_single_leaf() {
+        let mut hasher = Sha256::default();
+        let leaves = leaves(&mut hasher, 1);
+        let root = root(&mut hasher, &leaves);
+        assert_eq!(root, leaves[0]);
+        let proof = prove(&mut hasher, &leaves, 0).unwrap();
+        assert!(proof.siblings.is_empty());
+        assert!(proof.verify(&mut hasher, &leaves[0], &root));
+    }
+
+    #[test]
+    fn test_odd_leaves() {
+        // The last leaf of 5 is promoted twice and has a single sibling
+        let mut hasher = Sha256::default();
+        let leaves = leaves(&mut hasher, 5);
+        let five = root(&mut hasher, &leaves);
+        let proof = prove(&mut hasher, &leaves, 4).unwrap();
+        assert_eq!(proof.siblings.len(), 1);
+        assert!(proof.verify(&mut hasher, &leaves[4], &five));
+
+        // Promoting rather than duplicating keeps 3 and 4 leaves apart
+        let three = root(&mut hasher, &leaves[..3]);
+        let mut padded = leaves[..3].to_vec();
+        padded.push(leaves[2].clone());
+        assert_ne!(three, root(&mut hasher, &padded));
+    }
+
+    #[test]
+    fn test_tampered_proof() {
+        let mut hasher = Sha256::default();
+        let leaves = leaves(&mut hasher, 6);
+        let root = root(&mut hasher, &leaves);
+        let proof = prove(&mut hasher, &leaves, 3).unwrap();
+
+        // Wrong leaf
+        assert!(!proof.verify(&mut hasher, &leaves[2], &root));
+
+        // Tampered sibling
+        let mut tampered = proof.clone();
+        tampered.siblings[0][0] ^= 1;
+        assert!(!tampered.verify(&mut hasher, &leaves[3], &root));
+
+        // Wrong position
+        let mut moved = proof.clone();
+        moved.index = 2;
+        assert!(!moved.verify(&mut hasher, &leaves[3], &root));
+
+        // Missing or extra siblings
+        let mut short = proof.clone();
+        short.siblings.pop();
+        assert!(short.root(&mut hasher, &leaves[3]).is_none());
+        let mut long = proof.clone();
+        long.siblings.push(leaves[0].to_vec());
+        assert!(long.root(&mut hasher, &leaves[3]).is_none());
+
+        // Index out of the tree
+        let mut outside = proof;
+        outside.index = outside.leaves;
+        assert!(outside.root(&mut hasher, &leaves[3]).is_none());
+
+        // A leaf cannot pose as an inner node
+        let inner = node(&mut hasher, &leaves[0], &leaves[1]);
+        assert_ne!(inner, leaf(&mut hasher, &[leaves[0].as_ref(), leaves[1].as_ref()].concat()));
+    }
+}
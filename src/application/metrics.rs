@@ -0,0 +1,116 @@
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{
+        counter::Counter,
+        family::Family,
+        histogram::{exponential_buckets, Histogram},
+    },
+    registry::Registry,
+};
+use std::sync::{Arc, Mutex};
+
+/// Label identifying a peer by its hex-encoded public key.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct Peer {
+    pub peer: String,
+}
+
+/// Label for the result of verifying a proposal.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct Outcome {
+    pub outcome: String,
+}
+
+/// Label for the reason a transaction was not executed.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct Reason {
+    pub reason: String,
+}
+
+/// Metrics recorded by the application actor.
+#[derive(Clone)]
+pub struct Metrics {
+    /// Seconds between consensus asking for a proposal and the chatter collecting
+    /// sufficient mini-blocks.
+    pub quorum_wait: Histogram,
+    /// Proposals given up on because mini-blocks were insufficient.
+    pub proposals_abandoned: Counter,
+    /// Verifications of proposals by result.
+    pub verify_outcomes: Family<Outcome, Counter>,
+    /// Seconds between proposing or verifying a view and its finalization.
+    pub finalization_latency: Histogram,
+    /// Finalized transactions executed.
+    pub transactions_executed: Counter,
+    /// Finalized transactions skipped as forged, replayed or skipping nonces, by reason.
+    pub transactions_rejected: Family<Reason, Counter>,
+    /// Finalized proto-blocks fetched from peers, by peer.
+    pub blocks_synced: Family<Peer, Counter>,
+    /// Finalized proto-blocks served to peers catching up.
+    pub blocks_served: Counter,
+    /// Warnings that execution is stalled because no peer served the missing views.
+    pub sync_stalled: Counter,
+}
+
+impl Metrics {
+    pub fn init(registry: &Arc<Mutex<Registry>>) -> Self {
+        let metrics = Self {
+            quorum_wait: Histogram::new(exponential_buckets(0.001, 2.0, 16)),
+            proposals_abandoned: Counter::default(),
+            verify_outcomes: Family::default(),
+            finalization_latency: Histogram::new(exponential_buckets(0.01, 2.0, 12)),
+            transactions_executed: Counter::default(),
+            transactions_rejected: Family::default(),
+            blocks_synced: Family::default(),
+            blocks_served: Counter::default(),
+            sync_stalled: Counter::default(),
+        };
+        let mut registry = registry.lock().unwrap();
+        let registry = registry.sub_registry_with_prefix("application");
+        registry.register(
+            "quorum_wait",
+            "seconds waiting for sufficient mini-blocks to propose",
+            metrics.quorum_wait.clone(),
+        );
+        registry.register(
+            "proposals_abandoned",
+            "proposals abandoned due to insufficient mini-blocks",
+            metrics.proposals_abandoned.clone(),
+        );
+        registry.register(
+            "verify_outcomes",
+            "proposal verifications by outcome",
+            metrics.verify_outcomes.clone(),
+        );
+        registry.register(
+            "finalization_latency",
+            "seconds from proposing or verifying a view to its finalization",
+            metrics.finalization_latency.clone(),
+        );
+        registry.register(
+            "transactions_executed",
+            "finalized transactions executed",
+            metrics.transactions_executed.clone(),
+        );
+        registry.register(
+            "transactions_rejected",
+            "finalized transactions skipped by reason",
+            metrics.transactions_rejected.clone(),
+        );
+        registry.register(
+            "blocks_synced",
+            "finalized proto-blocks fetched from peers",
+            metrics.blocks_synced.clone(),
+        );
+        registry.register(
+            "blocks_served",
+            "finalized proto-blocks served to peers",
+            metrics.blocks_served.clone(),
+        );
+        registry.register(
+            "sync_stalled",
+            "sync.attempts requests in a row that no peer served the missing views for",
+            metrics.sync_stalled.clone(),
+        );
+        metrics
+    }
+}
@@ -1,61 +1,477 @@
-use commonware_cryptography::{Ed25519, Scheme, PublicKey, Signature};
+use commonware_cryptography::{bls12381::primitives::group, Digest, Ed25519, Hasher, Scheme, PublicKey, Signature};
 use commonware_consensus::Supervisor;
+use crate::application::{attestation::{self, Attestation}, merkle};
 use crate::application::supervisor::Supervisor as SupervisorImpl;
 use crate::APPLICATION_P2P_NAMESPACE;
+use commonware_utils::union_unique;
+use ed25519_consensus::{batch, VerificationKeyBytes};
+use rand::rngs::OsRng;
+use std::collections::HashSet;
 
+/// Prefix of the state commitment leaf, telling it apart from mini-block headers
+const STATE_PREFIX: &[u8] = b"_STATE";
+
+/// Encoded size of a mini-block besides its chats (view, public key, signature and
+/// field names), plus its share of the attestation
+const MINI_BLOCK_OVERHEAD: usize = 1024;
+
+/// Encoded size of the state commitment and the fixed part of the attestation
+const PROTO_BLOCK_OVERHEAD: usize = 4096;
 
 /// A single mini block from a chatter
 #[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
 pub struct MiniBlock {
     pub view: u64,
-    pub data: Vec<u8>,
+    /// chats drained from the chatter's queue
+    pub chats: Vec<Vec<u8>>,
     pub pubkey: Vec<u8>, // ed25519, not the bls threshold sig
+    /// signature over the header, see [`MiniBlockHeader`]: Ed25519, or BLS until it is
+    /// aggregated into the [`Attestation`] of a proposal (then empty)
     pub sig: Vec<u8>,
 }
 
-/// ProtoBlock is a collections of mini-blocks treated as the content for 
-/// a consensus blok
+/// Summary of a mini-block committing to its chats by their Merkle root. The creator
+/// signs the header rather than the chats, so a single chat can be proven to belong to
+/// a signed mini-block without the others.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MiniBlockHeader {
+    pub view: u64,
+    pub chats_root: Vec<u8>,
+    pub pubkey: Vec<u8>,
+    pub sig: Vec<u8>,
+}
+
+/// ProtoBlock is a collections of mini-blocks treated as the content for
+/// a consensus blok. Consensus agrees on its Merkle root, see [`ProtoBlock::root`].
+///
+/// The proto-block tree has one leaf per mini-block header, in order, and each
+/// header commits to the tree of its chats. The [`Attestation`] of BLS mini-blocks
+/// follows them, and the leader's [`StateCommitment`] is the last leaf:
+///
+/// ```text
+///                    root
+///               /      |      \
+///        header 0  header 1 ... attestation   state
+///           |
+///      chats root
+///       /      \
+///   chat 0    chat 1 ...
+/// ```
 #[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
 pub struct ProtoBlock {
     pub mini_blocks: Vec<MiniBlock>,
+    /// aggregated signatures of the mini-blocks when they are attested with BLS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attestation: Option<Attestation>,
+    /// state after the latest view the leader executed, checked by validators before
+    /// voting so snapshots can be proven against a finalized root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<StateCommitment>,
+}
+
+/// State reached after executing a finalized view
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct StateCommitment {
+    pub view: u64,
+    /// root returned by the state machine
+    pub state_root: Vec<u8>,
+    /// root of the executed nonces, see [`Nonces::root`](crate::application::transaction::Nonces::root)
+    pub nonces_root: Vec<u8>,
+    /// root of the epoch schedule, see [`schedule_root`](crate::application::epoch::schedule_root)
+    pub epochs_root: Vec<u8>,
+}
+
+/// Stake behind the mini-blocks of a proto-block, see [`ProtoBlock::collect`]
+#[derive(Clone, Debug, Default)]
+pub struct Collected {
+    /// creators of valid mini-blocks, each counted once
+    pub participants: HashSet<Vec<u8>>,
+    pub weight: u64,
+    /// creators of mini-blocks with a bad signature or who are not participants
+    pub rejected: Vec<Vec<u8>>,
+}
+
+/// Proof that a chat belongs to a signed mini-block of a proto-block
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ChatProof {
+    pub header: MiniBlockHeader,
+    /// path from the chat to the header's chats root
+    pub chat: merkle::Proof,
+    /// path from the header to the proto-block root
+    pub mini_block: merkle::Proof,
 }
 
 impl MiniBlock {
-    pub fn new(view: u64, data: Vec<u8>, pubkey: Vec<u8>) -> Self {
+    pub fn new(view: u64, chats: Vec<Vec<u8>>, pubkey: Vec<u8>) -> Self {
         Self {
             view: view,
-            data: data,
+            chats: chats,
             pubkey: pubkey,
             sig: vec![],
         }
     }
 
-    pub fn non_sig_bytes(&self) -> Vec<u8> {
-        let mut v: Vec<u8> = Vec::new();
-        v.extend_from_slice(&self.view.to_be_bytes());
-        v.extend_from_slice(&self.data);
-        v.extend_from_slice(&self.pubkey);
-        v
+    /// Header committing to the chats
+    pub fn header<H: Hasher>(&self, hasher: &mut H) -> MiniBlockHeader {
+        MiniBlockHeader {
+            view: self.view,
+            chats_root: self.chats_root(hasher).to_vec(),
+            pubkey: self.pubkey.clone(),
+            sig: self.sig.clone(),
+        }
     }
 
-    pub fn sign(&mut self, crypto: &mut Ed25519) {
+    pub fn sign<H: Hasher>(&mut self, hasher: &mut H, crypto: &mut Ed25519) {
+        let header = self.header(hasher);
         self.sig = crypto.sign(
             Some(APPLICATION_P2P_NAMESPACE),
-            &self.non_sig_bytes(),
+            &header.non_sig_bytes(),
         ).into();
     }
 
+    /// Sign the header with our share of the group key instead, see [`attestation`]
+    pub fn sign_bls<H: Hasher>(&mut self, hasher: &mut H, share: &group::Share) {
+        let header = self.header(hasher);
+        self.sig = attestation::sign(share, &header);
+    }
+
+    pub fn verify<H: Hasher>(&self, hasher: &mut H) -> bool {
+        self.header(hasher).verify()
+    }
+
+    /// Check the signatures of several mini-blocks at once, see [`MiniBlockHeader::verify_batch`]
+    pub fn verify_batch<H: Hasher>(hasher: &mut H, mini_blocks: &[MiniBlock]) -> Vec<bool> {
+        let headers: Vec<MiniBlockHeader> = mini_blocks.iter().map(|mini_block| mini_block.header(hasher)).collect();
+        MiniBlockHeader::verify_batch(&headers)
+    }
+
+    pub fn is_participant(&self, view: u64, supervisor: &SupervisorImpl) -> bool {
+        let pubkey = PublicKey::copy_from_slice(&self.pubkey);
+        supervisor.is_participant(view, &pubkey).is_some()
+    }
+
+    /// Stake of the mini-block creator at the view, none if it is not a participant
+    pub fn weight(&self, view: u64, supervisor: &SupervisorImpl) -> Option<u64> {
+        let pubkey = PublicKey::copy_from_slice(&self.pubkey);
+        supervisor.weight(view, &pubkey)
+    }
+
+    /// Merkle leaves of the chats, in order
+    pub fn chat_leaves<H: Hasher>(&self, hasher: &mut H) -> Vec<Digest> {
+        self.chats.iter().map(|chat| merkle::leaf(hasher, chat)).collect()
+    }
+
+    /// Merkle root over the chats
+    pub fn chats_root<H: Hasher>(&self, hasher: &mut H) -> Digest {
+        let leaves = self.chat_leaves(hasher);
+        merkle::root(hasher, &leaves)
+    }
+
+    /// Merkle leaf committing to the mini-block (and its chats) in the proto-block tree
+    pub fn leaf<H: Hasher>(&self, hasher: &mut H) -> Digest {
+        self.header(hasher).leaf(hasher)
+    }
+}
+
+impl MiniBlockHeader {
+    pub fn non_sig_bytes(&self) -> Vec<u8> {
+        let mut v: Vec<u8> = Vec::new();
+        v.extend_from_slice(&self.view.to_be_bytes());
+        v.extend_from_slice(&self.chats_root);
+        v.extend_from_slice(&self.pubkey);
+        v
+    }
+
+    /// Check the creator signed the header
     pub fn verify(&self) -> bool {
         Ed25519::verify(
             Some(APPLICATION_P2P_NAMESPACE),
-            &self.non_sig_bytes(), 
-            &PublicKey::copy_from_slice(&self.pubkey), 
+            &self.non_sig_bytes(),
+            &PublicKey::copy_from_slice(&self.pubkey),
             &Signature::copy_from_slice(&self.sig),
         )
     }
 
-    pub fn is_participant(&self, view: u64, supervisor: &SupervisorImpl) -> bool {
-        let pubkey = PublicKey::copy_from_slice(&self.pubkey);
-        supervisor.is_participant(view, &pubkey).is_some()
+    /// Check the creators signed the headers, returning which did. A batch costs much
+    /// less than checking each signature, but only tells whether all are valid, so a
+    /// failed batch falls back to checking them one by one.
+    pub fn verify_batch(headers: &[MiniBlockHeader]) -> Vec<bool> {
+        let mut verifier = batch::Verifier::new();
+        let mut well_formed = true;
+        for header in headers {
+            let (Ok(pubkey), Ok(sig)) = (
+                VerificationKeyBytes::try_from(header.pubkey.as_slice()),
+                ed25519_consensus::Signature::try_from(header.sig.as_slice()),
+            ) else {
+                well_formed = false;
+                break;
+            };
+            let message = union_unique(APPLICATION_P2P_NAMESPACE, &header.non_sig_bytes());
+            verifier.queue((pubkey, sig, &message));
+        }
+        if well_formed && verifier.verify(OsRng).is_ok() {
+            return vec![true; headers.len()];
+        }
+        headers.iter().map(|header| header.verify()).collect()
+    }
+
+    /// Merkle leaf of the mini-block in the proto-block tree
+    pub fn leaf<H: Hasher>(&self, hasher: &mut H) -> Digest {
+        let mut v = self.non_sig_bytes();
+        v.extend_from_slice(&self.sig);
+        merkle::leaf(hasher, &v)
+    }
+}
+
+impl StateCommitment {
+    /// Merkle leaf of the commitment in the proto-block tree
+    pub fn leaf<H: Hasher>(&self, hasher: &mut H) -> Digest {
+        let mut v: Vec<u8> = STATE_PREFIX.to_vec();
+        v.extend_from_slice(&self.view.to_be_bytes());
+        v.extend_from_slice(&(self.state_root.len() as u32).to_be_bytes());
+        v.extend_from_slice(&self.state_root);
+        v.extend_from_slice(&self.nonces_root);
+        v.extend_from_slice(&self.epochs_root);
+        merkle::leaf(hasher, &v)
+    }
+}
+
+impl ProtoBlock {
+    /// Merkle leaves of the mini-blocks in order, then of the attestation and the
+    /// state commitment
+    pub fn leaves<H: Hasher>(&self, hasher: &mut H) -> Vec<Digest> {
+        let mut leaves: Vec<Digest> = self.mini_blocks.iter().map(|mini_block| mini_block.leaf(hasher)).collect();
+        if let Some(attestation) = &self.attestation {
+            leaves.push(attestation.leaf(hasher));
+        }
+        if let Some(state) = &self.state {
+            leaves.push(state.leaf(hasher));
+        }
+        leaves
+    }
+
+    /// Largest encoding of a proto-block holding a mini-block from each of
+    /// `participants`, all within the given budgets. Chats are encoded as JSON arrays,
+    /// up to four bytes per byte.
+    pub fn max_encoded_size(participants: usize, mini_block_transactions: usize, mini_block_bytes: usize) -> usize {
+        let chats = mini_block_bytes.saturating_mul(4).saturating_add(mini_block_transactions.saturating_mul(4));
+        participants
+            .saturating_mul(chats.saturating_add(MINI_BLOCK_OVERHEAD))
+            .saturating_add(PROTO_BLOCK_OVERHEAD)
+    }
+
+    /// Merkle root over the mini-blocks, attestation and state, used as the consensus payload
+    pub fn root<H: Hasher>(&self, hasher: &mut H) -> Digest {
+        let leaves = self.leaves(hasher);
+        merkle::root(hasher, &leaves)
+    }
+
+    /// Sum the stake of the participants who signed a mini-block for `view`, skipping
+    /// mini-blocks for other views and creators already counted
+    pub fn collect<H: Hasher>(&self, hasher: &mut H, view: u64, supervisor: &SupervisorImpl) -> Collected {
+        let valid = MiniBlock::verify_batch(hasher, &self.mini_blocks);
+        self.tally(view, supervisor, &valid)
+    }
+
+    /// [`ProtoBlock::collect`] with the signatures already checked, `valid[i]` telling
+    /// whether mini-block `i` is signed by its creator
+    pub fn tally(&self, view: u64, supervisor: &SupervisorImpl, valid: &[bool]) -> Collected {
+        let mut collected = Collected::default();
+        for (mini_block, valid) in self.mini_blocks.iter().zip(valid) {
+            if mini_block.view != view || collected.participants.contains(&mini_block.pubkey) {
+                continue;
+            }
+            match mini_block.weight(view, supervisor) {
+                Some(weight) if *valid => {
+                    collected.weight += weight;
+                    collected.participants.insert(mini_block.pubkey.clone());
+                }
+                _ => collected.rejected.push(mini_block.pubkey.clone()),
+            }
+        }
+        collected
+    }
+
+    /// Path from the mini-block at `index` to the root
+    pub fn prove_mini_block<H: Hasher>(&self, hasher: &mut H, index: usize) -> Option<merkle::Proof> {
+        if index >= self.mini_blocks.len() {
+            return None;
+        }
+        let leaves = self.leaves(hasher);
+        merkle::prove(hasher, &leaves, index)
+    }
+
+    /// Path from the state commitment to the root, the last leaf
+    pub fn prove_state<H: Hasher>(&self, hasher: &mut H) -> Option<merkle::Proof> {
+        self.state.as_ref()?;
+        let leaves = self.leaves(hasher);
+        merkle::prove(hasher, &leaves, leaves.len() - 1)
+    }
+
+    /// Proof that chat `chat` of mini-block `mini_block` belongs to the proto-block
+    pub fn prove_chat<H: Hasher>(&self, hasher: &mut H, mini_block: usize, chat: usize) -> Option<ChatProof> {
+        let mini_block_proof = self.prove_mini_block(hasher, mini_block)?;
+        let mini_block = &self.mini_blocks[mini_block];
+        let leaves = mini_block.chat_leaves(hasher);
+        Some(ChatProof {
+            header: mini_block.header(hasher),
+            chat: merkle::prove(hasher, &leaves, chat)?,
+            mini_block: mini_block_proof,
+        })
+    }
+
+    /// Position (mini-block, chat) of the first chat with the given digest
+    pub fn find_chat<H: Hasher>(&self, hasher: &mut H, digest: &Digest) -> Option<(usize, usize)> {
+        for (index, mini_block) in self.mini_blocks.iter().enumerate() {
+            for (chat_index, chat) in mini_block.chats.iter().enumerate() {
+                if chat_digest(hasher, chat) == *digest {
+                    return Some((index, chat_index));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl ChatProof {
+    /// Check `chat` belongs to the header and the header to the proto-block with the
+    /// given root. The header signature is checked separately with [`MiniBlockHeader::verify`].
+    pub fn verify<H: Hasher>(&self, hasher: &mut H, chat: &[u8], root: &[u8]) -> bool {
+        let chat_leaf = merkle::leaf(hasher, chat);
+        if !self.chat.verify(hasher, &chat_leaf, &self.header.chats_root) {
+            return false;
+        }
+        let header_leaf = self.header.leaf(hasher);
+        self.mini_block.verify(hasher, &header_leaf, root)
+    }
+}
+
+/// Identifier of a chat, returned to users when they submit it
+pub fn chat_digest<H: Hasher>(hasher: &mut H, chat: &[u8]) -> Digest {
+    hasher.update(chat);
+    hasher.finalize()
+}
+
+/// Fixtures shared by the tests of the modules proving chats.
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use commonware_cryptography::Sha256;
+
+    /// Mini-block for view 1, signed by the signer of `seed`
+    pub fn signed(seed: u64, chats: Vec<Vec<u8>>) -> MiniBlock {
+        let mut signer = Ed25519::from_seed(seed);
+        let mut mini_block = MiniBlock::new(1, chats, signer.public_key().to_vec());
+        mini_block.sign(&mut Sha256::default(), &mut signer);
+        mini_block
+    }
+
+    /// Proto-block of `mini_blocks` signed mini-blocks, mini-block `i` holding the
+    /// chats `[i, 0]` to `[i, chats - 1]`
+    pub fn proto_block(mini_blocks: u64, chats: u8) -> ProtoBlock {
+        let mini_blocks = (0..mini_blocks)
+            .map(|seed| signed(seed, (0..chats).map(|chat| vec![seed as u8, chat]).collect()))
+            .collect();
+        ProtoBlock { mini_blocks, attestation: None, state: None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::signed;
+    use super::*;
+    use commonware_cryptography::Sha256;
+
+    #[test]
+    fn test_verify_batch() {
+        let mut hasher = Sha256::default();
+        let mut mini_blocks: Vec<MiniBlock> = (0..4).map(|seed| signed(seed, vec![vec![seed as u8; 32]])).collect();
+        assert_eq!(MiniBlock::verify_batch(&mut hasher, &mini_blocks), vec![true; 4]);
+
+        // A tampered mini-block fails alone
+        mini_blocks[2].chats.push(b"forged".to_vec());
+        assert_eq!(MiniBlock::verify_batch(&mut hasher, &mini_blocks), vec![true, true, false, true]);
+
+        // So does a malformed signature
+        mini_blocks[2] = signed(2, vec![]);
+        mini_blocks[0].sig.truncate(10);
+        assert_eq!(MiniBlock::verify_batch(&mut hasher, &mini_blocks), vec![false, true, true, true]);
+        assert!(MiniBlock::verify_batch(&mut hasher, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_max_encoded_size() {
+        // Chats of a single byte, the worst case for their encoding
+        let transactions = 64;
+        let mini_blocks: Vec<MiniBlock> =
+            (0..4).map(|seed| signed(seed, vec![vec![255]; transactions])).collect();
+        let proto_block = ProtoBlock {
+            mini_blocks,
+            attestation: Some(Attestation { signers: vec![255; 1], signature: vec![255; 96] }),
+            state: Some(StateCommitment {
+                view: u64::MAX,
+                state_root: vec![255; 32],
+                nonces_root: vec![255; 32],
+                epochs_root: vec![255; 32],
+            }),
+        };
+        let encoded = serde_json::to_vec(&proto_block).unwrap().len();
+        assert!(encoded <= ProtoBlock::max_encoded_size(4, transactions, transactions));
+    }
+
+    #[test]
+    fn test_state_commitment() {
+        let mut hasher = Sha256::default();
+        let mut proto_block = ProtoBlock {
+            mini_blocks: (0..3).map(|seed| signed(seed, vec![vec![seed as u8; 32]])).collect(),
+            attestation: None,
+            state: None,
+        };
+        let without = proto_block.root(&mut hasher);
+        assert!(proto_block.prove_state(&mut hasher).is_none());
+
+        // The commitment is the last leaf and changes the root
+        let state = StateCommitment { view: 4, state_root: vec![1; 32], nonces_root: vec![2; 32], epochs_root: vec![3; 32] };
+        proto_block.state = Some(state.clone());
+        let root = proto_block.root(&mut hasher);
+        assert_ne!(root, without);
+        let proof = proto_block.prove_state(&mut hasher).unwrap();
+        assert_eq!((proof.index, proof.leaves), (3, 4));
+        assert!(proof.verify(&mut hasher, &state.leaf(&mut hasher), &root));
+        let other = StateCommitment { view: 5, ..state.clone() };
+        assert!(!proof.verify(&mut hasher, &other.leaf(&mut hasher), &root));
+        let other = StateCommitment { epochs_root: vec![4; 32], ..state };
+        assert!(!proof.verify(&mut hasher, &other.leaf(&mut hasher), &root));
+
+        // Chats are still proven against the root, and no mini-block stands for the state
+        let proof = proto_block.prove_chat(&mut hasher, 2, 0).unwrap();
+        assert!(proof.verify(&mut hasher, &[2; 32], &root));
+        assert!(proto_block.prove_mini_block(&mut hasher, 3).is_none());
+    }
+
+    #[test]
+    fn test_attestation_in_root() {
+        let mut hasher = Sha256::default();
+        let mut proto_block = ProtoBlock {
+            mini_blocks: (0..3).map(|seed| signed(seed, vec![vec![seed as u8; 32]])).collect(),
+            attestation: Some(Attestation { signers: vec![0b111], signature: vec![1; 96] }),
+            state: Some(StateCommitment { view: 4, state_root: vec![1; 32], nonces_root: vec![2; 32], epochs_root: vec![3; 32] }),
+        };
+        let root = proto_block.root(&mut hasher);
+
+        // A copy with another attestation has another root
+        let mut forged = proto_block.clone();
+        forged.attestation = Some(Attestation { signers: vec![0b111], signature: vec![2; 96] });
+        assert_ne!(forged.root(&mut hasher), root);
+        forged.attestation = None;
+        assert_ne!(forged.root(&mut hasher), root);
+
+        // The state is still the last leaf and chats are still proven
+        let proof = proto_block.prove_state(&mut hasher).unwrap();
+        assert_eq!((proof.index, proof.leaves), (4, 5));
+        let chat = proto_block.prove_chat(&mut hasher, 1, 0).unwrap();
+        assert!(chat.verify(&mut hasher, &[1; 32], &root));
+        proto_block.state = None;
+        assert!(proto_block.prove_state(&mut hasher).is_none());
     }
 }
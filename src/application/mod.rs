@@ -2,36 +2,92 @@
 //! This includes things like how to produce/verify blocks and how to identify which
 //! participants are active at a given view.
 
-use commonware_consensus::threshold_simplex::Prover;
 use commonware_cryptography::{
     bls12381::primitives::{group, poly},
     Hasher, PublicKey,
 };
+use commonware_runtime::{Blob, Storage};
+use futures::channel::mpsc;
+use prometheus_client::registry::Registry;
+use state_machine::StateMachine;
+use std::sync::{Arc, Mutex};
 
 mod actor;
 pub use actor::Application;
 mod ingress;
-mod supervisor;
+pub use ingress::Mailbox;
+pub mod attestation;
+pub mod chat;
+pub mod epoch;
+pub mod event;
+pub mod history;
+pub mod kv;
+pub mod merkle;
+pub mod metrics;
+pub mod receipt;
+pub mod state_machine;
+pub mod status;
+pub mod supervisor;
+pub mod sync;
 pub mod chatter;
 pub mod p2p;
 pub mod mini_block;
+pub mod transaction;
 
 /// Configuration for the application.
-pub struct Config<H: Hasher> {
+pub struct Config<B: Blob, E: Storage<B>, H: Hasher, S: StateMachine> {
     /// Hashing scheme to use.
     pub hasher: H,
 
-    /// Prover used to decode opaque proofs from consensus.
-    pub prover: Prover<H>,
+    /// State machine executing finalized transactions.
+    pub state_machine: S,
+
+    /// Namespace of consensus messages, to check finalizations against the group
+    /// key of the epoch they belong to.
+    pub namespace: Vec<u8>,
 
     pub identity: poly::Public,
 
-    /// Participants active in consensus.
-    pub participants: Vec<PublicKey>,
+    /// Participants active in consensus and their stake.
+    pub participants: Vec<(PublicKey, u64)>,
 
     pub share: group::Share,
 
+    /// Shares dealt for future validator sets, matched against the group
+    /// polynomial of each finalized reconfiguration.
+    pub epoch_shares: Vec<group::Share>,
+
+    /// Notified with (epoch, participants) whenever a new validator set is
+    /// scheduled, so the p2p oracle can be updated.
+    pub peer_sets: mpsc::Sender<(u64, Vec<PublicKey>)>,
+
     /// Number of messages from consensus to hold in our backlog
     /// before blocking.
     pub mailbox_size: usize,
+
+    /// Number of finalized proto-blocks kept to answer receipt queries and peers
+    /// catching up.
+    pub retained_blocks: usize,
+
+    /// Snapshot to restore before executing anything, instead of starting from genesis.
+    pub snapshot: Option<sync::Snapshot>,
+
+    /// Finalized proto-blocks and snapshots on disk.
+    pub history: history::History<B, E>,
+
+    /// What the history held on startup, resumed from unless `snapshot` is newer.
+    pub recovered: history::Recovered,
+
+    /// Finalized proto-blocks requested from (and served to) a peer at once.
+    pub sync_batch: usize,
+
+    /// Requests for missing finalized views before giving up on them.
+    pub sync_attempts: usize,
+
+    /// Largest message sent to peers, bounding proposed proto-blocks and the
+    /// finalized proto-blocks served at once.
+    pub max_message_size: usize,
+
+    /// Registry for application metrics.
+    pub registry: Arc<Mutex<Registry>>,
 }
@@ -1,7 +1,8 @@
 use futures::{channel::mpsc ,StreamExt};
 use commonware_consensus::ThresholdSupervisor as TSU;
 use crate::application::supervisor::Supervisor;
-use crate::application::p2p::ingress::{Message, Mailbox};
+use crate::application::p2p::{ingress::{Message, Mailbox}, metrics::Metrics, Config};
+use crate::application::metrics::Peer;
 use crate::application::chatter::ingress::Mailbox as ChatterMailbox;
 use crate::wire;
 
@@ -16,19 +17,22 @@ pub struct Actor {
     control: mpsc::Receiver<Message>,
     chatter_mailbox: ChatterMailbox,
     supervisor: Supervisor,
+    metrics: Metrics,
 }
 
 impl Actor {
     pub fn new(
+        config: Config,
         chatter_mailbox: ChatterMailbox,
         supervisor: Supervisor,
     ) -> (Self, Mailbox) {
-        let (control_sender, control_receiver) = mpsc::channel(100);
+        let (control_sender, control_receiver) = mpsc::channel(config.mailbox_size);
         (
             Self {
                 control: control_receiver,
                 chatter_mailbox: chatter_mailbox,
                 supervisor: supervisor,
+                metrics: Metrics::init(&config.registry),
             },
             Mailbox::new(control_sender),
         )
@@ -61,9 +65,11 @@ impl Actor {
                             }
                             .encode_to_vec();
 
-                            sender.send(Recipients::One(next_leader), inbound_msg.into(), false).await.unwrap();
+                            // only reports the leader if it is connected
+                            let sent = sender.send(Recipients::One(next_leader.clone()), inbound_msg.into(), false).await.unwrap();
+                            self.metrics.mini_blocks_sent.get_or_create(&Peer { peer: hex(&next_leader) }).inc();
 
-                            let _ = response.send(true).map_err(|e| error!("send_mini_bock_to_leader p2p error {}", e));
+                            let _ = response.send(!sent.is_empty()).map_err(|e| error!("send_mini_bock_to_leader p2p error {}", e));
                         }
                     }
                 },
@@ -74,18 +80,27 @@ impl Actor {
                         break;
                     };
 
+                    let peer = Peer { peer: hex(&pubkey) };
                     let msg = match wire::Inbound::decode(msg) {
                         Ok(msg) => msg,
-                        Err(_) => continue,
+                        Err(_) => {
+                            self.metrics.mini_blocks_rejected.get_or_create(&peer).inc();
+                            continue
+                        },
                     };
 
-                    let Some(payload) = msg.payload else {                        
+                    let Some(payload) = msg.payload else {
+                        self.metrics.mini_blocks_rejected.get_or_create(&peer).inc();
                         continue
                     };
 
                     match payload {
                         wire::inbound::Payload::PutMiniBlock(msg) => {
-                            let mini_block = serde_json::from_slice(&msg.data).unwrap();                            
+                            let Ok(mini_block) = serde_json::from_slice(&msg.data) else {
+                                self.metrics.mini_blocks_rejected.get_or_create(&peer).inc();
+                                continue
+                            };
+                            self.metrics.mini_blocks_received.get_or_create(&peer).inc();
                             let response = self.chatter_mailbox.load_mini_block(pubkey, mini_block).await;
                             let _sent = response.await;                            
                         },
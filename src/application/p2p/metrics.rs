@@ -0,0 +1,46 @@
+use prometheus_client::{
+    metrics::{counter::Counter, family::Family},
+    registry::Registry,
+};
+use std::sync::{Arc, Mutex};
+
+use crate::application::metrics::Peer;
+
+/// Metrics recorded by the p2p server.
+#[derive(Clone)]
+pub struct Metrics {
+    /// Mini-blocks sent, by recipient leader.
+    pub mini_blocks_sent: Family<Peer, Counter>,
+    /// Mini-blocks received, by sender.
+    pub mini_blocks_received: Family<Peer, Counter>,
+    /// Messages that could not be decoded, by sender.
+    pub mini_blocks_rejected: Family<Peer, Counter>,
+}
+
+impl Metrics {
+    pub fn init(registry: &Arc<Mutex<Registry>>) -> Self {
+        let metrics = Self {
+            mini_blocks_sent: Family::default(),
+            mini_blocks_received: Family::default(),
+            mini_blocks_rejected: Family::default(),
+        };
+        let mut registry = registry.lock().unwrap();
+        let registry = registry.sub_registry_with_prefix("p2p");
+        registry.register(
+            "mini_blocks_sent",
+            "mini-blocks sent per leader",
+            metrics.mini_blocks_sent.clone(),
+        );
+        registry.register(
+            "mini_blocks_received",
+            "mini-blocks received per peer",
+            metrics.mini_blocks_received.clone(),
+        );
+        registry.register(
+            "mini_blocks_rejected",
+            "undecodable mini-block messages per peer",
+            metrics.mini_blocks_rejected.clone(),
+        );
+        metrics
+    }
+}
@@ -1,2 +1,15 @@
+use prometheus_client::registry::Registry;
+use std::sync::{Arc, Mutex};
+
 pub mod actor;
 pub mod ingress;
+mod metrics;
+
+/// Configuration for the p2p server.
+pub struct Config {
+    /// Number of mini-blocks from the chatter to hold before blocking.
+    pub mailbox_size: usize,
+
+    /// Registry for p2p metrics.
+    pub registry: Arc<Mutex<Registry>>,
+}
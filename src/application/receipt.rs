@@ -0,0 +1,162 @@
+//! Proof that a chat was finalized, verifiable offline with the group public key.
+//!
+//! A receipt carries the finalization certificate of the view, the signed header of
+//! the mini-block holding the chat and the Merkle paths from the chat to the
+//! proto-block root that consensus finalized, so its size only grows logarithmically
+//! with the number of chats and mini-blocks. Users who sent the same chat to several
+//! validators receive a receipt for the first mini-block including it.
+//!
+//! Receipts are only verifiable for Ed25519-signed mini-blocks: the signatures of
+//! BLS-attested ones were aggregated away, so their receipts are refused.
+
+use commonware_consensus::{
+    threshold_simplex::{Prover, View},
+    Proof as Certificate,
+};
+use commonware_cryptography::Hasher;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::application::merkle;
+use crate::application::mini_block::{ChatProof, MiniBlockHeader, ProtoBlock};
+
+/// Reasons a receipt does not prove inclusion.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("finalization certificate is invalid")]
+    InvalidCertificate,
+    #[error("certificate finalizes view {0}, receipt claims view {1}")]
+    ViewMismatch(View, View),
+    #[error("mini-block signature is invalid")]
+    InvalidMiniBlock,
+    #[error("mini-block carries no signature of its creator")]
+    Unsigned,
+    #[error("merkle path does not lead to the finalized root")]
+    InvalidPath,
+}
+
+/// Inclusion receipt for a chat.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Receipt {
+    /// Finalized view including the chat.
+    pub view: View,
+    /// Threshold finalization certificate of `view`.
+    pub finalization: Vec<u8>,
+    /// Signed header of the mini-block including the chat.
+    pub header: MiniBlockHeader,
+    /// Path from the chat to the header's chats root.
+    pub chat_proof: merkle::Proof,
+    /// Path from the header to the proto-block root.
+    pub mini_block_proof: merkle::Proof,
+}
+
+impl Receipt {
+    /// Build the receipt for the chat at (`mini_block`, `chat`) of a finalized proto-block.
+    pub fn new<H: Hasher>(
+        hasher: &mut H,
+        view: View,
+        finalization: Certificate,
+        proto_block: &ProtoBlock,
+        mini_block: usize,
+        chat: usize,
+    ) -> Option<Self> {
+        let proof = proto_block.prove_chat(hasher, mini_block, chat)?;
+        Some(Self {
+            view,
+            finalization: finalization.to_vec(),
+            header: proof.header,
+            chat_proof: proof.chat,
+            mini_block_proof: proof.mini_block,
+        })
+    }
+
+    /// Check the receipt proves `chat` was finalized by the group behind `prover`.
+    ///
+    /// The prover must be built from the group polynomial of the epoch governing
+    /// `view` and the consensus namespace.
+    pub fn verify<H: Hasher>(
+        &self,
+        prover: &Prover<H>,
+        hasher: &mut H,
+        chat: &[u8],
+    ) -> Result<(), Error> {
+        // Ensure the mini-block was signed by its creator
+        if self.header.sig.is_empty() {
+            return Err(Error::Unsigned);
+        }
+        if !self.header.verify() {
+            return Err(Error::InvalidMiniBlock);
+        }
+
+        // Ensure the root was finalized
+        let (view, _, root, _, _) = prover
+            .deserialize_finalization(self.finalization.clone().into())
+            .ok_or(Error::InvalidCertificate)?;
+        if view != self.view {
+            return Err(Error::ViewMismatch(view, self.view));
+        }
+
+        // Ensure the chat leads to the finalized root
+        if !self.inclusion().verify(hasher, chat, &root) {
+            return Err(Error::InvalidPath);
+        }
+        Ok(())
+    }
+
+    /// The inclusion proof the receipt carries, see [`ChatProof`].
+    pub fn inclusion(&self) -> ChatProof {
+        ChatProof {
+            header: self.header.clone(),
+            chat: self.chat_proof.clone(),
+            mini_block: self.mini_block_proof.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::mini_block::testing;
+    use commonware_cryptography::{
+        bls12381::{dkg::ops, primitives::poly},
+        Sha256,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_inclusion() {
+        let mut hasher = Sha256::default();
+        let proto_block = testing::proto_block(3, 5);
+        let root = proto_block.root(&mut hasher);
+
+        let receipt = Receipt::new(&mut hasher, 1, Vec::new().into(), &proto_block, 2, 4).unwrap();
+        assert!(receipt.header.verify());
+        assert_eq!(receipt.header, proto_block.mini_blocks[2].header(&mut hasher));
+        assert!(receipt.inclusion().verify(&mut hasher, &[2, 4], &root));
+        assert!(!receipt.inclusion().verify(&mut hasher, &[2, 3], &root));
+
+        // Out of range chats have no receipt
+        assert!(Receipt::new(&mut hasher, 1, Vec::new().into(), &proto_block, 2, 5).is_none());
+        assert!(Receipt::new(&mut hasher, 1, Vec::new().into(), &proto_block, 3, 0).is_none());
+    }
+
+    #[test]
+    fn test_unsigned_mini_block() {
+        let mut hasher = Sha256::default();
+        let (identity, _) = ops::generate_shares(&mut StdRng::seed_from_u64(0), None, 4, 3);
+        let prover = Prover::new(poly::public(&identity), b"namespace");
+        let mut proto_block = testing::proto_block(3, 5);
+
+        // A signed header gets as far as the certificate
+        let receipt = Receipt::new(&mut hasher, 1, Vec::new().into(), &proto_block, 1, 0).unwrap();
+        assert!(matches!(receipt.verify(&prover, &mut hasher, &[1, 0]), Err(Error::InvalidCertificate)));
+
+        // Headers whose signature went into an attestation, or was tampered with, do not
+        proto_block.mini_blocks[1].sig.clear();
+        let receipt = Receipt::new(&mut hasher, 1, Vec::new().into(), &proto_block, 1, 0).unwrap();
+        assert!(matches!(receipt.verify(&prover, &mut hasher, &[1, 0]), Err(Error::Unsigned)));
+        proto_block.mini_blocks[1].sig = proto_block.mini_blocks[0].sig.clone();
+        let receipt = Receipt::new(&mut hasher, 1, Vec::new().into(), &proto_block, 1, 0).unwrap();
+        assert!(matches!(receipt.verify(&prover, &mut hasher, &[1, 0]), Err(Error::InvalidMiniBlock)));
+    }
+}
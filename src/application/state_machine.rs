@@ -0,0 +1,59 @@
+//! State machines replicated on top of the mini-block consensus.
+//!
+//! Little Dipper takes care of everything up to deciding which transactions execute:
+//! users sign them, validators queue them in mini-blocks, the leader must include
+//! mini-blocks from a stake quorum (so it cannot censor), and once a proto-block is
+//! finalized the forged and replayed transactions are dropped. What executing the
+//! rest means is up to a [`StateMachine`], such as the [`Chat`](crate::application::chat::Chat)
+//! used by default or the key-value [`Store`](crate::application::kv::Store).
+
+use crate::application::transaction::Transaction;
+use std::future::Future;
+
+/// Deterministic state replicated by every validator.
+pub trait StateMachine: Send + 'static {
+    /// Commitment to the initial state, hashed into the genesis payload.
+    fn genesis(&mut self) -> Vec<u8>;
+
+    /// Check a transaction against the current state before it is queued, returning
+    /// the reason it is refused. This only spares mini-block space: a transaction may
+    /// still be executed against a different state, so [`execute`](Self::execute) must
+    /// handle invalid ones too.
+    fn validate(&self, transaction: &Transaction) -> Result<(), String>;
+
+    /// Apply the transactions executed by the proto-block finalized at `view`, in order,
+    /// and return the resulting state root. Every transaction is signed by its sender
+    /// and executed at most once.
+    fn execute(
+        &mut self,
+        view: u64,
+        transactions: &[Transaction],
+    ) -> impl Future<Output = Vec<u8>> + Send;
+
+    /// Value stored under `key`, for state machines exposing their state to the api.
+    fn query(&self, _key: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Encoding of the current state, for state machines validators can be started
+    /// from (see [`Snapshot`](crate::application::sync::Snapshot)).
+    fn snapshot(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Replace the state with a snapshot taken after executing `view`, returning the
+    /// resulting state root.
+    fn restore(
+        &mut self,
+        _view: u64,
+        _state: &[u8],
+    ) -> impl Future<Output = Result<Vec<u8>, String>> + Send {
+        async { Err("snapshots are not supported".to_string()) }
+    }
+
+    /// Drop whatever the state machine persisted that is only needed to rebuild the
+    /// state before `view`, once a snapshot of it is stored.
+    fn prune(&mut self, _view: u64) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+}
@@ -0,0 +1,77 @@
+//! Snapshots of the actors' state, served by the api and rendered by the dashboard.
+
+use serde::{Deserialize, Serialize};
+
+/// Number of views, nullifications and incidents kept for reporting.
+pub const HISTORY: usize = 32;
+
+/// State of a validator.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Status {
+    pub consensus: Consensus,
+    pub chatter: Chatter,
+}
+
+/// Progress of consensus as observed by the application.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Consensus {
+    /// Latest view we were asked to propose, verify or nullify.
+    pub view: u64,
+    /// Hex-encoded leader of `view`.
+    pub leader: String,
+    /// Latest finalized view.
+    pub finalized: u64,
+    /// Hex-encoded state root after executing `finalized`.
+    pub state_root: String,
+    /// Index of the epoch governing `view`.
+    pub epoch: u64,
+    /// Recently nullified views, most recent last.
+    pub nullified: Vec<u64>,
+}
+
+/// Mini-blocks and chats held by the chatter.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Chatter {
+    /// Chats waiting for our next mini-block.
+    pub chat_queue: usize,
+    /// Mini-blocks received from peers for recent views, most recent last.
+    pub views: Vec<View>,
+    /// Mini-block traffic with each participant of the current epoch.
+    pub peers: Vec<Peer>,
+    /// Recent proposals rejected for carrying insufficient mini-blocks, most recent last.
+    pub incidents: Vec<Incident>,
+}
+
+/// Mini-blocks collected for a view.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct View {
+    pub view: u64,
+    pub mini_blocks: usize,
+    /// Stake of the mini-block creators.
+    pub weight: u64,
+    /// Stake required to propose.
+    pub quorum_weight: u64,
+}
+
+/// Mini-block traffic with a participant.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Peer {
+    /// Hex-encoded public key.
+    pub peer: String,
+    pub weight: u64,
+    /// Latest view we received a mini-block for from this peer.
+    pub last_received: Option<u64>,
+    /// Latest view we delivered a mini-block for to this peer (as leader).
+    pub last_delivered: Option<u64>,
+}
+
+/// A leader proposing fewer mini-blocks than a stake quorum, i.e. possibly censoring.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Incident {
+    pub view: u64,
+    /// Hex-encoded leader of `view`.
+    pub leader: String,
+    /// Stake of the valid mini-blocks in the proposal.
+    pub weight: u64,
+    pub quorum_weight: u64,
+}
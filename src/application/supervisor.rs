@@ -8,39 +8,184 @@ use commonware_cryptography::{
     },
     PublicKey,
 };
-use std::collections::HashMap;
+use commonware_utils::quorum;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+use crate::application::epoch::{Epoch, Error as EpochError, Reconfiguration};
+
+/// Total stake of a validator set, none if empty or if it overflows.
+pub fn total_weight(weights: &[u64]) -> Option<u64> {
+    weights
+        .iter()
+        .try_fold(0u64, |total, weight| total.checked_add(*weight))
+        .filter(|total| *total > 0)
+}
+
+/// Minimum stake required for a quorum, mirroring `commonware_utils::quorum`
+/// but counting weight instead of participants.
+pub fn weighted_quorum(total: u64) -> Option<u64> {
+    if total == 0 {
+        return None;
+    }
+    let max_faults = (total - 1) / 3;
+    total.checked_sub(max_faults)
+}
+
+/// Number of share indices dealt to each validator, proportional to its stake.
+///
+/// Stakes are divided by their greatest common divisor, so equal stakes get a single
+/// index each. None if a stake is zero or the total overflows.
+pub fn share_counts(weights: &[u64]) -> Option<Vec<u32>> {
+    if weights.iter().any(|weight| *weight == 0) {
+        return None;
+    }
+    total_weight(weights)?;
+    let divisor = weights.iter().fold(0u64, |divisor, weight| gcd(divisor, *weight));
+    let counts = weights
+        .iter()
+        .map(|weight| u32::try_from(weight / divisor).ok())
+        .collect::<Option<Vec<_>>>()?;
+    counts.iter().try_fold(0u32, |total, count| total.checked_add(*count))?;
+    Some(counts)
+}
+
+/// Share indices of each validator, in participant order.
+///
+/// A validator's first index is its participant index, the one it signs consensus
+/// messages with. Its remaining indices follow those of all participants.
+pub fn share_indices(weights: &[u64]) -> Option<Vec<Vec<u32>>> {
+    let counts = share_counts(weights)?;
+    let mut next = u32::try_from(counts.len()).ok()?;
+    let mut indices = Vec::with_capacity(counts.len());
+    for (index, count) in counts.iter().enumerate() {
+        let mut owned = vec![index as u32];
+        for _ in 1..*count {
+            owned.push(next);
+            next += 1;
+        }
+        indices.push(owned);
+    }
+    Some(indices)
+}
+
+/// Threshold of the BLS shares dealt to a validator set with the given stakes: a
+/// quorum of the [`share_indices`], so any set of shares reaching it holds a stake
+/// quorum.
+///
+/// Consensus takes a single partial signature per validator, from its first index, so
+/// the threshold must not exceed the number of validators. None if it does: such
+/// stakes are too skewed for the size of the set and consensus could never finalize.
+pub fn weighted_threshold(weights: &[u64]) -> Option<u32> {
+    let total = share_counts(weights)?.iter().sum();
+    let threshold = quorum(total)?;
+    if threshold > u32::try_from(weights.len()).ok()? {
+        return None;
+    }
+    Some(threshold)
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
 
 /// Implementation of `commonware-consensus::Supervisor`.
+///
+/// Clones share the same epoch schedule, so a reconfiguration applied by the
+/// application is observed by consensus, the chatter and the p2p actor alike.
 #[derive(Clone)]
 pub struct Supervisor {
-    identity: Poly<group::Public>,
-    participants: Vec<PublicKey>,
-    participants_map: HashMap<PublicKey, u32>,
-
-    share: group::Share,
+    /// start view -> epoch
+    ///
+    /// Consensus borrows the participants, identity and share of an epoch for as long
+    /// as it borrows the supervisor, longer than any lock guard. Epochs are therefore
+    /// leaked when scheduled: there is one per reconfiguration and they are never
+    /// dropped anyway.
+    epochs: Arc<RwLock<BTreeMap<View, &'static Epoch>>>,
+    /// Shares we may receive for future epochs.
+    shares: Arc<Vec<group::Share>>,
 }
 
 impl Supervisor {
     pub fn new(
         identity: Poly<group::Public>,
-        mut participants: Vec<PublicKey>,
+        participants: Vec<(PublicKey, u64)>,
         share: group::Share,
+        epoch_shares: Vec<group::Share>,
     ) -> Self {
-        // Setup participants
-        participants.sort();
-        let mut participants_map = HashMap::new();
-        for (index, validator) in participants.iter().enumerate() {
-            participants_map.insert(validator.clone(), index as u32);
-        }
+        let genesis = Epoch::new(0, 0, identity, participants, Some(share))
+            .expect("genesis validator set not well-formed");
+        let mut epochs = BTreeMap::new();
+        epochs.insert(0, &*Box::leak(Box::new(genesis)));
 
         // Return supervisor
         Self {
-            identity,
-            participants,
-            participants_map,
-            share,
+            epochs: Arc::new(RwLock::new(epochs)),
+            shares: Arc::new(epoch_shares),
         }
     }
+
+    /// Epoch governing the given view.
+    pub fn epoch(&self, view: View) -> &'static Epoch {
+        let epochs = self.epochs.read().unwrap();
+        let (_, epoch) = epochs
+            .range(..=view)
+            .next_back()
+            .expect("genesis epoch starts at view 0");
+        *epoch
+    }
+
+    /// Reconfigurations behind the scheduled epochs, in order, with the view finalizing
+    /// each. Scheduling them again rebuilds the same epochs.
+    pub fn reconfigurations(&self) -> Vec<(View, Reconfiguration)> {
+        let epochs = self.epochs.read().unwrap();
+        epochs
+            .values()
+            .filter_map(|epoch| epoch.reconfiguration.clone())
+            .collect()
+    }
+
+    /// Every scheduled epoch, latest first.
+    pub fn epochs(&self) -> Vec<&'static Epoch> {
+        self.epochs.read().unwrap().values().rev().copied().collect()
+    }
+
+    /// Schedule the validator set described by a reconfiguration finalized at `finalized`,
+    /// returning the new epoch. Reconfigurations activating before an already scheduled
+    /// epoch are ignored.
+    pub fn schedule(
+        &self,
+        finalized: View,
+        reconfiguration: &Reconfiguration,
+    ) -> Result<Option<&'static Epoch>, EpochError> {
+        let mut epochs = self.epochs.write().unwrap();
+        let (latest_start, latest) = epochs.iter().next_back().unwrap();
+        if reconfiguration.activation <= *latest_start {
+            return Ok(None);
+        }
+        let epoch = Epoch::from_reconfiguration(
+            latest.index + 1,
+            finalized,
+            reconfiguration,
+            &self.shares,
+        )?;
+        let epoch = &*Box::leak(Box::new(epoch));
+        epochs.insert(epoch.start, epoch);
+        Ok(Some(epoch))
+    }
+
+    /// Stake of a participant, if it is active at the given view.
+    pub fn weight(&self, view: View, candidate: &PublicKey) -> Option<u64> {
+        self.epoch(view).weights.get(candidate).cloned()
+    }
+
+    /// Stake that must be covered (by mini-blocks or votes) to reach quorum.
+    pub fn quorum_weight(&self, view: View) -> u64 {
+        self.epoch(view).quorum_weight
+    }
 }
 
 impl Su for Supervisor {
@@ -50,12 +195,12 @@ impl Su for Supervisor {
         unimplemented!("only defined in supertrait")
     }
 
-    fn participants(&self, _: Self::Index) -> Option<&Vec<PublicKey>> {
-        Some(&self.participants)
+    fn participants(&self, index: Self::Index) -> Option<&Vec<PublicKey>> {
+        Some(&self.epoch(index).participants)
     }
 
-    fn is_participant(&self, _: Self::Index, candidate: &PublicKey) -> Option<u32> {
-        self.participants_map.get(candidate).cloned()
+    fn is_participant(&self, index: Self::Index, candidate: &PublicKey) -> Option<u32> {
+        self.epoch(index).participants_map.get(candidate).cloned()
     }
 
     async fn report(&self, _: Activity, _: Proof) {
@@ -69,16 +214,125 @@ impl TSu for Supervisor {
     type Identity = poly::Public;
     type Share = group::Share;
 
-    fn leader(&self, _: Self::Index, _: Self::Seed) -> Option<PublicKey> {
-        // fixed leader
-        Some(self.participants[0 as usize].clone())
+    fn leader(&self, index: Self::Index, _: Self::Seed) -> Option<PublicKey> {
+        // fixed leader within an epoch
+        Some(self.epoch(index).participants[0 as usize].clone())
+    }
+
+    fn identity(&self, index: Self::Index) -> Option<&Self::Identity> {
+        Some(&self.epoch(index).identity)
     }
 
-    fn identity(&self, _: Self::Index) -> Option<&Self::Identity> {
-        Some(&self.identity)
+    fn share(&self, index: Self::Index) -> Option<&Self::Share> {
+        self.epoch(index).share.as_ref()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commonware_cryptography::{bls12381::dkg::ops, Ed25519, Scheme};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    /// Validator set of `n` equally weighted validators, derived from `seed`
+    fn validators(n: u32, seed: u64) -> (poly::Public, Vec<(PublicKey, u64)>, Vec<group::Share>) {
+        let participants = (0..n as u64)
+            .map(|index| (Ed25519::from_seed(seed + index).public_key(), 1))
+            .collect();
+        let threshold = weighted_threshold(&vec![1; n as usize]).unwrap();
+        let (identity, shares) = ops::generate_shares(&mut StdRng::seed_from_u64(seed), None, n, threshold);
+        (identity, participants, shares)
+    }
+
+    #[test]
+    fn test_schedule() {
+        let (identity, participants, shares) = validators(4, 0);
+        let (next_identity, next_participants, next_shares) = validators(7, 100);
+        let supervisor = Supervisor::new(identity, participants, shares[0].clone(), vec![next_shares[3].clone()]);
+        let reconfiguration = Reconfiguration {
+            activation: 20,
+            participants: next_participants
+                .iter()
+                .map(|(pubkey, weight)| (pubkey.to_vec(), *weight))
+                .collect(),
+            identity: next_identity.serialize(),
+        };
+
+        // What consensus borrowed outlives later schedules
+        let genesis = supervisor.participants(5).unwrap();
+        let epoch = supervisor.schedule(10, &reconfiguration).unwrap().unwrap();
+        assert_eq!((epoch.index, epoch.start), (1, 20));
+        assert_eq!(genesis.len(), 4);
+        assert_eq!(supervisor.epoch(19).index, 0);
+        assert_eq!(supervisor.epoch(20).index, 1);
+        assert_eq!(supervisor.participants(19).unwrap().len(), 4);
+        assert_eq!(supervisor.participants(25).unwrap().len(), 7);
+        assert_eq!(supervisor.share(25).map(|share| share.index), Some(3));
+        assert_eq!(supervisor.quorum_weight(25), 5);
+
+        // Clones observe the schedule, which only grows
+        let clone = supervisor.clone();
+        assert!(clone.schedule(11, &reconfiguration).unwrap().is_none());
+        let indices: Vec<u64> = clone.epochs().iter().map(|epoch| epoch.index).collect();
+        assert_eq!(indices, vec![1, 0]);
+
+        // Its reconfigurations rebuild it from genesis
+        let (identity, participants, shares) = validators(4, 0);
+        let rebuilt = Supervisor::new(identity, participants, shares[0].clone(), vec![next_shares[3].clone()]);
+        for (finalized, reconfiguration) in supervisor.reconfigurations() {
+            rebuilt.schedule(finalized, &reconfiguration).unwrap().unwrap();
+        }
+        assert_eq!(rebuilt.reconfigurations(), vec![(10, reconfiguration)]);
+        assert_eq!(rebuilt.share(25).map(|share| share.index), Some(3));
+    }
+
+    #[test]
+    fn test_weighted_quorum() {
+        assert_eq!(weighted_quorum(0), None);
+        assert_eq!(weighted_quorum(1), Some(1));
+        assert_eq!(weighted_quorum(4), Some(3));
+        assert_eq!(weighted_quorum(100), Some(67));
+        assert_eq!(weighted_quorum(u64::MAX), Some(u64::MAX - (u64::MAX - 1) / 3));
+    }
+
+    #[test]
+    fn test_total_weight_overflow() {
+        assert_eq!(total_weight(&[]), None);
+        assert_eq!(total_weight(&[0, 0]), None);
+        assert_eq!(total_weight(&[u64::MAX, 1]), None);
+        assert_eq!(total_weight(&[u64::MAX - 1, 1]), Some(u64::MAX));
+        assert_eq!(weighted_threshold(&[u64::MAX, 1]), None);
+    }
+
+    #[test]
+    fn test_weighted_threshold_equal() {
+        assert_eq!(weighted_threshold(&[]), None);
+        assert_eq!(weighted_threshold(&[1]), Some(1));
+        assert_eq!(weighted_threshold(&[1; 4]), Some(3));
+        assert_eq!(weighted_threshold(&[10; 7]), Some(5));
+        assert_eq!(weighted_threshold(&[5; 100]), Some(67));
+    }
+
+    #[test]
+    fn test_share_indices() {
+        assert_eq!(share_counts(&[10, 20, 30]), Some(vec![1, 2, 3]));
+        assert_eq!(share_counts(&[7; 3]), Some(vec![1; 3]));
+        assert_eq!(share_counts(&[1, 0]), None);
+        assert_eq!(share_counts(&[u64::MAX, 1]), None);
+        assert_eq!(
+            share_indices(&[20, 10, 30]),
+            Some(vec![vec![0, 3], vec![1], vec![2, 4, 5]])
+        );
+    }
+
+    #[test]
+    fn test_weighted_threshold_skewed() {
+        // A quorum of the 7 shares, any 5 validators hold at least 5 of them
+        assert_eq!(weighted_threshold(&[2, 1, 1, 1, 1, 1]), Some(5));
+        assert_eq!(weighted_threshold(&[2, 2, 1, 1, 1, 1, 1, 1, 1, 1]), Some(9));
 
-    fn share(&self, _: Self::Index) -> Option<&Self::Share> {
-        Some(&self.share)
+        // Consensus could never gather a quorum of 10 shares from 4 validators
+        assert_eq!(weighted_threshold(&[30, 30, 30, 10]), None);
+        assert_eq!(weighted_threshold(&[1000, 1, 1, 1, 1, 1, 1]), None);
     }
 }
@@ -0,0 +1,253 @@
+//! Catching up on proto-blocks finalized while we were offline or lagging.
+//!
+//! Consensus only agrees on proto-block roots and the proto-blocks themselves are
+//! relayed once, so a validator that missed some cannot execute the views they
+//! finalized. Validators therefore serve their retained finalized proto-blocks, each
+//! with its finalization certificate, to peers asking for the ones after a view. A
+//! certificate is checked against the group key and must finalize the root of the
+//! proto-block it comes with, so peers cannot forge blocks. They could still leave
+//! one out of a range they claim complete (nullified views and notarized ancestors
+//! leave gaps too), so requests go to a random peer each time.
+//!
+//! A gap shows up as a finalized proto-block we never received, or as a finalized view
+//! whose parent is neither executed nor known to us. Later finalized views are held
+//! until the gap is filled, as they must execute in order. A finalized view is never
+//! skipped, as executing the next ones without it would diverge from the other
+//! validators: if no peer fills the gap, requests go on and the validator warns that
+//! its execution is stalled.
+//!
+//! Peers only retain so many blocks, so a validator that was offline for longer starts
+//! from a [`Snapshot`] of the state at a finalized view instead. Every proposal
+//! commits to the state its leader reached (see [`StateCommitment`]), which validators
+//! check before voting, so a snapshot is proven by the certificate of a later view
+//! committing to its state.
+
+use commonware_consensus::threshold_simplex::{Prover, View};
+use commonware_cryptography::Hasher;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::application::epoch::{schedule_root, Reconfiguration};
+use crate::application::merkle;
+use crate::application::mini_block::{ProtoBlock, StateCommitment};
+use crate::application::transaction::Nonces;
+
+/// Encoded size of a [`Finalized`] besides its proto-block, and of the response holding it
+pub const FINALIZED_OVERHEAD: usize = 4096;
+
+/// Reasons a synced block or snapshot is refused.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("finalization certificate is invalid")]
+    InvalidCertificate,
+    #[error("certificate finalizes view {0}, expected view {1}")]
+    ViewMismatch(View, View),
+    #[error("proto-block does not match the root finalized at view {0}")]
+    RootMismatch(View),
+    #[error("snapshot state is not the one committed at view {0}")]
+    InvalidCommitment(View),
+    #[error("state root {0} does not match the snapshot's {1}")]
+    StateRootMismatch(String, String),
+    #[error("state machine cannot restore the snapshot: {0}")]
+    Restore(String),
+}
+
+/// Message exchanged on the sync channel, encoded as JSON.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    /// Ask for at most `limit` finalized proto-blocks after view `from`.
+    Request { from: View, limit: usize },
+    /// Finalized proto-blocks after `from` in view order. No other view in
+    /// `(from, through]` was finalized, as far as the peer knows.
+    Response {
+        from: View,
+        through: View,
+        blocks: Vec<Finalized>,
+    },
+}
+
+impl Message {
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap()
+    }
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        serde_json::from_slice(data).ok()
+    }
+}
+
+/// A finalized proto-block with its certificate.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Finalized {
+    pub view: View,
+    /// Threshold finalization certificate of `view`.
+    pub finalization: Vec<u8>,
+    pub proto_block: ProtoBlock,
+}
+
+impl Finalized {
+    /// Check the certificate finalizes the proto-block at its view, returning the
+    /// view of its parent.
+    pub fn verify<H: Hasher>(&self, prover: &Prover<H>, hasher: &mut H) -> Result<View, Error> {
+        let (view, parent, root, _, _) = prover
+            .deserialize_finalization(self.finalization.clone().into())
+            .ok_or(Error::InvalidCertificate)?;
+        if view != self.view {
+            return Err(Error::ViewMismatch(view, self.view));
+        }
+        if self.proto_block.root(hasher) != root {
+            return Err(Error::RootMismatch(view));
+        }
+        Ok(parent)
+    }
+}
+
+/// State of the application after executing a finalized view, proven by the
+/// certificate of the view whose proto-block commits to it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub view: View,
+    /// State root after executing `view`, recomputed when restoring.
+    pub state_root: Vec<u8>,
+    /// State machine specific encoding of its state.
+    pub state: Vec<u8>,
+    /// Nonce of the last executed transaction of each sender.
+    pub nonces: Vec<(Vec<u8>, u64)>,
+    /// Reconfigurations scheduled up to `view`, with the view finalizing each.
+    pub epochs: Vec<(View, Reconfiguration)>,
+    /// Finalized view whose proto-block commits to the state.
+    pub committed: View,
+    /// Threshold finalization certificate of `committed`.
+    pub finalization: Vec<u8>,
+    /// Path from the state commitment to the root finalized at `committed`.
+    pub proof: merkle::Proof,
+}
+
+impl Snapshot {
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap()
+    }
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        serde_json::from_slice(data).ok()
+    }
+
+    /// State the snapshot claims, as committed to by proposals.
+    pub fn commitment<H: Hasher>(&self, hasher: &mut H) -> StateCommitment {
+        let mut nonces = Nonces::default();
+        for (sender, nonce) in self.nonces.iter() {
+            nonces.update(sender.clone(), *nonce);
+        }
+        StateCommitment {
+            view: self.view,
+            state_root: self.state_root.clone(),
+            nonces_root: nonces.root(hasher).to_vec(),
+            epochs_root: schedule_root(hasher, &self.epochs).to_vec(),
+        }
+    }
+
+    /// Check the certificate finalizes a proto-block committing to the snapshot's
+    /// state and epoch schedule. The state itself is checked against the state root
+    /// once restored.
+    ///
+    /// The prover must be built from a group key the caller trusts, not from the
+    /// snapshot's schedule, which is only proven by this certificate.
+    pub fn verify<H: Hasher>(&self, prover: &Prover<H>, hasher: &mut H) -> Result<(), Error> {
+        let (view, _, root, _, _) = prover
+            .deserialize_finalization(self.finalization.clone().into())
+            .ok_or(Error::InvalidCertificate)?;
+        if view != self.committed {
+            return Err(Error::ViewMismatch(view, self.committed));
+        }
+        self.verify_commitment(hasher, &root)
+    }
+
+    /// Check the snapshot's state is the commitment of the proto-block with `root`.
+    pub fn verify_commitment<H: Hasher>(&self, hasher: &mut H, root: &[u8]) -> Result<(), Error> {
+        // The commitment is the last leaf, so no mini-block can pose as one
+        if self.committed <= self.view || self.proof.index + 1 != self.proof.leaves {
+            return Err(Error::InvalidCommitment(self.committed));
+        }
+        let leaf = self.commitment(hasher).leaf(hasher);
+        if !self.proof.verify(hasher, &leaf, root) {
+            return Err(Error::InvalidCommitment(self.committed));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::mini_block::MiniBlock;
+    use commonware_cryptography::Sha256;
+
+    /// Snapshot of view 3 proven by the proto-block of view 5, with that block's root
+    fn snapshot(hasher: &mut Sha256) -> (Snapshot, Vec<u8>) {
+        let mut snapshot = Snapshot {
+            view: 3,
+            state_root: vec![7; 32],
+            state: b"state".to_vec(),
+            nonces: vec![(vec![1; 32], 0), (vec![2; 32], 4)],
+            epochs: Vec::new(),
+            committed: 5,
+            finalization: Vec::new(),
+            proof: merkle::Proof::default(),
+        };
+        let proto_block = ProtoBlock {
+            mini_blocks: vec![MiniBlock::new(5, vec![b"chat".to_vec()], vec![0; 32]); 2],
+            attestation: None,
+            state: Some(snapshot.commitment(hasher)),
+        };
+        snapshot.proof = proto_block.prove_state(hasher).unwrap();
+        (snapshot, proto_block.root(hasher).to_vec())
+    }
+
+    #[test]
+    fn test_verify_commitment() {
+        let mut hasher = Sha256::default();
+        let (snapshot, root) = snapshot(&mut hasher);
+        assert!(snapshot.verify_commitment(&mut hasher, &root).is_ok());
+
+        // Nonces are committed to, in any order
+        let mut reordered = snapshot.clone();
+        reordered.nonces.reverse();
+        assert!(reordered.verify_commitment(&mut hasher, &root).is_ok());
+        let mut tampered = snapshot.clone();
+        tampered.nonces[1].1 = 5;
+        assert!(matches!(tampered.verify_commitment(&mut hasher, &root), Err(Error::InvalidCommitment(5))));
+
+        // So are the state root and the view
+        let mut tampered = snapshot.clone();
+        tampered.state_root[0] = 0;
+        assert!(tampered.verify_commitment(&mut hasher, &root).is_err());
+        let mut tampered = snapshot.clone();
+        tampered.view = 4;
+        assert!(tampered.verify_commitment(&mut hasher, &root).is_err());
+
+        // And the epoch schedule
+        let mut tampered = snapshot.clone();
+        tampered.epochs.push((
+            1,
+            Reconfiguration { activation: 11, participants: vec![(vec![1; 32], 1)], identity: Vec::new() },
+        ));
+        assert!(tampered.verify_commitment(&mut hasher, &root).is_err());
+    }
+
+    #[test]
+    fn test_commitment_must_be_later_and_last() {
+        let mut hasher = Sha256::default();
+        let (snapshot, root) = snapshot(&mut hasher);
+
+        // A view cannot commit to its own state, it is executed after finalization
+        let mut tampered = snapshot.clone();
+        tampered.committed = tampered.view;
+        assert!(tampered.verify_commitment(&mut hasher, &root).is_err());
+
+        // Only the last leaf is a commitment
+        let mut tampered = snapshot;
+        tampered.proof.index = 0;
+        assert!(tampered.verify_commitment(&mut hasher, &root).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! Chats signed by the user sending them.
+//!
+//! Users sign the payload with their own Ed25519 key so the validators relaying it
+//! cannot alter it, along with a nonce so they cannot replay it. Nonces are
+//! sequential: a sender's first transaction has nonce 0 and a transaction is only
+//! executed if its nonce is the next one of its sender. Once a nonce is executed, every
+//! copy of that transaction (such as the ones a client fanned out to several
+//! validators) is rejected as a replay, and a transaction skipping nonces is rejected
+//! as a gap rather than executed out of order. Mini-blocks of the same proto-block may
+//! hold a sender's transactions in any order, so they are executed in nonce order
+//! (see [`nonce_order`]). The fee is a bid for priority when validators select
+//! transactions with the fee policy, and is only owed by transactions that execute.
+//! Transactions travel as regular chats, encoded as JSON.
+
+use commonware_cryptography::{Digest, Ed25519, Hasher, PublicKey, Scheme, Signature};
+use commonware_utils::union;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+use crate::application::merkle;
+use crate::{APPLICATION_NAMESPACE, TRANSACTION_SUFFIX};
+
+/// Reasons a transaction is not accepted.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum Error {
+    #[error("chat is not a transaction")]
+    Malformed,
+    #[error("signature is invalid")]
+    InvalidSignature,
+    #[error("nonce {0} already used, next nonce is {1}")]
+    Replayed(u64, u64),
+    #[error("nonce {0} skips ahead of the next nonce {1}")]
+    Gap(u64, u64),
+    #[error("transaction is already queued")]
+    Duplicate,
+}
+
+/// A payload signed by its sender.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transaction {
+    /// Ed25519 public key of the sender.
+    pub sender: Vec<u8>,
+    /// Next nonce of the sender, see [`Nonces`].
+    pub nonce: u64,
+    /// Priority bid, see [`FeePriority`](crate::application::chatter::selection::FeePriority).
+    pub fee: u64,
+    pub payload: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl Transaction {
+    pub fn sign(signer: &mut Ed25519, nonce: u64, fee: u64, payload: Vec<u8>) -> Self {
+        let mut transaction = Self {
+            sender: signer.public_key().to_vec(),
+            nonce,
+            fee,
+            payload,
+            signature: Vec::new(),
+        };
+        transaction.signature = signer
+            .sign(Some(&namespace()), &transaction.non_sig_bytes())
+            .to_vec();
+        transaction
+    }
+
+    pub fn non_sig_bytes(&self) -> Vec<u8> {
+        let mut v: Vec<u8> = Vec::new();
+        v.extend_from_slice(&self.sender);
+        v.extend_from_slice(&self.nonce.to_be_bytes());
+        v.extend_from_slice(&self.fee.to_be_bytes());
+        v.extend_from_slice(&self.payload);
+        v
+    }
+
+    /// Check the sender signed the transaction.
+    pub fn verify(&self) -> bool {
+        let sender = PublicKey::copy_from_slice(&self.sender);
+        if !Ed25519::validate(&sender) {
+            return false;
+        }
+        Ed25519::verify(
+            Some(&namespace()),
+            &self.non_sig_bytes(),
+            &sender,
+            &Signature::copy_from_slice(&self.signature),
+        )
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap()
+    }
+
+    /// Decode a chat, none if it is not a transaction.
+    pub fn decode(chat: &[u8]) -> Option<Self> {
+        serde_json::from_slice(chat).ok()
+    }
+
+    /// Decode a chat and check its signature.
+    pub fn decode_verified(chat: &[u8]) -> Result<Self, Error> {
+        let transaction = Self::decode(chat).ok_or(Error::Malformed)?;
+        if !transaction.verify() {
+            return Err(Error::InvalidSignature);
+        }
+        Ok(transaction)
+    }
+}
+
+/// Nonce of the last executed transaction of each sender.
+#[derive(Clone, Debug, Default)]
+pub struct Nonces {
+    executed: HashMap<Vec<u8>, u64>,
+}
+
+impl Nonces {
+    /// Nonce of the last executed transaction from `sender`, if any.
+    pub fn get(&self, sender: &[u8]) -> Option<u64> {
+        self.executed.get(sender).cloned()
+    }
+
+    /// Nonce the next transaction from `sender` must carry.
+    pub fn next(&self, sender: &[u8]) -> u64 {
+        self.get(sender).map_or(0, |executed| executed + 1)
+    }
+
+    /// Check the transaction carries the next nonce of its sender.
+    pub fn check(&self, transaction: &Transaction) -> Result<(), Error> {
+        let next = self.next(&transaction.sender);
+        match transaction.nonce {
+            nonce if nonce < next => Err(Error::Replayed(nonce, next)),
+            nonce if nonce > next => Err(Error::Gap(nonce, next)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record the transaction as executed if it carries the next nonce.
+    pub fn execute(&mut self, transaction: &Transaction) -> Result<(), Error> {
+        self.check(transaction)?;
+        self.update(transaction.sender.clone(), transaction.nonce);
+        Ok(())
+    }
+
+    /// Last executed nonce of every sender, for snapshots.
+    pub fn entries(&self) -> Vec<(Vec<u8>, u64)> {
+        self.executed.iter().map(|(sender, nonce)| (sender.clone(), *nonce)).collect()
+    }
+
+    /// Merkle root over the last executed nonce of every sender, in sender order,
+    /// committed to along with the state root (see [`StateCommitment`]).
+    ///
+    /// [`StateCommitment`]: crate::application::mini_block::StateCommitment
+    pub fn root<H: Hasher>(&self, hasher: &mut H) -> Digest {
+        let mut entries = self.entries();
+        entries.sort();
+        let leaves: Vec<Digest> = entries
+            .iter()
+            .map(|(sender, nonce)| merkle::leaf(hasher, &[sender.as_slice(), &nonce.to_be_bytes()].concat()))
+            .collect();
+        merkle::root(hasher, &leaves)
+    }
+
+    /// Record `nonce` as executed for `sender`, ignoring older nonces.
+    pub fn update(&mut self, sender: Vec<u8>, nonce: u64) {
+        let executed = self.executed.entry(sender).or_insert(nonce);
+        *executed = (*executed).max(nonce);
+    }
+}
+
+/// Order in which to execute `transactions`: each sender keeps the positions its
+/// transactions occupy, filled in nonce order.
+pub fn nonce_order(transactions: &[Transaction]) -> Vec<usize> {
+    let mut lanes: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for (index, transaction) in transactions.iter().enumerate() {
+        lanes.entry(&transaction.sender).or_default().push(index);
+    }
+    let mut order = vec![0; transactions.len()];
+    for positions in lanes.into_values() {
+        let mut sorted = positions.clone();
+        sorted.sort_by_key(|index| transactions[*index].nonce);
+        for (position, index) in positions.into_iter().zip(sorted) {
+            order[position] = index;
+        }
+    }
+    order
+}
+
+fn namespace() -> Vec<u8> {
+    union(APPLICATION_NAMESPACE, TRANSACTION_SUFFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction(sender: u64, nonce: u64) -> Transaction {
+        let mut signer = Ed25519::from_seed(sender);
+        Transaction::sign(&mut signer, nonce, 0, vec![sender as u8])
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let transaction = transaction(1, 0);
+        assert!(transaction.verify());
+        assert_eq!(Transaction::decode_verified(&transaction.encode()), Ok(transaction.clone()));
+
+        let mut forged = transaction;
+        forged.nonce = 1;
+        assert!(!forged.verify());
+        assert_eq!(Transaction::decode_verified(&forged.encode()), Err(Error::InvalidSignature));
+        assert_eq!(Transaction::decode_verified(b"hi"), Err(Error::Malformed));
+    }
+
+    #[test]
+    fn test_sequential_nonces() {
+        let mut nonces = Nonces::default();
+        assert_eq!(nonces.next(&transaction(1, 0).sender), 0);
+        assert_eq!(nonces.execute(&transaction(1, 1)), Err(Error::Gap(1, 0)));
+        assert_eq!(nonces.execute(&transaction(1, 0)), Ok(()));
+        assert_eq!(nonces.execute(&transaction(1, 0)), Err(Error::Replayed(0, 1)));
+        assert_eq!(nonces.execute(&transaction(1, 1)), Ok(()));
+        assert_eq!(nonces.execute(&transaction(1, 3)), Err(Error::Gap(3, 2)));
+
+        // Senders are independent
+        assert_eq!(nonces.execute(&transaction(2, 0)), Ok(()));
+        assert_eq!(nonces.next(&transaction(1, 0).sender), 2);
+    }
+
+    #[test]
+    fn test_nonce_order() {
+        // Sender 1's transactions arrived in reverse, sender 2 keeps its positions
+        let transactions = vec![
+            transaction(1, 2),
+            transaction(2, 0),
+            transaction(1, 1),
+            transaction(1, 0),
+            transaction(2, 1),
+        ];
+        let order = nonce_order(&transactions);
+        assert_eq!(order, vec![3, 1, 2, 0, 4]);
+
+        // Executing in that order leaves nothing behind
+        let mut nonces = Nonces::default();
+        for index in order {
+            nonces.execute(&transactions[index]).unwrap();
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Sign a chat, send it to several validators and wait for its inclusion receipt.
+
+use clap::{value_parser, Arg, ArgAction, Command};
+use commonware_cryptography::{
+    bls12381::primitives::group::{self, Element},
+    Scheme,
+};
+use commonware_utils::{from_hex, hex};
+use little_dipper::{
+    application::{chat::ChatMessage, transaction::Transaction},
+    client::{Client, Config},
+    keys,
+    light_client::LightClient,
+};
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() {
+    // Parse arguments
+    let matches = Command::new("chat")
+        .about("send a signed chat to the validators")
+        .arg(
+            Arg::new("validators")
+                .long("validators")
+                .required(false)
+                .default_value("http://127.0.0.1:4001")
+                .value_delimiter(',')
+                .help("Comma-separated base urls of the validators' api servers"),
+        )
+        .arg(
+            Arg::new("fanout")
+                .long("fanout")
+                .required(false)
+                .default_value("2")
+                .value_parser(value_parser!(usize))
+                .help("Number of validators (picked at random) the chat is sent to"),
+        )
+        .arg(
+            Arg::new("key")
+                .long("key")
+                .required(true)
+                .help("Private key file signing the chat (or a seed with --insecure-seeds)"),
+        )
+        .arg(
+            Arg::new("insecure-seeds")
+                .long("insecure-seeds")
+                .required(false)
+                .action(ArgAction::SetTrue)
+                .help("Derive the key from a seed (local testing only)"),
+        )
+        .arg(
+            Arg::new("nonce")
+                .long("nonce")
+                .required(false)
+                .value_parser(value_parser!(u64))
+                .help("Must follow the nonce of our last executed chat, defaults to the next nonce reported by the validators"),
+        )
+        .arg(
+            Arg::new("fee")
+                .long("fee")
+                .required(false)
+                .default_value("0")
+                .value_parser(value_parser!(u64))
+                .help("Priority bid for validators selecting transactions by fee"),
+        )
+        .arg(Arg::new("room").long("room").required(false).default_value("lobby"))
+        .arg(Arg::new("name").long("name").required(true))
+        .arg(Arg::new("text").long("text").required(true))
+        .arg(
+            Arg::new("public")
+                .long("public")
+                .required(false)
+                .help("Hex-encoded group public key, verifies the receipt when set"),
+        )
+        .arg(
+            Arg::new("timeout-ms")
+                .long("timeout-ms")
+                .required(false)
+                .default_value("30000")
+                .value_parser(value_parser!(u64)),
+        )
+        .get_matches();
+    let validators: Vec<String> = matches
+        .get_many::<String>("validators")
+        .unwrap()
+        .cloned()
+        .collect();
+    let fanout = *matches.get_one::<usize>("fanout").unwrap();
+    let mut signer = keys::load_signer(
+        matches.get_one::<String>("key").unwrap(),
+        matches.get_flag("insecure-seeds"),
+    )
+    .expect("Failed to load key");
+    let light_client = matches.get_one::<String>("public").map(|public| {
+        let public = from_hex(public).expect("Public key not hex");
+        let public = group::Public::deserialize(&public).expect("Public key is invalid");
+        LightClient::new(public, 1)
+    });
+    let fee = *matches.get_one::<u64>("fee").unwrap();
+    let timeout = Duration::from_millis(*matches.get_one::<u64>("timeout-ms").unwrap());
+
+    // Sign the chat
+    let message = ChatMessage {
+        room: matches.get_one::<String>("room").unwrap().clone(),
+        sender: matches.get_one::<String>("name").unwrap().clone(),
+        text: matches.get_one::<String>("text").unwrap().clone(),
+    };
+    let mut client = Client::new(Config {
+        validators,
+        fanout,
+        poll_interval: Duration::from_millis(200),
+        light_client,
+    });
+    let nonce = match matches.get_one::<u64>("nonce") {
+        Some(nonce) => *nonce,
+        None => client
+            .next_nonce(&signer.public_key())
+            .await
+            .expect("Failed to fetch the next nonce"),
+    };
+    let chat = Transaction::sign(&mut signer, nonce, fee, message.encode()).encode();
+
+    // Send it to the validators
+    let submission = client.submit(&chat).await.expect("Failed to submit chat");
+    println!("digest: {}", hex(&submission.digest));
+    for validator in submission.accepted.iter() {
+        println!("accepted: {}", validator);
+    }
+    for (validator, reason) in submission.rejected.iter() {
+        println!("rejected: {} ({})", validator, reason);
+    }
+
+    // Wait for the receipt
+    let inclusion = client
+        .wait(&submission, &chat, timeout)
+        .await
+        .expect("Chat was not included");
+    println!(
+        "included: view={} validator={} latency={:?} verified={}",
+        inclusion.receipt.view, inclusion.validator, inclusion.latency, inclusion.verified
+    );
+}
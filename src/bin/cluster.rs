@@ -0,0 +1,220 @@
+//! Launch a local testnet: generate keys, deal shares, write a config per validator
+//! and run every validator as a child process, streaming their logs until Ctrl-C.
+
+use clap::{value_parser, Arg, ArgAction, Command};
+use commonware_cryptography::{
+    bls12381::{dkg::ops, primitives::group::Element},
+    Scheme,
+};
+use commonware_utils::hex;
+use little_dipper::{application::supervisor::weighted_threshold, config::Config, keys};
+use rand::rngs::OsRng;
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::{Child, Command as Process},
+};
+
+/// Whether `dir` only holds what a previous run wrote: a directory per validator,
+/// named by its index and holding its generated config.
+fn is_cluster(dir: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return false;
+    };
+    entries.into_iter().all(|entry| {
+        let Ok(entry) = entry else {
+            return false;
+        };
+        let path = entry.path();
+        path.is_dir()
+            && entry.file_name().to_str().is_some_and(|name| name.parse::<u32>().is_ok())
+            && path.join("config.json").is_file()
+    })
+}
+
+#[tokio::main]
+async fn main() {
+    // Parse arguments
+    let matches = Command::new("cluster")
+        .about("run a local testnet")
+        .arg(
+            Arg::new("validators")
+                .long("validators")
+                .required(false)
+                .default_value("4")
+                .value_parser(value_parser!(u32)),
+        )
+        .arg(
+            Arg::new("base-port")
+                .long("base-port")
+                .required(false)
+                .default_value("3001")
+                .value_parser(value_parser!(u16)),
+        )
+        .arg(
+            Arg::new("api-base-port")
+                .long("api-base-port")
+                .required(false)
+                .default_value("4001")
+                .value_parser(value_parser!(u16))
+                .help("Port of the first validator's api server (metrics, status for `dashboard`)"),
+        )
+        .arg(
+            Arg::new("dir")
+                .long("dir")
+                .required(false)
+                .default_value("/tmp/little-dipper")
+                .help("Directory holding every validator's keys, config and storage (wiped on start if it holds a previous cluster)"),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .required(false)
+                .action(ArgAction::SetTrue)
+                .help("Wipe --dir even if it does not look like a previous cluster"),
+        )
+        .arg(
+            Arg::new("validator-bin")
+                .long("validator-bin")
+                .required(false)
+                .help("Path to the validator binary (defaults to the one next to this binary)"),
+        )
+        .get_matches();
+    let n = *matches.get_one::<u32>("validators").unwrap();
+    if n == 0 {
+        panic!("Please run at least one validator");
+    }
+    let base_port = *matches.get_one::<u16>("base-port").unwrap();
+    let api_base_port = *matches.get_one::<u16>("api-base-port").unwrap();
+    let dir = PathBuf::from(matches.get_one::<String>("dir").unwrap());
+    let force = matches.get_flag("force");
+    let validator_bin = match matches.get_one::<String>("validator-bin") {
+        Some(path) => PathBuf::from(path),
+        None => std::env::current_exe()
+            .expect("Failed to locate current binary")
+            .with_file_name("validator"),
+    };
+
+    // Start from a clean directory, never wiping one we did not create
+    if dir.exists() {
+        if !force && !is_cluster(&dir) {
+            panic!(
+                "{} does not look like a previous cluster, pick another --dir or pass --force to wipe it",
+                dir.display()
+            );
+        }
+        fs::remove_dir_all(&dir).expect("Failed to remove previous cluster");
+    }
+
+    // Generate identities
+    let mut nodes = Vec::new();
+    for index in 0..n {
+        let node_dir = dir.join(index.to_string());
+        fs::create_dir_all(&node_dir).expect("Failed to create node directory");
+        let signer = keys::generate_signer();
+        let key = node_dir.join("key");
+        keys::write_signer(&key, &signer).expect("Failed to write key");
+        let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), base_port + index as u16);
+        nodes.push((node_dir, key, signer.public_key(), address));
+    }
+
+    // Deal shares, indexed by sorted public key like the supervisor
+    let mut sorted: Vec<_> = nodes.iter().map(|(_, _, public_key, _)| public_key.clone()).collect();
+    sorted.sort();
+    let t = weighted_threshold(&vec![1; n as usize]).expect("unable to compute threshold");
+    let (polynomial, shares) = ops::generate_shares(&mut OsRng, None, n, t);
+    let polynomial = hex(&polynomial.serialize());
+    let participants: Vec<String> = sorted.iter().map(|public_key| hex(public_key)).collect();
+
+    // Write configs
+    let (_, _, bootstrapper_key, bootstrapper_address) = &nodes[0];
+    let bootstrapper = format!("{}@{}", hex(bootstrapper_key), bootstrapper_address);
+    let mut configs = Vec::new();
+    for (index, (node_dir, key, public_key, address)) in nodes.iter().enumerate() {
+        let share_index = sorted.iter().position(|p| p == public_key).unwrap();
+        let share = node_dir.join("share");
+        keys::write_shares(&share, &shares[share_index..=share_index]).expect("Failed to write share");
+
+        let mut config = Config::default();
+        config.identity.key = Some(key.display().to_string());
+        config.identity.share = Some(share.display().to_string());
+        config.identity.polynomial = Some(polynomial.clone());
+        config.identity.participants = participants.clone();
+        config.network.listen = Some(*address);
+        config.api.listen = Some(SocketAddr::new(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            api_base_port + index as u16,
+        ));
+        if index != 0 {
+            config.network.bootstrappers = vec![bootstrapper.clone()];
+        }
+        config.storage.directory = Some(node_dir.join("storage").display().to_string());
+        config.validate().expect("generated config is invalid");
+
+        let path = node_dir.join("config.json");
+        fs::write(&path, serde_json::to_vec_pretty(&config).unwrap())
+            .expect("Failed to write config");
+        configs.push(path);
+    }
+    println!("wrote configs for {} validators to {}", n, dir.display());
+    println!(
+        "api servers on ports {}..{}, monitor with `dashboard --api http://127.0.0.1:{}`",
+        api_base_port,
+        api_base_port + n as u16 - 1,
+        api_base_port
+    );
+
+    // Launch validators
+    let mut children: Vec<Child> = Vec::new();
+    for (index, config) in configs.iter().enumerate() {
+        let mut child = Process::new(&validator_bin)
+            .arg("--config")
+            .arg(config)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .expect("Failed to launch validator");
+
+        // Stream logs prefixed by validator
+        let stdout = child.stdout.take().unwrap();
+        let stderr = child.stderr.take().unwrap();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                println!("[validator {}] {}", index, line);
+            }
+        });
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                eprintln!("[validator {}] {}", index, line);
+            }
+        });
+        children.push(child);
+    }
+
+    // Run until interrupted or any validator exits
+    let exited = async {
+        let waits = children.iter_mut().map(|child| Box::pin(child.wait()));
+        futures::future::select_all(waits).await
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {
+            println!("shutting down cluster");
+        },
+        (status, index, _) = exited => {
+            println!("validator {} exited ({:?}), shutting down cluster", index, status);
+        },
+    }
+
+    // Tear down
+    for child in children.iter_mut() {
+        let _ = child.kill().await;
+    }
+}
@@ -0,0 +1,237 @@
+//! Terminal dashboard polling a validator's api (`api.listen` in its config) and
+//! rendering consensus progress, mini-block collection, peers and censorship
+//! incidents until `q` is pressed.
+
+use clap::{value_parser, Arg, Command};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use little_dipper::application::status::Status;
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, List, ListItem, Paragraph, Row, Table},
+    Frame, Terminal,
+};
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+/// Views without mini-block traffic after which a peer is shown as stale.
+const STALE_VIEWS: u64 = 10;
+
+fn main() -> io::Result<()> {
+    // Parse arguments
+    let matches = Command::new("dashboard")
+        .about("monitor a running validator")
+        .arg(
+            Arg::new("api")
+                .long("api")
+                .required(false)
+                .default_value("http://127.0.0.1:4001")
+                .help("Address of the validator's api server"),
+        )
+        .arg(
+            Arg::new("refresh-ms")
+                .long("refresh-ms")
+                .required(false)
+                .default_value("1000")
+                .value_parser(value_parser!(u64)),
+        )
+        .get_matches();
+    let url = format!(
+        "{}/status",
+        matches.get_one::<String>("api").unwrap().trim_end_matches('/')
+    );
+    let refresh = Duration::from_millis(*matches.get_one::<u64>("refresh-ms").unwrap());
+    let client = reqwest::blocking::Client::builder()
+        .timeout(refresh)
+        .build()
+        .expect("Failed to create http client");
+
+    // Take over the terminal
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+
+    // Refresh until interrupted
+    let result = (|| -> io::Result<()> {
+        loop {
+            let status = client
+                .get(&url)
+                .send()
+                .and_then(|response| response.error_for_status())
+                .and_then(|response| response.json::<Status>())
+                .map_err(|e| e.to_string());
+            terminal.draw(|frame| draw(frame, &url, &status))?;
+
+            // Wait for the next refresh, exiting on `q` or Esc
+            let deadline = Instant::now() + refresh;
+            while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+                if !event::poll(timeout)? {
+                    break;
+                }
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press
+                        && matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+                    {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    })();
+
+    // Restore the terminal
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen)?;
+    result
+}
+
+fn draw(frame: &mut Frame, url: &str, status: &Result<Status, String>) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Min(8),
+            Constraint::Length(10),
+            Constraint::Length(1),
+        ])
+        .split(frame.area());
+    let footer = match status {
+        Ok(_) => format!("{}  (q to quit)", url),
+        Err(e) => format!("{}: {}  (q to quit)", url, e),
+    };
+    frame.render_widget(Paragraph::new(footer), rows[3]);
+    let Ok(status) = status else {
+        return;
+    };
+    let consensus = &status.consensus;
+    let chatter = &status.chatter;
+
+    // Consensus progress
+    let summary = vec![
+        Line::from(format!(
+            "view {}   finalized {}   epoch {}   leader {}",
+            consensus.view,
+            consensus.finalized,
+            consensus.epoch,
+            short(&consensus.leader)
+        )),
+        Line::from(format!(
+            "chat queue {}   nullified {}",
+            chatter.chat_queue,
+            consensus.nullified.len()
+        )),
+    ];
+    frame.render_widget(
+        Paragraph::new(summary).block(Block::default().borders(Borders::ALL).title("consensus")),
+        rows[0],
+    );
+
+    // Mini-blocks per view and peers
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(45), Constraint::Percentage(55)])
+        .split(rows[1]);
+    let views = chatter.views.iter().rev().map(|view| {
+        let style = match view.weight >= view.quorum_weight {
+            true => Style::default().fg(Color::Green),
+            false => Style::default().fg(Color::Yellow),
+        };
+        Row::new(vec![
+            view.view.to_string(),
+            view.mini_blocks.to_string(),
+            format!("{}/{}", view.weight, view.quorum_weight),
+        ])
+        .style(style)
+    });
+    let views = Table::new(views, [Constraint::Length(10), Constraint::Length(12), Constraint::Min(10)])
+        .header(header(["view", "mini-blocks", "stake/quorum"]))
+        .block(Block::default().borders(Borders::ALL).title("mini-blocks received"));
+    frame.render_widget(views, columns[0]);
+
+    let peers = chatter.peers.iter().map(|peer| {
+        let last = peer.last_received.max(peer.last_delivered);
+        let (state, color) = match last {
+            Some(last) if last + STALE_VIEWS >= consensus.view => ("active", Color::Green),
+            Some(_) => ("stale", Color::Yellow),
+            None => ("silent", Color::Red),
+        };
+        Row::new(vec![
+            short(&peer.peer),
+            peer.weight.to_string(),
+            view_or_dash(peer.last_received),
+            view_or_dash(peer.last_delivered),
+            state.to_string(),
+        ])
+        .style(Style::default().fg(color))
+    });
+    let peers = Table::new(
+        peers,
+        [
+            Constraint::Length(18),
+            Constraint::Length(8),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Min(8),
+        ],
+    )
+    .header(header(["peer", "stake", "received", "delivered", "state"]))
+    .block(Block::default().borders(Borders::ALL).title("peers"));
+    frame.render_widget(peers, columns[1]);
+
+    // Censorship incidents and nullified views, most recent first
+    let mut incidents: Vec<(u64, String)> = chatter
+        .incidents
+        .iter()
+        .map(|incident| {
+            (
+                incident.view,
+                format!(
+                    "view {}: leader {} proposed mini-blocks holding {}/{} stake",
+                    incident.view,
+                    short(&incident.leader),
+                    incident.weight,
+                    incident.quorum_weight
+                ),
+            )
+        })
+        .chain(
+            consensus
+                .nullified
+                .iter()
+                .map(|view| (*view, format!("view {}: nullified", view))),
+        )
+        .collect();
+    incidents.sort_by(|a, b| b.0.cmp(&a.0));
+    let incidents: Vec<ListItem> = incidents
+        .into_iter()
+        .map(|(_, incident)| ListItem::new(incident))
+        .collect();
+    frame.render_widget(
+        List::new(incidents).block(Block::default().borders(Borders::ALL).title("incidents")),
+        rows[2],
+    );
+}
+
+fn header<const N: usize>(titles: [&'static str; N]) -> Row<'static> {
+    Row::new(titles).style(Style::default().add_modifier(Modifier::BOLD))
+}
+
+/// Abbreviate a hex-encoded public key.
+fn short(key: &str) -> String {
+    match key.len() > 16 {
+        true => format!("{}..", &key[..16]),
+        false => key.to_string(),
+    }
+}
+
+fn view_or_dash(view: Option<u64>) -> String {
+    view.map(|view| view.to_string()).unwrap_or_else(|| "-".to_string())
+}
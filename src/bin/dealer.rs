@@ -1,13 +1,16 @@
-use clap::{value_parser, Arg, Command};
-use commonware_cryptography::{
-    bls12381::{
-        dkg::ops,
-        primitives::{group::Element, poly},
-    },
-    Ed25519, Scheme,
+use clap::{value_parser, Arg, ArgAction, Command};
+use commonware_cryptography::bls12381::{
+    dkg::ops,
+    primitives::{group::Element, poly},
 };
-use commonware_utils::{hex, quorum};
-use rand::{rngs::StdRng, SeedableRng};
+use commonware_utils::hex;
+use little_dipper::application::{epoch::Reconfiguration, supervisor::{share_indices, weighted_threshold}};
+use little_dipper::keys;
+use rand::{
+    rngs::{OsRng, StdRng},
+    SeedableRng,
+};
+use std::{fs, path::PathBuf};
 
 fn main() {
     // Parse arguments
@@ -16,52 +19,118 @@ fn main() {
         .arg(
             Arg::new("seed")
                 .long("seed")
-                .required(true)
-                .value_parser(value_parser!(u64)),
+                .required(false)
+                .value_parser(value_parser!(u64))
+                .help("Seed the dealing randomness (testing only, requires --insecure-seeds)"),
         )
         .arg(
             Arg::new("participants")
                 .long("participants")
                 .required(true)
                 .value_delimiter(',')
+                .value_parser(value_parser!(String))
+                .help("Public keys of all participants (arbiter and contributors)"),
+        )
+        .arg(
+            Arg::new("insecure-seeds")
+                .long("insecure-seeds")
+                .action(ArgAction::SetTrue)
+                .help("Derive participants from integer seeds given in place of public keys (testing only)"),
+        )
+        .arg(
+            Arg::new("output-dir")
+                .long("output-dir")
+                .required(true)
+                .help("Directory to write each participant's share to"),
+        )
+        .arg(
+            Arg::new("weights")
+                .long("weights")
+                .required(false)
+                .value_delimiter(',')
+                .value_parser(value_parser!(u64))
+                .help("Stake of each participant, in the same order (defaults to 1 each)"),
+        )
+        .arg(
+            Arg::new("activation")
+                .long("activation")
+                .required(false)
                 .value_parser(value_parser!(u64))
-                .help("All participants (arbiter and contributors)"),
+                .help("Also output a reconfiguration activating these shares at the given view"),
         )
         .get_matches();
 
     // Parse args
-    let seed = *matches.get_one::<u64>("seed").expect("seed is required");
+    let insecure_seeds = matches.get_flag("insecure-seeds");
+    let seed = matches.get_one::<u64>("seed").copied();
+    if seed.is_some() && !insecure_seeds {
+        panic!("--seed makes the group secret predictable, pass --insecure-seeds to use it");
+    }
+    let output_dir = PathBuf::from(
+        matches
+            .get_one::<String>("output-dir")
+            .expect("Please provide output directory"),
+    );
+    fs::create_dir_all(&output_dir).expect("Failed to create output directory");
     let mut validators = Vec::new();
     let participants = matches
-        .get_many::<u64>("participants")
-        .expect("Please provide allowed keys")
-        .copied();
+        .get_many::<String>("participants")
+        .expect("Please provide allowed keys");
     if participants.len() == 0 {
         panic!("Please provide at least one participant");
     }
-    for peer in participants {
-        let verifier = Ed25519::from_seed(peer).public_key();
-        validators.push((peer, verifier));
+    let weights: Vec<u64> = match matches.get_many::<u64>("weights") {
+        Some(weights) => weights.copied().collect(),
+        None => vec![1; participants.len()],
+    };
+    if weights.len() != participants.len() {
+        panic!("Please provide one weight per participant");
+    }
+    for (peer, weight) in participants.zip(weights.iter()) {
+        let verifier = keys::parse_participant(peer, insecure_seeds).expect("Participant not well-formed");
+        validators.push((peer.clone(), verifier, *weight));
     }
-    validators.sort_by(|(_, a), (_, b)| a.cmp(b));
-    let n = validators.len() as u32;
-    let t = quorum(n).expect("unable to compute threshold");
+    validators.sort_by(|(_, a, _), (_, b, _)| a.cmp(b));
+
+    // Share indices and threshold the validators expect for these stakes
+    let weights: Vec<u64> = validators.iter().map(|(_, _, weight)| *weight).collect();
+    let indices = share_indices(&weights).expect("Stakes not well-formed");
+    let t = weighted_threshold(&weights)
+        .expect("Stakes too skewed: consensus needs a threshold of validators, not just of shares");
+    let n = indices.iter().map(|owned| owned.len() as u32).sum();
 
     // Generate secret
-    let mut rng = StdRng::seed_from_u64(seed);
-    let (public, shares) = ops::generate_shares(&mut rng, None, n, t);
+    let (public, shares) = match seed {
+        Some(seed) => ops::generate_shares(&mut StdRng::seed_from_u64(seed), None, n, t),
+        None => ops::generate_shares(&mut OsRng, None, n, t),
+    };
 
     // Log secret
     println!("polynomial: {}", hex(&public.serialize()));
+    if let Some(activation) = matches.get_one::<u64>("activation") {
+        let reconfiguration = Reconfiguration {
+            activation: *activation,
+            participants: validators
+                .iter()
+                .map(|(_, verifier, weight)| (verifier.to_vec(), *weight))
+                .collect(),
+            identity: public.serialize(),
+        };
+        println!("reconfiguration: {}", hex(&reconfiguration.encode()));
+    }
     let public = poly::public(&public);
     println!("public: {}", hex(&public.serialize()));
-    for share in shares {
-        let validator = validators[share.index as usize].0;
+    println!("threshold: {}", t);
+    for ((validator, verifier, weight), owned) in validators.iter().zip(indices) {
+        let owned: Vec<_> = owned.iter().map(|index| shares[*index as usize].clone()).collect();
+        let path = output_dir.join(format!("{}.share", hex(verifier)));
+        keys::write_shares(&path, &owned).expect("Failed to write shares");
         println!(
-            "share (index={} validator={}): {}",
-            share.index,
+            "shares (indices={:?} validator={} weight={}): {}",
+            owned.iter().map(|share| share.index).collect::<Vec<_>>(),
             validator,
-            hex(&share.serialize())
+            weight,
+            path.display()
         );
     }
 }
@@ -0,0 +1,307 @@
+//! Interactive alternative to `dealer`: run one DKG (or resharing) round over the
+//! p2p network so no single party learns the group secret.
+//!
+//! Start every contributor first and then the arbiter, which opens the round. Players
+//! write their new shares to `--output`.
+
+use clap::{value_parser, Arg, ArgAction, Command};
+use commonware_cryptography::{
+    bls12381::primitives::{
+        group::{self, Element},
+        poly::{self, Poly},
+    },
+    PublicKey, Scheme,
+};
+use commonware_p2p::authenticated;
+use commonware_runtime::{
+    tokio::{self, Executor},
+    Runner, Spawner,
+};
+use commonware_utils::{from_hex, hex, union};
+use governor::Quota;
+use little_dipper::{
+    application::{epoch::Reconfiguration, supervisor::weighted_threshold},
+    config::resolve_bootstrapper,
+    dkg::{self, Arbiter, Contributor},
+    keys, APPLICATION_NAMESPACE, DKG_SUFFIX,
+};
+use prometheus_client::registry::Registry;
+use std::sync::{Arc, Mutex};
+use std::{
+    net::SocketAddr,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+fn main() {
+    // Parse arguments
+    let matches = Command::new("dkg")
+        .about("generate threshold secret without a trusted dealer")
+        .arg(
+            Arg::new("bootstrappers")
+                .long("bootstrappers")
+                .required(false)
+                .value_delimiter(',')
+                .value_parser(value_parser!(String))
+                .help("Peers to connect to on startup, as <public key>@<host>:<port>"),
+        )
+        .arg(
+            Arg::new("key")
+                .long("key")
+                .required(true)
+                .help("File holding our private key (generated by `keygen`)"),
+        )
+        .arg(
+            Arg::new("listen")
+                .long("listen")
+                .required(true)
+                .value_parser(value_parser!(SocketAddr))
+                .help("Address to bind, e.g. 0.0.0.0:3001 or [::]:3001"),
+        )
+        .arg(
+            Arg::new("advertise")
+                .long("advertise")
+                .required(false)
+                .value_parser(value_parser!(SocketAddr))
+                .help("Address other peers should dial (defaults to --listen)"),
+        )
+        .arg(
+            Arg::new("insecure-seeds")
+                .long("insecure-seeds")
+                .action(ArgAction::SetTrue)
+                .help("Derive keys from integer seeds given in place of key files and public keys (testing only)"),
+        )
+        .arg(Arg::new("arbiter").long("arbiter").required(true))
+        .arg(
+            Arg::new("dealers")
+                .long("dealers")
+                .required(true)
+                .value_delimiter(',')
+                .value_parser(value_parser!(String))
+                .help("Participants dealing a polynomial (the current validators when resharing)"),
+        )
+        .arg(
+            Arg::new("players")
+                .long("players")
+                .required(false)
+                .value_delimiter(',')
+                .value_parser(value_parser!(String))
+                .help("Participants receiving a share (defaults to the dealers)"),
+        )
+        .arg(
+            Arg::new("weights")
+                .long("weights")
+                .required(false)
+                .value_delimiter(',')
+                .value_parser(value_parser!(u64))
+                .help("Stake of each player, in the same order as --players (defaults to 1 each)"),
+        )
+        .arg(
+            Arg::new("dealer-weights")
+                .long("dealer-weights")
+                .required(false)
+                .value_delimiter(',')
+                .value_parser(value_parser!(u64))
+                .help("Stake of each dealer when resharing, in the same order (defaults to 1 each)"),
+        )
+        .arg(
+            Arg::new("polynomial")
+                .long("polynomial")
+                .required(false)
+                .help("Group polynomial to reshare"),
+        )
+        .arg(
+            Arg::new("share")
+                .long("share")
+                .required(false)
+                .help("File holding our shares of the group polynomial to reshare"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .required(false)
+                .help("File to write our new shares to (required for players)"),
+        )
+        .arg(
+            Arg::new("phase-timeout")
+                .long("phase-timeout")
+                .required(false)
+                .default_value("5")
+                .value_parser(value_parser!(u64))
+                .help("Seconds to wait for each phase of the round"),
+        )
+        .arg(
+            Arg::new("activation")
+                .long("activation")
+                .required(false)
+                .value_parser(value_parser!(u64))
+                .help("Also output a reconfiguration activating the new shares at the given view"),
+        )
+        .get_matches();
+
+    // Create logger
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .init();
+
+    // Configure my identity
+    let insecure_seeds = matches.get_flag("insecure-seeds");
+    let key = matches
+        .get_one::<String>("key")
+        .expect("Please provide key");
+    let signer = keys::load_signer(key, insecure_seeds).expect("Failed to load key");
+    tracing::info!(key = hex(&signer.public_key()), "loaded signer");
+    let listen = *matches.get_one::<SocketAddr>("listen").expect("Please provide listen address");
+    let advertise = matches.get_one::<SocketAddr>("advertise").copied().unwrap_or(listen);
+    if advertise.ip().is_unspecified() {
+        panic!("Please provide an address to advertise");
+    }
+
+    // Configure participants
+    let parse = |peer: &String| {
+        keys::parse_participant(peer, insecure_seeds).expect("Participant not well-formed")
+    };
+    let arbiter = parse(matches.get_one::<String>("arbiter").unwrap());
+    let dealers: Vec<_> = matches
+        .get_many::<String>("dealers")
+        .expect("Please provide dealers")
+        .map(parse)
+        .collect();
+    let dealers = weighted(dealers, matches.get_many::<u64>("dealer-weights"));
+    let players = match matches.get_many::<String>("players") {
+        Some(players) => weighted(players.map(parse).collect(), matches.get_many::<u64>("weights")),
+        None => dealers.clone(),
+    };
+    let dealer_weights: Vec<u64> = dealers.iter().map(|(_, weight)| *weight).collect();
+    let dealers: Vec<_> = dealers.into_iter().map(|(dealer, _)| dealer).collect();
+    let weights: Vec<u64> = players.iter().map(|(_, weight)| *weight).collect();
+    let players: Vec<_> = players.into_iter().map(|(player, _)| player).collect();
+    let mut peers = dealers.clone();
+    peers.extend(players.iter().cloned());
+    peers.push(arbiter.clone());
+    peers.sort();
+    peers.dedup();
+
+    // Configure resharing (if requested)
+    let previous = matches.get_one::<String>("polynomial").map(|polynomial| {
+        let polynomial = from_hex(polynomial).expect("Polynomial not well-formed");
+        let threshold = weighted_threshold(&dealer_weights).expect("Threshold not well-formed");
+        Poly::<group::Public>::deserialize(&polynomial, threshold).expect("Polynomial not well-formed")
+    });
+    let shares = matches
+        .get_one::<String>("share")
+        .map(|share| keys::read_shares(Path::new(share)).expect("Failed to load share"))
+        .unwrap_or_default();
+    if previous.is_some() && dealers.contains(&signer.public_key()) && shares.is_empty() {
+        panic!("Please provide our shares to reshare");
+    }
+    let output = matches.get_one::<String>("output").map(PathBuf::from);
+    if players.contains(&signer.public_key()) && output.is_none() {
+        panic!("Please provide a file to write our share to");
+    }
+    let config = dkg::Config {
+        dealers,
+        dealer_weights,
+        players: players.clone(),
+        weights: weights.clone(),
+        previous,
+        phase_timeout: Duration::from_secs(*matches.get_one::<u64>("phase-timeout").unwrap()),
+    };
+    let activation = matches.get_one::<u64>("activation").copied();
+
+    // Configure bootstrappers (if provided)
+    let bootstrappers = matches.get_many::<String>("bootstrappers");
+    let mut bootstrapper_identities = Vec::new();
+    if let Some(bootstrappers) = bootstrappers {
+        for bootstrapper in bootstrappers {
+            match resolve_bootstrapper(bootstrapper, insecure_seeds) {
+                Ok(bootstrapper) => bootstrapper_identities.push(bootstrapper),
+                Err(e) => panic!("{}", e),
+            }
+        }
+    }
+
+    // Initialize runtime
+    let (executor, runtime) = Executor::init(tokio::Config::default());
+
+    // Configure network
+    let mut p2p_cfg = authenticated::Config::aggressive(
+        signer.clone(),
+        &union(APPLICATION_NAMESPACE, DKG_SUFFIX),
+        Arc::new(Mutex::new(Registry::default())),
+        listen,
+        bootstrapper_identities,
+        1024 * 1024, // 1MB
+    );
+    p2p_cfg.dialable = advertise;
+
+    // Start runtime
+    executor.start(async move {
+        let (mut network, mut oracle) = authenticated::Network::new(runtime.clone(), p2p_cfg);
+        oracle.register(0, peers).await;
+        let (sender, receiver) = network.register(
+            0,
+            Quota::per_second(NonZeroU32::new(128).unwrap()),
+            1024, // 1024 messages in flight
+            Some(3),
+        );
+        runtime.spawn("network", network.run());
+
+        let public = if signer.public_key() == arbiter {
+            Arbiter::new(runtime.clone(), config)
+                .run(0, sender, receiver)
+                .await
+        } else {
+            let output = Contributor::new(runtime.clone(), signer.public_key(), arbiter, config, shares)
+                .run(sender, receiver)
+                .await;
+            output.map(|result| {
+                let path = output.expect("players write their share");
+                keys::write_shares(&path, &result.shares).expect("Failed to write shares");
+                println!(
+                    "shares (indices={:?}): {}",
+                    result.shares.iter().map(|share| share.index).collect::<Vec<_>>(),
+                    path.display()
+                );
+                result.public
+            })
+        };
+        let Some(public) = public else {
+            panic!("DKG round failed");
+        };
+
+        // Log result
+        println!("polynomial: {}", hex(&public.serialize()));
+        println!("public: {}", hex(&poly::public(&public).serialize()));
+        if let Some(activation) = activation {
+            let reconfiguration = Reconfiguration {
+                activation,
+                participants: players
+                    .iter()
+                    .zip(weights.iter())
+                    .map(|(player, weight)| (player.to_vec(), *weight))
+                    .collect(),
+                identity: public.serialize(),
+            };
+            println!("reconfiguration: {}", hex(&reconfiguration.encode()));
+        }
+    });
+}
+
+/// Pair participants with their stake (1 each if not given), sorted by key.
+fn weighted<'a>(
+    participants: Vec<PublicKey>,
+    weights: Option<impl Iterator<Item = &'a u64>>,
+) -> Vec<(PublicKey, u64)> {
+    let weights: Vec<u64> = match weights {
+        Some(weights) => weights.copied().collect(),
+        None => vec![1; participants.len()],
+    };
+    if weights.len() != participants.len() {
+        panic!("Please provide one weight per participant");
+    }
+    let mut weighted: Vec<_> = participants.into_iter().zip(weights).collect();
+    weighted.sort();
+    weighted
+}
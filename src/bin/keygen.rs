@@ -0,0 +1,28 @@
+use clap::{Arg, Command};
+use commonware_cryptography::Scheme;
+use commonware_utils::hex;
+use little_dipper::keys;
+use std::path::Path;
+
+fn main() {
+    // Parse arguments
+    let matches = Command::new("keygen")
+        .about("generate a validator identity key")
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .required(true)
+                .help("File to write the private key to (must not exist)"),
+        )
+        .get_matches();
+
+    // Generate and store key
+    let output = matches
+        .get_one::<String>("output")
+        .expect("Please provide output file");
+    let signer = keys::generate_signer();
+    keys::write_signer(Path::new(output), &signer).expect("Failed to write key");
+
+    // Log public key, used to refer to this validator in `--participants`
+    println!("public: {}", hex(&signer.public_key()));
+}
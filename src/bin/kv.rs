@@ -0,0 +1,152 @@
+//! Sign a key-value operation, send it to several validators running the kv store and
+//! print the key's value once the operation is included.
+
+use clap::{value_parser, Arg, ArgAction, Command};
+use commonware_cryptography::Scheme;
+use commonware_utils::{from_hex, hex};
+use little_dipper::{
+    api::Entry,
+    application::{kv::Operation, transaction::Transaction},
+    client::{Client, Config},
+    keys,
+};
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() {
+    // Parse arguments
+    let matches = Command::new("kv")
+        .about("update the replicated key-value store")
+        .arg(
+            Arg::new("validators")
+                .long("validators")
+                .required(false)
+                .default_value("http://127.0.0.1:4001")
+                .value_delimiter(',')
+                .help("Comma-separated base urls of the validators' api servers"),
+        )
+        .arg(
+            Arg::new("fanout")
+                .long("fanout")
+                .required(false)
+                .default_value("2")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("key")
+                .long("key")
+                .required(true)
+                .help("Private key file signing the operation (or a seed with --insecure-seeds)"),
+        )
+        .arg(
+            Arg::new("insecure-seeds")
+                .long("insecure-seeds")
+                .required(false)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("nonce")
+                .long("nonce")
+                .required(false)
+                .value_parser(value_parser!(u64))
+                .help("Defaults to the next nonce reported by the validators"),
+        )
+        .arg(
+            Arg::new("op")
+                .long("op")
+                .required(true)
+                .value_parser(["set", "delete", "cas"]),
+        )
+        .arg(Arg::new("entry").long("entry").required(true).help("Key to operate on"))
+        .arg(Arg::new("value").long("value").required(false).help("New value, required by set"))
+        .arg(
+            Arg::new("expected")
+                .long("expected")
+                .required(false)
+                .help("Value cas expects, the key must be absent when unset"),
+        )
+        .arg(
+            Arg::new("timeout-ms")
+                .long("timeout-ms")
+                .required(false)
+                .default_value("30000")
+                .value_parser(value_parser!(u64)),
+        )
+        .get_matches();
+    let validators: Vec<String> = matches
+        .get_many::<String>("validators")
+        .unwrap()
+        .cloned()
+        .collect();
+    let fanout = *matches.get_one::<usize>("fanout").unwrap();
+    let mut signer = keys::load_signer(
+        matches.get_one::<String>("key").unwrap(),
+        matches.get_flag("insecure-seeds"),
+    )
+    .expect("Failed to load key");
+    let timeout = Duration::from_millis(*matches.get_one::<u64>("timeout-ms").unwrap());
+
+    // Sign the operation
+    let key = matches.get_one::<String>("entry").unwrap().clone();
+    let value = matches.get_one::<String>("value").cloned();
+    let operation = match matches.get_one::<String>("op").unwrap().as_str() {
+        "set" => Operation::Set {
+            key: key.clone(),
+            value: value.expect("--value is required by set"),
+        },
+        "delete" => Operation::Delete { key: key.clone() },
+        _ => Operation::CompareAndSwap {
+            key: key.clone(),
+            expected: matches.get_one::<String>("expected").cloned(),
+            value,
+        },
+    };
+    let mut client = Client::new(Config {
+        validators,
+        fanout,
+        poll_interval: Duration::from_millis(200),
+        light_client: None,
+    });
+    let nonce = match matches.get_one::<u64>("nonce") {
+        Some(nonce) => *nonce,
+        None => client
+            .next_nonce(&signer.public_key())
+            .await
+            .expect("Failed to fetch the next nonce"),
+    };
+    let chat = Transaction::sign(&mut signer, nonce, 0, operation.encode()).encode();
+
+    // Send it and wait for its inclusion
+    let submission = client.submit(&chat).await.expect("Failed to submit operation");
+    println!("digest: {}", hex(&submission.digest));
+    let inclusion = client
+        .wait(&submission, &chat, timeout)
+        .await
+        .expect("Operation was not included");
+    println!(
+        "included: view={} latency={:?}",
+        inclusion.receipt.view, inclusion.latency
+    );
+
+    // Read the key back (a failed cas leaves it unchanged)
+    let response = reqwest::get(format!(
+        "{}/state/{}",
+        inclusion.validator.trim_end_matches('/'),
+        key
+    ))
+    .await
+    .expect("Failed to query state");
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        println!("{}: absent", key);
+        return;
+    }
+    let entry: Entry = response.json().await.expect("Failed to decode entry");
+    let value = from_hex(&entry.value).unwrap_or_default();
+    println!(
+        "{}: {} (view={} state_root={})",
+        key,
+        String::from_utf8_lossy(&value),
+        entry.view,
+        entry.state_root
+    );
+}
@@ -1,17 +1,22 @@
-use clap::{value_parser, Arg, Command};
+use clap::{value_parser, Arg, ArgAction, Command};
 use little_dipper::{
-    application, APPLICATION_NAMESPACE, CONSENSUS_SUFFIX, P2P_SUFFIX,
+    api, application,
+    config::{resolve_bootstrapper, Config, StateMachine as StateMachineKind},
+    keys, APPLICATION_NAMESPACE, CONSENSUS_SUFFIX, P2P_SUFFIX,
 };
-use little_dipper::application::chatter::actor::Actor;
+use little_dipper::application::{chat::Chat, chatter, history::{self, History}, kv, p2p};
+use little_dipper::application::{state_machine::StateMachine, sync::Snapshot, transaction::Transaction};
+use little_dipper::application::chatter::{actor::Actor, mempool, verifier};
+use little_dipper::application::supervisor::{share_indices, weighted_threshold};
 use little_dipper::application::p2p::actor::Actor as P2PActor;
 
-use commonware_consensus::threshold_simplex::{self, Engine, Prover};
+use commonware_consensus::threshold_simplex::{self, Engine};
 use commonware_cryptography::{
     bls12381::primitives::{
         group,
-        poly::{self, Poly},
+        poly::Poly,
     },
-    Ed25519, Scheme, Sha256,
+    Scheme, Sha256,
 };
 use commonware_p2p::authenticated;
 use commonware_runtime::{
@@ -19,39 +24,101 @@ use commonware_runtime::{
     Network, Runner, Spawner,
 };
 use commonware_storage::journal::{self, Journal};
-use commonware_utils::{from_hex, hex, quorum, union};
+use commonware_utils::{from_hex, hex, union};
+use futures::{channel::mpsc, StreamExt};
 use governor::Quota;
 use prometheus_client::registry::Registry;
 use std::sync::{Arc, Mutex};
-use std::{
-    net::{IpAddr, Ipv4Addr, SocketAddr},
-    num::NonZeroU32,
-};
-use std::{str::FromStr, time::Duration};
+use std::{net::SocketAddr, num::NonZeroU32, path::Path};
 
 fn main() {
     // Parse arguments
     let matches = Command::new("validator")
         .about("produce finality certificates and verify external finality certificates")
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .required(false)
+                .help("JSON configuration file, overridden by the flags below"),
+        )
         .arg(
             Arg::new("bootstrappers")
                 .long("bootstrappers")
                 .required(false)
                 .value_delimiter(',')
-                .value_parser(value_parser!(String)),
+                .value_parser(value_parser!(String))
+                .help("Peers to connect to on startup, as <public key>@<host>:<port>"),
+        )
+        .arg(
+            Arg::new("key")
+                .long("key")
+                .required(false)
+                .help("File holding our private key (generated by `keygen`)"),
+        )
+        .arg(
+            Arg::new("listen")
+                .long("listen")
+                .required(false)
+                .value_parser(value_parser!(SocketAddr))
+                .help("Address to bind, e.g. 0.0.0.0:3001 or [::]:3001"),
+        )
+        .arg(
+            Arg::new("advertise")
+                .long("advertise")
+                .required(false)
+                .value_parser(value_parser!(SocketAddr))
+                .help("Address other peers should dial (defaults to --listen)"),
         )
-        .arg(Arg::new("me").long("me").required(true))
         .arg(
             Arg::new("participants")
                 .long("participants")
-                .required(true)
+                .required(false)
+                .value_delimiter(',')
+                .value_parser(value_parser!(String))
+                .help("Public keys of all participants"),
+        )
+        .arg(
+            Arg::new("insecure-seeds")
+                .long("insecure-seeds")
+                .action(ArgAction::SetTrue)
+                .help("Derive keys from integer seeds given in place of key files and public keys (testing only)"),
+        )
+        .arg(
+            Arg::new("weights")
+                .long("weights")
+                .required(false)
                 .value_delimiter(',')
                 .value_parser(value_parser!(u64))
-                .help("All participants"),
+                .help("Stake of each participant, in the same order (defaults to 1 each)"),
+        )
+        .arg(Arg::new("storage-dir").long("storage-dir").required(false))
+        .arg(Arg::new("identity").long("identity").required(false))
+        .arg(
+            Arg::new("share")
+                .long("share")
+                .required(false)
+                .help("File holding our share of the group polynomial"),
+        )
+        .arg(
+            Arg::new("epoch-shares")
+                .long("epoch-shares")
+                .required(false)
+                .value_delimiter(',')
+                .value_parser(value_parser!(String))
+                .help("Files holding shares dealt for future validator sets"),
+        )
+        .arg(
+            Arg::new("snapshot")
+                .long("snapshot")
+                .required(false)
+                .help("Snapshot file (as served by /snapshot) to start from instead of genesis"),
+        )
+        .arg(
+            Arg::new("reconfigure")
+                .long("reconfigure")
+                .required(false)
+                .help("Reconfiguration chat (hex) to include in our next mini-block"),
         )
-        .arg(Arg::new("storage-dir").long("storage-dir").required(true))
-        .arg(Arg::new("identity").long("identity").required(true))
-        .arg(Arg::new("share").long("share").required(true))
         .get_matches();
 
     // Create logger
@@ -59,72 +126,132 @@ fn main() {
         .with_max_level(tracing::Level::DEBUG)
         .init();
 
+    // Load configuration and apply overrides
+    let mut config = match matches.get_one::<String>("config") {
+        Some(path) => Config::load(Path::new(path)).expect("Failed to load config"),
+        None => Config::default(),
+    };
+    if let Some(key) = matches.get_one::<String>("key") {
+        config.identity.key = Some(key.clone());
+    }
+    if let Some(share) = matches.get_one::<String>("share") {
+        config.identity.share = Some(share.clone());
+    }
+    if let Some(polynomial) = matches.get_one::<String>("identity") {
+        config.identity.polynomial = Some(polynomial.clone());
+    }
+    if let Some(participants) = matches.get_many::<String>("participants") {
+        config.identity.participants = participants.cloned().collect();
+    }
+    if let Some(weights) = matches.get_many::<u64>("weights") {
+        config.identity.weights = weights.copied().collect();
+    }
+    if let Some(epoch_shares) = matches.get_many::<String>("epoch-shares") {
+        config.identity.epoch_shares = epoch_shares.cloned().collect();
+    }
+    if matches.get_flag("insecure-seeds") {
+        config.identity.insecure_seeds = true;
+    }
+    if let Some(listen) = matches.get_one::<SocketAddr>("listen") {
+        config.network.listen = Some(*listen);
+    }
+    if let Some(advertise) = matches.get_one::<SocketAddr>("advertise") {
+        config.network.advertise = Some(*advertise);
+    }
+    if let Some(bootstrappers) = matches.get_many::<String>("bootstrappers") {
+        config.network.bootstrappers = bootstrappers.cloned().collect();
+    }
+    if let Some(storage_directory) = matches.get_one::<String>("storage-dir") {
+        config.storage.directory = Some(storage_directory.clone());
+    }
+    if let Some(snapshot) = matches.get_one::<String>("snapshot") {
+        config.sync.snapshot = Some(snapshot.clone());
+    }
+    if let Err(e) = config.validate() {
+        panic!("{}", e);
+    }
+
     // Configure my identity
-    let me = matches
-        .get_one::<String>("me")
-        .expect("Please provide identity");
-    let parts = me.split('@').collect::<Vec<&str>>();
-    if parts.len() != 2 {
-        panic!("Identity not well-formed");
+    let insecure_seeds = config.identity.insecure_seeds;
+    if insecure_seeds {
+        tracing::warn!("deriving keys from seeds, do not use outside of testing");
     }
-    let key = parts[0].parse::<u64>().expect("Key not well-formed");
-    let signer = Ed25519::from_seed(key);
+    let key = config.identity.key.as_ref().unwrap();
+    let signer = keys::load_signer(key, insecure_seeds).expect("Failed to load key");
     tracing::info!(key = hex(&signer.public_key()), "loaded signer");
 
-    // Configure my port
-    let port = parts[1].parse::<u16>().expect("Port not well-formed");
-    tracing::info!(port, "loaded port");
+    // Configure my addresses
+    let listen = config.network.listen.unwrap();
+    let advertise = config.network.advertise().unwrap();
+    tracing::info!(%listen, %advertise, "loaded addresses");
 
     // Configure allowed peers
     let mut validators = Vec::new();
-    let participants = matches
-        .get_many::<u64>("participants")
-        .expect("Please provide allowed keys")
-        .copied();
-    if participants.len() == 0 {
-        panic!("Please provide at least one participant");
-    }
-    for peer in participants {
-        let verifier = Ed25519::from_seed(peer).public_key();
-        tracing::info!(key = hex(&verifier), "registered authorized key",);
-        validators.push(verifier);
+    let participants = &config.identity.participants;
+    let weights: Vec<u64> = match config.identity.weights.is_empty() {
+        true => vec![1; participants.len()],
+        false => config.identity.weights.clone(),
+    };
+    for (peer, weight) in participants.iter().zip(weights.iter()) {
+        let verifier = keys::parse_participant(peer, insecure_seeds).expect("Participant not well-formed");
+        tracing::info!(key = hex(&verifier), weight, "registered authorized key",);
+        validators.push((verifier, *weight));
     }
 
     // Configure bootstrappers (if provided)
-    let bootstrappers = matches.get_many::<String>("bootstrappers");
     let mut bootstrapper_identities = Vec::new();
-    if let Some(bootstrappers) = bootstrappers {
-        for bootstrapper in bootstrappers {
-            let parts = bootstrapper.split('@').collect::<Vec<&str>>();
-            let bootstrapper_key = parts[0]
-                .parse::<u64>()
-                .expect("Bootstrapper key not well-formed");
-            let verifier = Ed25519::from_seed(bootstrapper_key).public_key();
-            let bootstrapper_address =
-                SocketAddr::from_str(parts[1]).expect("Bootstrapper address not well-formed");
-            bootstrapper_identities.push((verifier, bootstrapper_address));
+    for bootstrapper in config.network.bootstrappers.iter() {
+        match resolve_bootstrapper(bootstrapper, insecure_seeds) {
+            Ok(bootstrapper) => bootstrapper_identities.push(bootstrapper),
+            Err(e) => panic!("{}", e),
         }
     }
 
     // Configure storage directory
-    let storage_directory = matches
-        .get_one::<String>("storage-dir")
-        .expect("Please provide storage directory");
+    let storage_directory = config.storage.directory.clone().unwrap();
 
     // Configure threshold
-    let threshold = quorum(validators.len() as u32).expect("Threshold not well-formed");
-    let identity = matches
-        .get_one::<String>("identity")
-        .expect("Please provide identity");
+    let threshold = weighted_threshold(&weights).expect("Threshold not well-formed");
+    let identity = config.identity.polynomial.as_ref().unwrap();
     let identity = from_hex(identity).expect("Identity not well-formed");
     let identity: Poly<group::Public> =
         Poly::deserialize(&identity, threshold).expect("Identity not well-formed");
-    let public = poly::public(&identity);
-    let share = matches
-        .get_one::<String>("share")
-        .expect("Please provide share");
-    let share = from_hex(share).expect("Share not well-formed");
-    let share = group::Share::deserialize(&share).expect("Share not well-formed");
+    let share = config.identity.share.as_ref().unwrap();
+    let shares = keys::read_shares(Path::new(share)).expect("Failed to load share");
+
+    // Ensure our key and shares belong to the configured validator set, consensus
+    // signs with the share at our participant index
+    let mut sorted = validators.clone();
+    sorted.sort();
+    let Some(index) = sorted.iter().position(|(peer, _)| *peer == signer.public_key()) else {
+        panic!("Our key {} is not a participant", hex(&signer.public_key()));
+    };
+    let stakes: Vec<u64> = sorted.iter().map(|(_, weight)| *weight).collect();
+    let owned = share_indices(&stakes).expect("Stakes not well-formed").swap_remove(index);
+    let mut indices: Vec<u32> = shares.iter().map(|share| share.index).collect();
+    indices.sort();
+    if indices != owned {
+        panic!("Share indices {:?} do not match ours {:?}", indices, owned);
+    }
+    if shares.iter().any(|share| identity.evaluate(share.index).value != share.public()) {
+        panic!("Share does not belong to the group polynomial");
+    }
+    let share = shares
+        .into_iter()
+        .find(|share| share.index == index as u32)
+        .unwrap();
+    let mut epoch_shares = Vec::new();
+    for epoch_share in config.identity.epoch_shares.iter() {
+        let shares = keys::read_shares(Path::new(epoch_share)).expect("Failed to load epoch share");
+        epoch_shares.extend(shares);
+    }
+    let reconfiguration = matches
+        .get_one::<String>("reconfigure")
+        .map(|chat| from_hex(chat).expect("Reconfiguration not well-formed"));
+    let snapshot = config.sync.snapshot.as_ref().map(|snapshot| {
+        let snapshot = std::fs::read(snapshot).expect("Failed to read snapshot");
+        Snapshot::decode(&snapshot).expect("Snapshot not well-formed")
+    });
 
     // Initialize runtime
     let runtime_cfg = tokio::Config {
@@ -133,16 +260,19 @@ fn main() {
     };
     let (executor, runtime) = Executor::init(runtime_cfg.clone());
 
+    // Share one registry between every component so the api can serve all metrics
+    let registry = Arc::new(Mutex::new(Registry::default()));
 
     // Configure network
-    let p2p_cfg = authenticated::Config::aggressive(
+    let mut p2p_cfg = authenticated::Config::aggressive(
         signer.clone(),
         &union(APPLICATION_NAMESPACE, P2P_SUFFIX),
-        Arc::new(Mutex::new(Registry::default())),
-        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port),
+        registry.clone(),
+        listen,
         bootstrapper_identities.clone(),
-        1024 * 1024, // 1MB
+        config.network.max_message_size,
     );
+    p2p_cfg.dialable = advertise;
 
     // Start runtime
     executor.start(async move {
@@ -151,9 +281,15 @@ fn main() {
 
         // Provide authorized peers
         //
-        // In a real-world scenario, this would be updated as new peer sets are created (like when
-        // the composition of a validator set changes).
-        oracle.register(0, validators.clone()).await;
+        // Later peer sets are registered as reconfigurations are finalized.
+        let peers = validators.iter().map(|(peer, _)| peer.clone()).collect();
+        oracle.register(0, peers).await;
+        let (peer_sets_sender, mut peer_sets_receiver) = mpsc::channel(8);
+        runtime.spawn("oracle", async move {
+            while let Some((epoch, peers)) = peer_sets_receiver.next().await {
+                oracle.register(epoch, peers).await;
+            }
+        });
 
         // Register consensus channels
         //
@@ -161,56 +297,167 @@ fn main() {
         // for this channel.
         let (voter_sender, voter_receiver) = network.register(
             0,
-            Quota::per_second(NonZeroU32::new(10).unwrap()),
-            256, // 256 messages in flight
-            Some(3),
+            Quota::per_second(NonZeroU32::new(config.network.consensus_rate).unwrap()),
+            config.network.message_backlog,
+            config.network.compression,
         );
         let (resolver_sender, resolver_receiver) = network.register(
             1,
-            Quota::per_second(NonZeroU32::new(10).unwrap()),
-            256, // 256 messages in flight
-            Some(3),
+            Quota::per_second(NonZeroU32::new(config.network.resolver_rate).unwrap()),
+            config.network.message_backlog,
+            config.network.compression,
         );
 
         // Register chatter channels
         let (chatter_p2p_sender, chatter_p2p_reciever) = network.register(
             2,
-            Quota::per_second(NonZeroU32::new(10).unwrap()),
-            256, // 256 messages in flight
-            Some(3),
+            Quota::per_second(NonZeroU32::new(config.network.chatter_rate).unwrap()),
+            config.network.message_backlog,
+            config.network.compression,
+        );
+
+        // Register proto-block channel
+        let (block_sender, block_receiver) = network.register(
+            3,
+            Quota::per_second(NonZeroU32::new(config.network.block_rate).unwrap()),
+            config.network.message_backlog,
+            config.network.compression,
+        );
+
+        // Register sync channel
+        let (sync_sender, sync_receiver) = network.register(
+            4,
+            Quota::per_second(NonZeroU32::new(config.network.sync_rate).unwrap()),
+            config.network.message_backlog,
+            config.network.compression,
         );
 
         // Initialize storage
         let journal = Journal::init(
             runtime.clone(),
             journal::Config {
-                registry: Arc::new(Mutex::new(Registry::default())),
-                partition: String::from("log"),
+                registry: registry.clone(),
+                partition: config.storage.partition.clone(),
             },
         )
         .await
         .expect("Failed to initialize journal");
 
+        // Initialize state machine
+        let state_machine = match config.application.state_machine {
+            StateMachineKind::Chat => Replicated::Chat(Chat::default()),
+            StateMachineKind::Kv => Replicated::Kv(
+                kv::Store::init(
+                    runtime.clone(),
+                    kv::Config {
+                        partition: config.storage.state_partition.clone(),
+                        registry: registry.clone(),
+                        replay_concurrency: config.consensus.replay_concurrency,
+                    },
+                )
+                .await
+                .expect("Failed to initialize kv store"),
+            ),
+        };
+
         // Initialize chatter
-        let (chatter_actor, chatter_mailbox) = Actor::new();
+        let mini_block_journal = Journal::init(
+            runtime.clone(),
+            journal::Config {
+                registry: registry.clone(),
+                partition: config.storage.mini_block_partition.clone(),
+            },
+        )
+        .await
+        .expect("Failed to initialize mini-block journal");
+        let (chatter_actor, chatter_mailbox) = Actor::new(chatter::Config {
+            hasher: Sha256::default(),
+            journal: mini_block_journal,
+            replay_concurrency: config.consensus.replay_concurrency,
+            retention_views: config.history.retention_views,
+            mailbox_size: config.chatter.mailbox_size,
+            mempool: mempool::Config {
+                max_transactions: config.mempool.max_transactions,
+                max_bytes: config.mempool.max_bytes,
+                max_per_sender: config.mempool.max_per_sender,
+                expiry: config.mempool.expiry_views,
+                mini_block_transactions: config.mempool.mini_block_transactions,
+                mini_block_bytes: config.mempool.mini_block_bytes,
+                policy: config.mempool.policy.policy(),
+            },
+            attestation: config.chatter.attestation,
+            verifier: verifier::Config {
+                threads: config.chatter.verifier_threads,
+                batch_size: config.chatter.verify_batch_size,
+                cache_size: config.chatter.verified_cache_size,
+            },
+            registry: registry.clone(),
+        });
         // Initialize application
+        let (history, recovered) = History::init(
+            runtime.clone(),
+            history::Config {
+                block_partition: config.storage.block_partition.clone(),
+                snapshot_partition: config.storage.snapshot_partition.clone(),
+                registry: registry.clone(),
+                replay_concurrency: config.consensus.replay_concurrency,
+                snapshot_interval: config.history.snapshot_interval,
+                retained_snapshots: config.history.retained_snapshots,
+                retention_views: config.history.retention_views,
+            },
+        )
+        .await
+        .expect("Failed to initialize history");
         let consensus_namespace = union(APPLICATION_NAMESPACE, CONSENSUS_SUFFIX);
         let hasher = Sha256::default();
-        let prover: Prover<Sha256> = Prover::new(public, &consensus_namespace);
         let (application, supervisor, mailbox) = application::Application::new(
             runtime.clone(),
             application::Config {
-                prover,
+                namespace: consensus_namespace.clone(),
                 hasher: hasher.clone(),
-                mailbox_size: 1024,
+                state_machine,
+                mailbox_size: config.chatter.application_mailbox_size,
+                retained_blocks: config.api.retained_blocks,
+                snapshot,
+                history,
+                recovered,
+                sync_batch: config.sync.batch,
+                sync_attempts: config.sync.attempts,
+                max_message_size: config.network.max_message_size,
                 identity,
                 participants: validators.clone(),
                 share,
+                epoch_shares,
+                peer_sets: peer_sets_sender,
+                registry: registry.clone(),
             },
             chatter_mailbox.clone(),
         );
 
-        let (p2p_actor, p2p_mailbox) = P2PActor::new(chatter_mailbox, supervisor.clone());
+        // Queue the validator set change we are voting for
+        if let Some(reconfiguration) = reconfiguration {
+            let _ = chatter_mailbox.clone().load_reconfiguration(reconfiguration.into()).await;
+        }
+
+        // Serve metrics and status (if configured)
+        if let Some(listen) = config.api.listen {
+            let server = api::Server::new(api::Config {
+                listen,
+                registry: registry.clone(),
+                application: mailbox.clone(),
+                chatter: chatter_mailbox.clone(),
+            });
+            runtime.spawn("api", server.run());
+        }
+
+        let (p2p_actor, p2p_mailbox) = P2PActor::new(
+            p2p::Config {
+                mailbox_size: config.chatter.p2p_mailbox_size,
+                registry: registry.clone(),
+            },
+            chatter_mailbox,
+            supervisor.clone(),
+        );
         let chatter_supervisor = supervisor.clone();
 
         // Initialize consensus
@@ -224,19 +471,21 @@ fn main() {
                 relay: mailbox.clone(),
                 committer: mailbox,
                 supervisor,
-                registry: Arc::new(Mutex::new(Registry::default())),
+                registry: registry.clone(),
                 namespace: consensus_namespace,
-                mailbox_size: 1024,
-                replay_concurrency: 1,
-                leader_timeout: Duration::from_secs(1),
-                notarization_timeout: Duration::from_secs(2),
-                nullify_retry: Duration::from_secs(10),
-                fetch_timeout: Duration::from_secs(1),
-                activity_timeout: 10,
-                max_fetch_count: 32,
-                max_fetch_size: 1024 * 512,
-                fetch_concurrent: 2,
-                fetch_rate_per_peer: Quota::per_second(NonZeroU32::new(1).unwrap()),
+                mailbox_size: config.consensus.mailbox_size,
+                replay_concurrency: config.consensus.replay_concurrency,
+                leader_timeout: config.consensus.leader_timeout(),
+                notarization_timeout: config.consensus.notarization_timeout(),
+                nullify_retry: config.consensus.nullify_retry(),
+                fetch_timeout: config.consensus.fetch_timeout(),
+                activity_timeout: config.consensus.activity_timeout,
+                max_fetch_count: config.consensus.max_fetch_count,
+                max_fetch_size: config.consensus.max_fetch_size,
+                fetch_concurrent: config.consensus.fetch_concurrent,
+                fetch_rate_per_peer: Quota::per_second(
+                    NonZeroU32::new(config.consensus.fetch_rate_per_peer).unwrap(),
+                ),
             },
         );
 
@@ -256,6 +505,63 @@ fn main() {
         runtime.spawn("p2p", p2p_actor.run(chatter_p2p_sender, chatter_p2p_reciever));
 
         // Block on application
-        application.run().await;
+        application.run(block_sender, block_receiver, sync_sender, sync_receiver).await;
     });
 }
+
+/// State machine picked in the config.
+enum Replicated {
+    Chat(Chat),
+    Kv(kv::Store<tokio::Blob, tokio::Context>),
+}
+
+impl StateMachine for Replicated {
+    fn genesis(&mut self) -> Vec<u8> {
+        match self {
+            Replicated::Chat(chat) => chat.genesis(),
+            Replicated::Kv(store) => store.genesis(),
+        }
+    }
+
+    fn validate(&self, transaction: &Transaction) -> Result<(), String> {
+        match self {
+            Replicated::Chat(chat) => chat.validate(transaction),
+            Replicated::Kv(store) => store.validate(transaction),
+        }
+    }
+
+    async fn execute(&mut self, view: u64, transactions: &[Transaction]) -> Vec<u8> {
+        match self {
+            Replicated::Chat(chat) => chat.execute(view, transactions).await,
+            Replicated::Kv(store) => store.execute(view, transactions).await,
+        }
+    }
+
+    fn query(&self, key: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Replicated::Chat(chat) => chat.query(key),
+            Replicated::Kv(store) => store.query(key),
+        }
+    }
+
+    fn snapshot(&self) -> Option<Vec<u8>> {
+        match self {
+            Replicated::Chat(chat) => chat.snapshot(),
+            Replicated::Kv(store) => store.snapshot(),
+        }
+    }
+
+    async fn restore(&mut self, view: u64, state: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Replicated::Chat(chat) => chat.restore(view, state).await,
+            Replicated::Kv(store) => store.restore(view, state).await,
+        }
+    }
+
+    async fn prune(&mut self, view: u64) {
+        match self {
+            Replicated::Chat(chat) => chat.prune(view).await,
+            Replicated::Kv(store) => store.prune(view).await,
+        }
+    }
+}
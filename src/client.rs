@@ -0,0 +1,238 @@
+//! Submit chats to several validators and wait for their inclusion.
+//!
+//! Sending the same chat to `fanout` validators means it is finalized as long as one
+//! of them is honest and its mini-block reaches the leader: a censoring leader would
+//! have to omit mini-blocks holding a stake quorum, which honest validators reject.
+
+use crate::api::{Nonce, Submitted};
+use crate::application::{mini_block::chat_digest, receipt::Receipt};
+use crate::light_client::{self, LightClient};
+use commonware_cryptography::{Digest, Sha256};
+use commonware_utils::hex;
+use futures::future::join_all;
+use rand::{rngs::OsRng, seq::SliceRandom};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Errors that can occur while submitting a chat.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("no validator accepted the chat")]
+    Rejected,
+    #[error("no validator returned the next nonce")]
+    Unavailable,
+    #[error("chat was not finalized within {0:?}")]
+    Timeout(Duration),
+    #[error("invalid receipt from {0}: {1}")]
+    InvalidReceipt(String, light_client::Error),
+}
+
+/// Configuration for the client.
+pub struct Config {
+    /// Base urls of the validators' apis, e.g. `http://127.0.0.1:4001`.
+    pub validators: Vec<String>,
+
+    /// Number of validators (picked at random) each chat is sent to. Validators refusing
+    /// it are replaced by others until as many accepted it or all were tried.
+    pub fanout: usize,
+
+    /// Interval between receipt queries.
+    pub poll_interval: Duration,
+
+    /// Verifies receipts against the group public key, receipts are trusted when unset.
+    pub light_client: Option<LightClient>,
+}
+
+/// Outcome of sending a chat to each selected validator.
+#[derive(Debug)]
+pub struct Submission {
+    /// Digest identifying the chat in receipt queries.
+    pub digest: Digest,
+    /// Validators that queued the chat.
+    pub accepted: Vec<String>,
+    /// Validators that refused or could not be reached, with the reason.
+    pub rejected: Vec<(String, String)>,
+    /// When the chat was sent.
+    pub sent: Instant,
+}
+
+/// Inclusion of a submitted chat.
+#[derive(Debug)]
+pub struct Inclusion {
+    pub receipt: Receipt,
+    /// Validator that served the receipt.
+    pub validator: String,
+    /// Time between sending the chat and observing its receipt.
+    pub latency: Duration,
+    /// Whether the receipt was checked against the group public key.
+    pub verified: bool,
+}
+
+/// Client fanning chats out to validators.
+pub struct Client {
+    http: reqwest::Client,
+    validators: Vec<String>,
+    fanout: usize,
+    poll_interval: Duration,
+    light_client: Option<LightClient>,
+}
+
+impl Client {
+    pub fn new(config: Config) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            validators: config
+                .validators
+                .iter()
+                .map(|validator| validator.trim_end_matches('/').to_string())
+                .collect(),
+            fanout: config.fanout,
+            poll_interval: config.poll_interval,
+            light_client: config.light_client,
+        }
+    }
+
+    /// Send a chat to `fanout` validators at once, then to others in place of those
+    /// refusing it (or answering with another digest) until `fanout` accepted it or every
+    /// validator was tried.
+    pub async fn submit(&self, chat: &[u8]) -> Result<Submission, Error> {
+        let digest = chat_digest(&mut Sha256::default(), chat);
+        let mut candidates = self.validators.clone();
+        candidates.shuffle(&mut OsRng);
+        let mut candidates = candidates.into_iter();
+        let sent = Instant::now();
+
+        let mut accepted = Vec::new();
+        let mut rejected = Vec::new();
+        while accepted.len() < self.fanout {
+            let selected: Vec<String> = candidates
+                .by_ref()
+                .take(self.fanout - accepted.len())
+                .collect();
+            if selected.is_empty() {
+                break;
+            }
+            let responses =
+                join_all(selected.iter().map(|validator| self.send(validator, chat))).await;
+            for (validator, response) in selected.into_iter().zip(responses) {
+                match response {
+                    Ok(submitted) if submitted.digest == hex(&digest) => accepted.push(validator),
+                    Ok(submitted) => {
+                        let reason = format!(
+                            "returned digest {}, expected {}",
+                            submitted.digest,
+                            hex(&digest)
+                        );
+                        rejected.push((validator, reason));
+                    }
+                    Err(reason) => rejected.push((validator, reason)),
+                }
+            }
+        }
+        if accepted.is_empty() {
+            return Err(Error::Rejected);
+        }
+        Ok(Submission {
+            digest,
+            accepted,
+            rejected,
+            sent,
+        })
+    }
+
+    /// Poll the validators that accepted the chat until one serves a valid receipt. A
+    /// validator serving an invalid receipt is no longer asked, and its error is returned
+    /// once no validator is left.
+    pub async fn wait(
+        &mut self,
+        submission: &Submission,
+        chat: &[u8],
+        timeout: Duration,
+    ) -> Result<Inclusion, Error> {
+        let deadline = Instant::now() + timeout;
+        let mut remaining = submission.accepted.clone();
+        loop {
+            let mut invalid = None;
+            for validator in remaining.iter() {
+                let Ok(receipt) = self.receipt(validator, &submission.digest).await else {
+                    continue;
+                };
+                let latency = submission.sent.elapsed();
+                let verified = match self.light_client.as_mut() {
+                    Some(light_client) => {
+                        if let Err(e) = light_client.verify_receipt(&receipt, chat) {
+                            invalid = Some(Error::InvalidReceipt(validator.clone(), e));
+                            continue;
+                        }
+                        true
+                    }
+                    None => false,
+                };
+                return Ok(Inclusion {
+                    receipt,
+                    validator: validator.clone(),
+                    latency,
+                    verified,
+                });
+            }
+            if let Some(Error::InvalidReceipt(validator, e)) = invalid {
+                remaining.retain(|candidate| *candidate != validator);
+                if remaining.is_empty() {
+                    return Err(Error::InvalidReceipt(validator, e));
+                }
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::Timeout(timeout));
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Nonce the next transaction of `sender` must carry, as seen by the validator
+    /// that finalized the most views among those answering.
+    pub async fn next_nonce(&self, sender: &[u8]) -> Result<u64, Error> {
+        let responses = join_all(self.validators.iter().map(|validator| async move {
+            self.http
+                .get(format!("{}/nonces/{}", validator, hex(sender)))
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<Nonce>()
+                .await
+        }))
+        .await;
+        responses
+            .into_iter()
+            .flatten()
+            .max_by_key(|nonce| nonce.view)
+            .map(|nonce| nonce.next)
+            .ok_or(Error::Unavailable)
+    }
+
+    /// Send the chat to a validator, returning the reason it was refused on failure.
+    async fn send(&self, validator: &str, chat: &[u8]) -> Result<Submitted, String> {
+        let response = self
+            .http
+            .post(format!("{}/chats", validator))
+            .body(chat.to_vec())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let status = response.status();
+        if !status.is_success() {
+            let reason = response.text().await.unwrap_or_default();
+            return Err(format!("{}: {}", status, reason));
+        }
+        response.json().await.map_err(|e| e.to_string())
+    }
+
+    async fn receipt(&self, validator: &str, digest: &Digest) -> Result<Receipt, reqwest::Error> {
+        self.http
+            .get(format!("{}/receipts/{}", validator, hex(digest)))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+}
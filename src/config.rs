@@ -0,0 +1,645 @@
+//! Validator configuration file.
+//!
+//! Every section can be omitted, in which case the defaults below (the values the
+//! validator used to hardcode) apply. Command line flags override the file.
+
+use commonware_cryptography::PublicKey;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs,
+    net::{SocketAddr, ToSocketAddrs},
+    path::Path,
+    time::Duration,
+};
+use thiserror::Error;
+
+use crate::application::{attestation, chatter::selection, mini_block::ProtoBlock, supervisor::{total_weight, weighted_threshold}, sync};
+use crate::keys;
+
+/// Errors that can occur when loading a configuration.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to read config: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse config: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("invalid config: {0}")]
+    Invalid(String),
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub identity: Identity,
+    pub network: Network,
+    pub consensus: Consensus,
+    pub chatter: Chatter,
+    pub mempool: Mempool,
+    pub application: Application,
+    pub storage: Storage,
+    pub sync: Sync,
+    pub history: History,
+    pub api: Api,
+}
+
+/// Who we are and who we run consensus with.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Identity {
+    /// File holding our private key (or a seed with `insecure_seeds`).
+    pub key: Option<String>,
+    /// File holding our share of the group polynomial.
+    pub share: Option<String>,
+    /// Hex-encoded group polynomial.
+    pub polynomial: Option<String>,
+    /// Public keys of all participants (or seeds with `insecure_seeds`).
+    pub participants: Vec<String>,
+    /// Stake of each participant, in the same order (defaults to 1 each).
+    pub weights: Vec<u64>,
+    /// Files holding shares dealt for future validator sets.
+    pub epoch_shares: Vec<String>,
+    /// Derive keys from integer seeds (testing only).
+    pub insecure_seeds: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Network {
+    /// Address to bind (e.g. `0.0.0.0:3001` or `[::]:3001`).
+    pub listen: Option<SocketAddr>,
+    /// Address other peers should dial, defaults to `listen`.
+    pub advertise: Option<SocketAddr>,
+    /// Peers to connect to on startup, as `<public key>@<host>:<port>`.
+    pub bootstrappers: Vec<String>,
+    /// Largest message sent or accepted, which must fit a full proto-block.
+    pub max_message_size: usize,
+    /// Messages per second accepted from each peer on the consensus channel.
+    pub consensus_rate: u32,
+    /// Messages per second accepted from each peer on the resolver channel.
+    pub resolver_rate: u32,
+    /// Messages per second accepted from each peer on the chatter channel.
+    pub chatter_rate: u32,
+    /// Messages per second accepted from each peer on the proto-block channel.
+    pub block_rate: u32,
+    /// Messages per second accepted from each peer on the sync channel.
+    pub sync_rate: u32,
+    /// Messages in flight per channel.
+    pub message_backlog: usize,
+    /// zstd compression level, none to disable compression.
+    pub compression: Option<i32>,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Self {
+            listen: None,
+            advertise: None,
+            bootstrappers: Vec::new(),
+            max_message_size: 16 * 1024 * 1024, // 16MB
+            consensus_rate: 10,
+            resolver_rate: 10,
+            chatter_rate: 10,
+            block_rate: 10,
+            sync_rate: 10,
+            message_backlog: 256,
+            compression: Some(3),
+        }
+    }
+}
+
+/// Tuning of `threshold_simplex`, durations are in milliseconds.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Consensus {
+    pub mailbox_size: usize,
+    pub replay_concurrency: usize,
+    pub leader_timeout_ms: u64,
+    pub notarization_timeout_ms: u64,
+    pub nullify_retry_ms: u64,
+    pub fetch_timeout_ms: u64,
+    pub activity_timeout: u64,
+    pub max_fetch_count: u64,
+    pub max_fetch_size: usize,
+    pub fetch_concurrent: usize,
+    pub fetch_rate_per_peer: u32,
+}
+
+impl Default for Consensus {
+    fn default() -> Self {
+        Self {
+            mailbox_size: 1024,
+            replay_concurrency: 1,
+            leader_timeout_ms: 1_000,
+            notarization_timeout_ms: 2_000,
+            nullify_retry_ms: 10_000,
+            fetch_timeout_ms: 1_000,
+            activity_timeout: 10,
+            max_fetch_count: 32,
+            max_fetch_size: 1024 * 512,
+            fetch_concurrent: 2,
+            fetch_rate_per_peer: 1,
+        }
+    }
+}
+
+impl Consensus {
+    pub fn leader_timeout(&self) -> Duration {
+        Duration::from_millis(self.leader_timeout_ms)
+    }
+
+    pub fn notarization_timeout(&self) -> Duration {
+        Duration::from_millis(self.notarization_timeout_ms)
+    }
+
+    pub fn nullify_retry(&self) -> Duration {
+        Duration::from_millis(self.nullify_retry_ms)
+    }
+
+    pub fn fetch_timeout(&self) -> Duration {
+        Duration::from_millis(self.fetch_timeout_ms)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Chatter {
+    /// Messages from other actors to hold before blocking.
+    pub mailbox_size: usize,
+    /// Mini-blocks waiting to be sent over p2p before blocking.
+    pub p2p_mailbox_size: usize,
+    /// Messages from consensus to hold in the application's backlog.
+    pub application_mailbox_size: usize,
+    /// Threads checking mini-block signatures.
+    pub verifier_threads: usize,
+    /// Mini-block signatures verified together in a batch.
+    pub verify_batch_size: usize,
+    /// Valid mini-blocks remembered so they are not verified again.
+    pub verified_cache_size: usize,
+    /// How mini-blocks are signed: `ed25519` (one signature each) or `bls` (aggregated
+    /// into a single signature per proposal). All validators must agree.
+    pub attestation: attestation::Kind,
+}
+
+impl Default for Chatter {
+    fn default() -> Self {
+        Self {
+            mailbox_size: 100,
+            p2p_mailbox_size: 100,
+            application_mailbox_size: 1024,
+            verifier_threads: 4,
+            verify_batch_size: 64,
+            verified_cache_size: 10_000,
+            attestation: attestation::Kind::Ed25519,
+        }
+    }
+}
+
+/// Limits of the queue of user transactions waiting for our next mini-block.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Mempool {
+    pub max_transactions: usize,
+    /// Encoded size of all queued transactions.
+    pub max_bytes: usize,
+    pub max_per_sender: usize,
+    /// Views a transaction may wait for a mini-block before being dropped.
+    pub expiry_views: u64,
+    /// Transactions included in each of our mini-blocks.
+    pub mini_block_transactions: usize,
+    /// Encoded size of the transactions included in each of our mini-blocks.
+    pub mini_block_bytes: usize,
+    /// Who goes first when the mempool exceeds a mini-block: `fifo`, `fee` or `round_robin`.
+    pub policy: selection::Kind,
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Self {
+            max_transactions: 10_000,
+            max_bytes: 16 * 1024 * 1024, // 16MB
+            max_per_sender: 64,
+            expiry_views: 100,
+            mini_block_transactions: 1024,
+            mini_block_bytes: 64 * 1024, // 64KB
+            policy: selection::Kind::Fifo,
+        }
+    }
+}
+
+/// State machine replicated by the validator.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateMachine {
+    #[default]
+    Chat,
+    Kv,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Application {
+    /// Every validator must run the same one.
+    pub state_machine: StateMachine,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Storage {
+    pub directory: Option<String>,
+    /// Journal partition used by consensus.
+    pub partition: String,
+    /// Journal partition used by state machines that persist their state.
+    pub state_partition: String,
+    /// Journal partition holding the mini-blocks we signed.
+    pub mini_block_partition: String,
+    /// Journal partition holding finalized proto-blocks.
+    pub block_partition: String,
+    /// Journal partition holding snapshots of the state.
+    pub snapshot_partition: String,
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            partition: String::from("log"),
+            state_partition: String::from("state"),
+            mini_block_partition: String::from("mini_blocks"),
+            block_partition: String::from("blocks"),
+            snapshot_partition: String::from("snapshots"),
+        }
+    }
+}
+
+/// Catching up on finalized proto-blocks from peers.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sync {
+    /// Snapshot file (as served by `/snapshot`) to start from instead of genesis.
+    pub snapshot: Option<String>,
+    /// Finalized proto-blocks requested from a peer at once.
+    pub batch: usize,
+    /// Unsuccessful requests for missing views between warnings that execution is stalled.
+    pub attempts: usize,
+}
+
+impl Default for Sync {
+    fn default() -> Self {
+        Self {
+            snapshot: None,
+            batch: 64,
+            attempts: 16,
+        }
+    }
+}
+
+/// Snapshots of the state and pruning of what is on disk.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct History {
+    /// Finalized views between snapshots.
+    pub snapshot_interval: u64,
+    /// Snapshots kept on disk.
+    pub retained_snapshots: usize,
+    /// Views of finalized proto-blocks and of our signed mini-blocks kept on disk.
+    pub retention_views: u64,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            snapshot_interval: 1_000,
+            retained_snapshots: 2,
+            retention_views: 10_000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Api {
+    /// Address the API server listens on, disabled when unset.
+    pub listen: Option<SocketAddr>,
+    /// Finalized proto-blocks kept in memory to answer receipt queries.
+    pub retained_blocks: usize,
+}
+
+impl Default for Api {
+    fn default() -> Self {
+        Self {
+            listen: None,
+            retained_blocks: 1024,
+        }
+    }
+}
+
+impl Config {
+    /// Read a configuration file.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let raw = fs::read(path)?;
+        Ok(serde_json::from_slice(&raw)?)
+    }
+
+    /// Check the configuration is complete and consistent.
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |reason: &str| Err(Error::Invalid(reason.to_string()));
+
+        // Identity
+        if self.identity.key.is_none() {
+            return invalid("identity.key is required");
+        }
+        if self.identity.share.is_none() {
+            return invalid("identity.share is required");
+        }
+        if self.identity.polynomial.is_none() {
+            return invalid("identity.polynomial is required");
+        }
+        if self.identity.participants.is_empty() {
+            return invalid("identity.participants must not be empty");
+        }
+        if self.identity.insecure_seeds && !cfg!(feature = "insecure-seeds") {
+            return invalid("identity.insecure_seeds requires building with --features insecure-seeds");
+        }
+        if !self.identity.weights.is_empty()
+            && self.identity.weights.len() != self.identity.participants.len()
+        {
+            return invalid("identity.weights must have one entry per participant");
+        }
+        if self.identity.weights.iter().any(|weight| *weight == 0) {
+            return invalid("identity.weights must be positive");
+        }
+        if !self.identity.weights.is_empty() && total_weight(&self.identity.weights).is_none() {
+            return invalid("identity.weights must not overflow");
+        }
+        if !self.identity.weights.is_empty() && weighted_threshold(&self.identity.weights).is_none() {
+            return invalid("identity.weights are too skewed for a threshold of validators to sign");
+        }
+
+        // Network
+        let Some(listen) = self.network.listen else {
+            return invalid("network.listen is required");
+        };
+        let advertise = self.network.advertise.unwrap_or(listen);
+        if advertise.ip().is_unspecified() {
+            return invalid("network.advertise is required when listening on an unspecified address");
+        }
+        if advertise.port() == 0 {
+            return invalid("network.advertise must have a port");
+        }
+        if self.network.max_message_size == 0 || self.network.message_backlog == 0 {
+            return invalid("network.max_message_size and network.message_backlog must be positive");
+        }
+        if self.network.consensus_rate == 0
+            || self.network.resolver_rate == 0
+            || self.network.chatter_rate == 0
+            || self.network.block_rate == 0
+            || self.network.sync_rate == 0
+        {
+            return invalid("network rates must be positive");
+        }
+
+        // Consensus
+        let consensus = &self.consensus;
+        if consensus.leader_timeout_ms == 0 || consensus.fetch_timeout_ms == 0 {
+            return invalid("consensus timeouts must be positive");
+        }
+        if consensus.leader_timeout_ms > consensus.notarization_timeout_ms {
+            return invalid("consensus.leader_timeout_ms must not exceed consensus.notarization_timeout_ms");
+        }
+        if consensus.activity_timeout == 0 {
+            return invalid("consensus.activity_timeout must be positive");
+        }
+        if consensus.mailbox_size == 0
+            || consensus.replay_concurrency == 0
+            || consensus.fetch_concurrent == 0
+            || consensus.max_fetch_count == 0
+            || consensus.max_fetch_size == 0
+            || consensus.fetch_rate_per_peer == 0
+        {
+            return invalid("consensus limits must be positive");
+        }
+
+        // Chatter
+        if self.chatter.mailbox_size == 0
+            || self.chatter.p2p_mailbox_size == 0
+            || self.chatter.application_mailbox_size == 0
+        {
+            return invalid("chatter mailbox sizes must be positive");
+        }
+        if self.chatter.verifier_threads == 0
+            || self.chatter.verify_batch_size == 0
+            || self.chatter.verified_cache_size == 0
+        {
+            return invalid("chatter verifier limits must be positive");
+        }
+
+        // Mempool
+        if self.mempool.max_transactions == 0
+            || self.mempool.max_bytes == 0
+            || self.mempool.max_per_sender == 0
+            || self.mempool.expiry_views == 0
+            || self.mempool.mini_block_transactions == 0
+            || self.mempool.mini_block_bytes == 0
+        {
+            return invalid("mempool limits must be positive");
+        }
+
+        // Proto-blocks are relayed and served to peers whole, holding a mini-block of
+        // every participant
+        let proto_block = ProtoBlock::max_encoded_size(
+            self.identity.participants.len(),
+            self.mempool.mini_block_transactions,
+            self.mempool.mini_block_bytes,
+        )
+        .saturating_add(sync::FINALIZED_OVERHEAD);
+        if self.network.max_message_size < proto_block {
+            return Err(Error::Invalid(format!(
+                "network.max_message_size must be at least {} bytes to carry a full proto-block",
+                proto_block
+            )));
+        }
+
+        // Storage
+        if self.storage.directory.is_none() {
+            return invalid("storage.directory is required");
+        }
+        let partitions = [
+            &self.storage.partition,
+            &self.storage.state_partition,
+            &self.storage.mini_block_partition,
+            &self.storage.block_partition,
+            &self.storage.snapshot_partition,
+        ];
+        if partitions.iter().any(|partition| partition.is_empty()) {
+            return invalid("storage partitions must not be empty");
+        }
+        if partitions.iter().collect::<HashSet<_>>().len() != partitions.len() {
+            return invalid("storage partitions must be distinct");
+        }
+
+        // Sync
+        if self.sync.batch == 0 || self.sync.attempts == 0 {
+            return invalid("sync limits must be positive");
+        }
+
+        // History
+        if self.history.snapshot_interval == 0 || self.history.retained_snapshots == 0 {
+            return invalid("history.snapshot_interval and history.retained_snapshots must be positive");
+        }
+        if self.history.retention_views < self.consensus.activity_timeout {
+            return invalid("history.retention_views must be at least consensus.activity_timeout");
+        }
+
+        // Api
+        if self.api.retained_blocks == 0 {
+            return invalid("api.retained_blocks must be positive");
+        }
+        Ok(())
+    }
+}
+
+impl Network {
+    /// Address other peers should dial.
+    pub fn advertise(&self) -> Option<SocketAddr> {
+        self.advertise.or(self.listen)
+    }
+}
+
+/// Parse a bootstrapper given as `<public key>@<host>:<port>`, resolving the host.
+pub fn resolve_bootstrapper(
+    value: &str,
+    insecure_seeds: bool,
+) -> Result<(PublicKey, SocketAddr), Error> {
+    let invalid = || Error::Invalid(format!("bootstrapper {} not well-formed", value));
+    let (key, address) = value.split_once('@').ok_or_else(invalid)?;
+    let key = keys::parse_participant(key, insecure_seeds).map_err(|_| invalid())?;
+    let address = address
+        .to_socket_addrs()
+        .map_err(|e| Error::Invalid(format!("failed to resolve bootstrapper {}: {}", value, e)))?
+        .next()
+        .ok_or_else(invalid)?;
+    Ok((key, address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Smallest configuration passing validation
+    fn valid() -> Config {
+        let mut config = Config::default();
+        config.identity.key = Some("key".to_string());
+        config.identity.share = Some("share".to_string());
+        config.identity.polynomial = Some("00".to_string());
+        config.identity.participants = vec!["a".to_string(), "b".to_string()];
+        config.network.listen = Some("127.0.0.1:3001".parse().unwrap());
+        config.storage.directory = Some("/tmp/little-dipper".to_string());
+        config
+    }
+
+    fn reason(config: &Config) -> String {
+        match config.validate() {
+            Err(Error::Invalid(reason)) => reason,
+            other => panic!("expected an invalid config, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_defaults() {
+        assert!(valid().validate().is_ok());
+    }
+
+    #[test]
+    fn test_example() {
+        let config: Config = serde_json::from_str(include_str!("../bench/validator.json")).unwrap();
+        assert_eq!(config.chatter.verifier_threads, 4);
+        assert!(serde_json::from_str::<Config>(r#"{"identity": {"unknown": 1}}"#).is_err());
+    }
+
+    #[test]
+    fn test_identity() {
+        let mut config = valid();
+        config.identity.key = None;
+        assert_eq!(reason(&config), "identity.key is required");
+
+        let mut config = valid();
+        config.identity.weights = vec![1];
+        assert_eq!(reason(&config), "identity.weights must have one entry per participant");
+
+        let mut config = valid();
+        config.identity.weights = vec![1, 0];
+        assert_eq!(reason(&config), "identity.weights must be positive");
+
+        let mut config = valid();
+        config.identity.weights = vec![u64::MAX, 1];
+        assert_eq!(reason(&config), "identity.weights must not overflow");
+
+        // 3 of the 4 shares are needed but a single partial signature comes from each
+        let mut config = valid();
+        config.identity.weights = vec![3, 1];
+        assert_eq!(reason(&config), "identity.weights are too skewed for a threshold of validators to sign");
+
+        let mut config = valid();
+        config.identity.weights = vec![3, 3];
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_network() {
+        let mut config = valid();
+        config.network.listen = Some("0.0.0.0:3001".parse().unwrap());
+        assert_eq!(
+            reason(&config),
+            "network.advertise is required when listening on an unspecified address"
+        );
+        config.network.advertise = Some("10.0.0.1:3001".parse().unwrap());
+        assert!(config.validate().is_ok());
+
+        let mut config = valid();
+        config.network.sync_rate = 0;
+        assert_eq!(reason(&config), "network rates must be positive");
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut config = valid();
+        config.consensus.leader_timeout_ms = config.consensus.notarization_timeout_ms + 1;
+        assert_eq!(
+            reason(&config),
+            "consensus.leader_timeout_ms must not exceed consensus.notarization_timeout_ms"
+        );
+
+        let mut config = valid();
+        config.chatter.verifier_threads = 0;
+        assert_eq!(reason(&config), "chatter verifier limits must be positive");
+
+        let mut config = valid();
+        config.mempool.max_per_sender = 0;
+        assert_eq!(reason(&config), "mempool limits must be positive");
+
+        let mut config = valid();
+        config.network.max_message_size = config.mempool.mini_block_bytes;
+        assert!(reason(&config).starts_with("network.max_message_size must be at least"));
+        config.network.max_message_size = ProtoBlock::max_encoded_size(2, 1024, 64 * 1024) + sync::FINALIZED_OVERHEAD;
+        assert!(config.validate().is_ok());
+
+        let mut config = valid();
+        config.history.retention_views = config.consensus.activity_timeout - 1;
+        assert_eq!(
+            reason(&config),
+            "history.retention_views must be at least consensus.activity_timeout"
+        );
+    }
+
+    #[test]
+    fn test_storage() {
+        let mut config = valid();
+        config.storage.block_partition = config.storage.partition.clone();
+        assert_eq!(reason(&config), "storage partitions must be distinct");
+
+        let mut config = valid();
+        config.storage.state_partition = String::new();
+        assert_eq!(reason(&config), "storage partitions must not be empty");
+    }
+}
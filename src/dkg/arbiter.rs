@@ -0,0 +1,178 @@
+use commonware_cryptography::{
+    bls12381::{
+        dkg,
+        primitives::{
+            group::{self, Element},
+            poly,
+        },
+    },
+    PublicKey,
+};
+use commonware_macros::select;
+use commonware_p2p::{Receiver, Recipients, Sender};
+use commonware_runtime::Clock;
+use commonware_utils::hex;
+use prost::Message as _;
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+use super::{owners, participant, participants, Config};
+use crate::wire;
+
+/// Coordinates a round: opens it, collects commitments from dealers and publishes
+/// the dealings every player must combine.
+pub struct Arbiter<E: Clock> {
+    runtime: E,
+    config: Config,
+}
+
+impl<E: Clock> Arbiter<E> {
+    pub fn new(runtime: E, config: Config) -> Self {
+        Self { runtime, config }
+    }
+
+    /// Run a single round, returning the new group polynomial if it succeeded.
+    pub async fn run(
+        self,
+        round: u64,
+        mut sender: impl Sender,
+        mut receiver: impl Receiver,
+    ) -> Option<poly::Public> {
+        let t = self.config.threshold().expect("player stakes not well-formed");
+        let dealer_indices = self.config.dealer_indices().expect("dealer stakes not well-formed");
+        let player_indices = self.config.player_indices().expect("player stakes not well-formed");
+        let dealer_owners = owners(&self.config.dealers, &dealer_indices);
+
+        // Open the round
+        let start = wire::Dkg {
+            round,
+            payload: Some(wire::dkg::Payload::Start(wire::Start {
+                group: self.config.previous.as_ref().map(|previous| previous.serialize()),
+            })),
+        }
+        .encode_to_vec();
+        sender
+            .send(Recipients::All, start.into(), true)
+            .await
+            .expect("failed to send start");
+        info!(round, "started dkg round");
+
+        // Collect commitments until every dealer responded or the round times out.
+        // Dealers first exchange shares and acks with players, so allow two phases.
+        let deadline = self.runtime.current() + 2 * self.config.phase_timeout;
+        let mut arbiter = dkg::Arbiter::new(
+            self.config.previous.clone(),
+            participants(&dealer_indices),
+            participants(&player_indices),
+            1,
+        );
+        // Disqualify every share index a misbehaving dealer deals for
+        let disqualify = |arbiter: &mut dkg::Arbiter, dealer: &PublicKey| {
+            if let Some(position) = self.config.dealers.iter().position(|d| d == dealer) {
+                for index in dealer_indices[position].iter() {
+                    arbiter.disqualify(participant(*index));
+                }
+            }
+        };
+        loop {
+            select! {
+                _ = self.runtime.sleep_until(deadline) => {
+                    warn!(round, "timed out waiting for commitments");
+                    break;
+                },
+                result = receiver.recv() => {
+                    let Ok((dealer, msg)) = result else {
+                        return None;
+                    };
+                    let Ok(msg) = wire::Dkg::decode(msg) else {
+                        disqualify(&mut arbiter, &dealer);
+                        continue;
+                    };
+                    if msg.round != round {
+                        continue;
+                    }
+                    let Some(wire::dkg::Payload::Commitment(commitment)) = msg.payload else {
+                        disqualify(&mut arbiter, &dealer);
+                        continue;
+                    };
+                    if dealer_owners.get(&commitment.dealer) != Some(&dealer) {
+                        disqualify(&mut arbiter, &dealer);
+                        continue;
+                    }
+                    let index = commitment.dealer;
+                    let Some(polynomial) = poly::Public::deserialize(&commitment.commitment, t) else {
+                        arbiter.disqualify(participant(index));
+                        continue;
+                    };
+                    let mut reveals = Vec::new();
+                    for reveal in commitment.reveals.iter() {
+                        match group::Share::deserialize(reveal) {
+                            Some(reveal) => reveals.push(reveal),
+                            None => break,
+                        }
+                    }
+                    if reveals.len() != commitment.reveals.len() {
+                        arbiter.disqualify(participant(index));
+                        continue;
+                    }
+                    if let Err(e) = arbiter.commitment(participant(index), polynomial, commitment.acks, reveals) {
+                        warn!(round, dealer = hex(&dealer), index, ?e, "rejected commitment");
+                        continue;
+                    }
+                    if arbiter.ready() {
+                        break;
+                    }
+                },
+            }
+        }
+
+        // Publish the selected dealings
+        let (result, disqualified) = arbiter.finalize();
+        for dealer in disqualified {
+            warn!(round, dealer = hex(&dealer), "disqualified dealer index");
+        }
+        let output = match result {
+            Ok(output) => output,
+            Err(e) => {
+                warn!(round, ?e, "dkg round failed");
+                let abort = wire::Dkg {
+                    round,
+                    payload: Some(wire::dkg::Payload::Abort(wire::Abort {})),
+                }
+                .encode_to_vec();
+                let _ = sender.send(Recipients::All, abort.into(), true).await;
+                return None;
+            }
+        };
+        let commitments: HashMap<u32, Vec<u8>> = output
+            .commitments
+            .iter()
+            .map(|(dealer, commitment)| (*dealer, commitment.serialize()))
+            .collect();
+        for (player, indices) in self.config.players.iter().zip(player_indices.iter()) {
+            // Each player only receives the shares revealed for its indices
+            let mut reveals = Vec::new();
+            for (dealer, shares) in output.reveals.iter() {
+                for share in shares.iter().filter(|share| indices.contains(&share.index)) {
+                    reveals.push(wire::Reveal {
+                        dealer: *dealer,
+                        share: share.serialize(),
+                    });
+                }
+            }
+            let success = wire::Dkg {
+                round,
+                payload: Some(wire::dkg::Payload::Success(wire::Success {
+                    commitments: commitments.clone(),
+                    reveals,
+                })),
+            }
+            .encode_to_vec();
+            let _ = sender
+                .send(Recipients::One(player.clone()), success.into(), true)
+                .await;
+        }
+        info!(round, public = hex(&poly::public(&output.public).serialize()), "dkg round succeeded");
+        Some(output.public)
+    }
+}
@@ -0,0 +1,306 @@
+use commonware_cryptography::{
+    bls12381::{
+        dkg,
+        primitives::{group, poly},
+    },
+    PublicKey,
+};
+use commonware_macros::select;
+use commonware_p2p::{Receiver, Recipients, Sender};
+use commonware_runtime::Clock;
+use commonware_utils::hex;
+use prost::Message as _;
+use rand::{CryptoRng, Rng};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tracing::{info, warn};
+
+use super::{owners, participant, participants, Config, Output};
+use crate::wire;
+
+/// Dealing of one share index: the dealer, its commitment, the shares it dealt and the
+/// player indices that acked them.
+type Dealing = (dkg::Dealer, poly::Public, Vec<group::Share>, HashSet<u32>);
+
+/// Deals a polynomial to every player (if it is a dealer) and combines the dealings
+/// selected by the arbiter into its shares (if it is a player).
+pub struct Contributor<E: Clock + Rng + CryptoRng> {
+    runtime: E,
+    me: PublicKey,
+    arbiter: PublicKey,
+    config: Config,
+    /// Our shares of `config.previous`, required to reshare.
+    shares: Vec<group::Share>,
+}
+
+impl<E: Clock + Rng + CryptoRng> Contributor<E> {
+    pub fn new(
+        runtime: E,
+        me: PublicKey,
+        arbiter: PublicKey,
+        config: Config,
+        shares: Vec<group::Share>,
+    ) -> Self {
+        Self {
+            runtime,
+            me,
+            arbiter,
+            config,
+            shares,
+        }
+    }
+
+    /// Participate in the next round opened by the arbiter, returning our shares if we
+    /// are a player and the round succeeded.
+    pub async fn run(
+        mut self,
+        mut sender: impl Sender,
+        mut receiver: impl Receiver,
+    ) -> Option<Output> {
+        let t = self.config.threshold().expect("player stakes not well-formed");
+        let dealer_indices = self.config.dealer_indices().expect("dealer stakes not well-formed");
+        let player_indices = self.config.player_indices().expect("player stakes not well-formed");
+        let dealer_owners = owners(&self.config.dealers, &dealer_indices);
+        let player_owners = owners(&self.config.players, &player_indices);
+
+        // Wait for the arbiter to open a round
+        let round = loop {
+            let Ok((peer, msg)) = receiver.recv().await else {
+                return None;
+            };
+            if peer != self.arbiter {
+                continue;
+            }
+            let Ok(msg) = wire::Dkg::decode(msg) else {
+                continue;
+            };
+            let Some(wire::dkg::Payload::Start(start)) = msg.payload else {
+                continue;
+            };
+            let expected = self.config.previous.as_ref().map(|previous| previous.serialize());
+            if start.group != expected {
+                warn!(round = msg.round, "arbiter started round for a different group");
+                return None;
+            }
+            break msg.round;
+        };
+        info!(round, "joined dkg round");
+
+        // Prepare our roles: a player per share index we receive and a dealer per share
+        // index we deal for
+        let mut players: BTreeMap<u32, dkg::Player> = player_owners
+            .iter()
+            .filter(|(_, owner)| **owner == self.me)
+            .map(|(index, _)| {
+                let player = dkg::Player::new(
+                    participant(*index),
+                    self.config.previous.clone(),
+                    participants(&dealer_indices),
+                    participants(&player_indices),
+                    1,
+                );
+                (*index, player)
+            })
+            .collect();
+        let mut dealings: BTreeMap<u32, Dealing> = BTreeMap::new();
+        for (dealer_index, owner) in dealer_owners.iter() {
+            if *owner != self.me {
+                continue;
+            }
+            let previous = match self.config.previous {
+                Some(_) => match self.shares.iter().find(|share| share.index == *dealer_index) {
+                    Some(share) => Some(share.clone()),
+                    None => {
+                        warn!(round, index = dealer_index, "missing share to reshare");
+                        continue;
+                    }
+                },
+                None => None,
+            };
+            let (mut dealer, commitment, shares) =
+                dkg::Dealer::new(&mut self.runtime, previous, participants(&player_indices));
+            let mut acks = HashSet::new();
+            for share in shares.iter() {
+                let recipient = &player_owners[&share.index];
+                if *recipient == self.me {
+                    // Deliver our own share locally
+                    let player = players.get_mut(&share.index).unwrap();
+                    if player
+                        .share(participant(*dealer_index), commitment.clone(), share.clone())
+                        .is_ok()
+                    {
+                        let _ = dealer.ack(participant(share.index));
+                        acks.insert(share.index);
+                    }
+                    continue;
+                }
+                let msg = wire::Dkg {
+                    round,
+                    payload: Some(wire::dkg::Payload::Share(wire::Share {
+                        dealer: *dealer_index,
+                        commitment: commitment.serialize(),
+                        share: share.serialize(),
+                    })),
+                }
+                .encode_to_vec();
+                let _ = sender
+                    .send(Recipients::One(recipient.clone()), msg.into(), true)
+                    .await;
+            }
+            dealings.insert(*dealer_index, (dealer, commitment, shares, acks));
+        }
+
+        // Exchange shares and acks until the first phase ends
+        let deadline = self.runtime.current() + self.config.phase_timeout;
+        loop {
+            select! {
+                _ = self.runtime.sleep_until(deadline) => {
+                    break;
+                },
+                result = receiver.recv() => {
+                    let Ok((peer, msg)) = result else {
+                        return None;
+                    };
+                    let Ok(msg) = wire::Dkg::decode(msg) else {
+                        continue;
+                    };
+                    if msg.round != round {
+                        continue;
+                    }
+                    match msg.payload {
+                        Some(wire::dkg::Payload::Share(share)) => {
+                            if dealer_owners.get(&share.dealer) != Some(&peer) {
+                                continue;
+                            }
+                            let Some(commitment) = poly::Public::deserialize(&share.commitment, t) else {
+                                continue;
+                            };
+                            let dealer = share.dealer;
+                            let Some(share) = group::Share::deserialize(&share.share) else {
+                                continue;
+                            };
+                            let player_index = share.index;
+                            let Some(player) = players.get_mut(&player_index) else {
+                                continue;
+                            };
+                            if let Err(e) = player.share(participant(dealer), commitment, share) {
+                                warn!(round, dealer = hex(&peer), index = dealer, ?e, "invalid share");
+                                continue;
+                            }
+                            let ack = wire::Dkg {
+                                round,
+                                payload: Some(wire::dkg::Payload::Ack(wire::Ack {
+                                    dealer,
+                                    player: player_index,
+                                })),
+                            }
+                            .encode_to_vec();
+                            let _ = sender.send(Recipients::One(peer), ack.into(), true).await;
+                        }
+                        Some(wire::dkg::Payload::Ack(ack)) => {
+                            let Some((dealer, _, _, acks)) = dealings.get_mut(&ack.dealer) else {
+                                continue;
+                            };
+                            if player_owners.get(&ack.player) != Some(&peer) {
+                                continue;
+                            }
+                            if dealer.ack(participant(ack.player)).is_ok() {
+                                acks.insert(ack.player);
+                            }
+                        }
+                        _ => {}
+                    }
+                },
+            }
+        }
+
+        // Send our commitments to the arbiter, revealing shares of players that did not ack
+        for (dealer_index, (_, commitment, shares, acks)) in dealings {
+            let reveals = shares
+                .iter()
+                .filter(|share| !acks.contains(&share.index))
+                .map(|share| share.serialize())
+                .collect();
+            let msg = wire::Dkg {
+                round,
+                payload: Some(wire::dkg::Payload::Commitment(wire::Commitment {
+                    dealer: dealer_index,
+                    commitment: commitment.serialize(),
+                    acks: acks.into_iter().collect(),
+                    reveals,
+                })),
+            }
+            .encode_to_vec();
+            let _ = sender
+                .send(Recipients::One(self.arbiter.clone()), msg.into(), true)
+                .await;
+        }
+        if players.is_empty() {
+            return None;
+        }
+
+        // Wait for the arbiter to publish the selected dealings
+        let deadline = self.runtime.current() + 2 * self.config.phase_timeout;
+        let success = loop {
+            select! {
+                _ = self.runtime.sleep_until(deadline) => {
+                    warn!(round, "timed out waiting for arbiter");
+                    return None;
+                },
+                result = receiver.recv() => {
+                    let Ok((peer, msg)) = result else {
+                        return None;
+                    };
+                    if peer != self.arbiter {
+                        continue;
+                    }
+                    let Ok(msg) = wire::Dkg::decode(msg) else {
+                        continue;
+                    };
+                    if msg.round != round {
+                        continue;
+                    }
+                    match msg.payload {
+                        Some(wire::dkg::Payload::Success(success)) => break success,
+                        Some(wire::dkg::Payload::Abort(_)) => {
+                            warn!(round, "arbiter aborted round");
+                            return None;
+                        }
+                        _ => {}
+                    }
+                },
+            }
+        };
+
+        // Combine the selected dealings into our shares
+        let mut commitments = HashMap::new();
+        for (dealer, commitment) in success.commitments.into_iter() {
+            let commitment = poly::Public::deserialize(&commitment, t)?;
+            commitments.insert(dealer, commitment);
+        }
+        let mut reveals: HashMap<u32, HashMap<u32, group::Share>> = HashMap::new();
+        for reveal in success.reveals.into_iter() {
+            let share = group::Share::deserialize(&reveal.share)?;
+            reveals.entry(share.index).or_default().insert(reveal.dealer, share);
+        }
+        let mut public = None;
+        let mut shares = Vec::new();
+        for (index, player) in players {
+            let reveals = reveals.remove(&index).unwrap_or_default();
+            match player.finalize(commitments.clone(), reveals) {
+                Ok(output) => {
+                    public = Some(output.public);
+                    shares.push(output.share);
+                }
+                Err(e) => {
+                    warn!(round, index, ?e, "failed to recover share");
+                    return None;
+                }
+            }
+        }
+        info!(round, "dkg round succeeded");
+        Some(Output {
+            public: public?,
+            shares,
+        })
+    }
+}
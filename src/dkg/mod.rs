@@ -0,0 +1,260 @@
+//! Distributed key generation over the p2p network.
+//!
+//! Replaces the trusted `dealer` with a round in which every contributor deals a
+//! polynomial to every player and an arbiter (who learns nothing secret) picks the set of
+//! valid dealings. Each player ends up with its share of the group polynomial without any
+//! party knowing the group secret.
+//!
+//! When contributors already hold shares of a group polynomial, the same round reshares
+//! it to a (possibly different) set of players while keeping the group public key, which
+//! is how a new validator set gets its shares before a reconfiguration.
+//!
+//! Players receive one share per index [`share_indices`] gives them for their stake, and
+//! the new polynomial has the threshold validators expect for those stakes (see
+//! [`weighted_threshold`]), so the output can back a weighted validator set. Each share
+//! index is a player (and, when resharing, each share of the previous polynomial a
+//! dealer) of its own, so dealings are dealt and checked against that same threshold.
+
+use commonware_cryptography::{
+    bls12381::primitives::{group, poly},
+    PublicKey,
+};
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::application::supervisor::{share_indices, weighted_threshold};
+
+mod arbiter;
+pub use arbiter::Arbiter;
+mod contributor;
+pub use contributor::Contributor;
+
+/// Configuration shared by the arbiter and contributors.
+#[derive(Clone)]
+pub struct Config {
+    /// Participants dealing a polynomial (the current validator set when resharing).
+    pub dealers: Vec<PublicKey>,
+
+    /// Stake of each dealer, in the order of `dealers`. When resharing, a dealer deals
+    /// once per share of `previous` it holds.
+    pub dealer_weights: Vec<u64>,
+
+    /// Participants receiving a share.
+    pub players: Vec<PublicKey>,
+
+    /// Stake of each player, in the order of `players`.
+    pub weights: Vec<u64>,
+
+    /// Group polynomial being reshared, if any.
+    pub previous: Option<poly::Public>,
+
+    /// Maximum time to wait for each phase of the round.
+    pub phase_timeout: Duration,
+}
+
+impl Config {
+    /// Threshold of the polynomial dealt to the players, none if the stakes do not
+    /// match the players.
+    pub fn threshold(&self) -> Option<u32> {
+        if self.weights.len() != self.players.len() {
+            return None;
+        }
+        weighted_threshold(&self.weights)
+    }
+
+    /// Share indices of each dealer, in the order of `dealers`.
+    pub fn dealer_indices(&self) -> Option<Vec<Vec<u32>>> {
+        if self.dealer_weights.len() != self.dealers.len() {
+            return None;
+        }
+        share_indices(&self.dealer_weights)
+    }
+
+    /// Share indices of each player, in the order of `players`.
+    pub fn player_indices(&self) -> Option<Vec<Vec<u32>>> {
+        if self.weights.len() != self.players.len() {
+            return None;
+        }
+        share_indices(&self.weights)
+    }
+}
+
+/// Identity of share `index` in the dealings, ordered like the indices.
+fn participant(index: u32) -> PublicKey {
+    PublicKey::copy_from_slice(&index.to_be_bytes())
+}
+
+/// Identities of every share index.
+fn participants(indices: &[Vec<u32>]) -> Vec<PublicKey> {
+    let total = indices.iter().map(|owned| owned.len() as u32).sum();
+    (0..total).map(participant).collect()
+}
+
+/// Owner of each share index.
+fn owners(keys: &[PublicKey], indices: &[Vec<u32>]) -> BTreeMap<u32, PublicKey> {
+    keys.iter()
+        .zip(indices)
+        .flat_map(|(key, owned)| owned.iter().map(move |index| (*index, key.clone())))
+        .collect()
+}
+
+/// Result of a successful round for a player.
+pub struct Output {
+    pub public: poly::Public,
+    /// One share per index of the player, in order.
+    pub shares: Vec<group::Share>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commonware_cryptography::{bls12381::dkg, Ed25519, Scheme};
+    use commonware_p2p::simulated;
+    use commonware_runtime::{
+        deterministic::{Context, Executor},
+        Runner, Spawner,
+    };
+    use prometheus_client::registry::Registry;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    fn config(weights: Vec<u64>) -> Config {
+        let mut players: Vec<PublicKey> = (0..weights.len() as u64)
+            .map(|seed| Ed25519::from_seed(seed).public_key())
+            .collect();
+        players.sort();
+        Config {
+            dealers: players.clone(),
+            dealer_weights: vec![1; players.len()],
+            players,
+            weights,
+            previous: None,
+            phase_timeout: Duration::from_secs(1),
+        }
+    }
+
+    /// Run a round on `channel` between the arbiter (seed 100) and every dealer and
+    /// player, holding the given shares of the previous polynomial
+    async fn round(
+        runtime: &Context,
+        oracle: &mut simulated::Oracle,
+        channel: u32,
+        config: &Config,
+        previous: &HashMap<PublicKey, Vec<group::Share>>,
+    ) -> (poly::Public, HashMap<PublicKey, Output>) {
+        let arbiter = Ed25519::from_seed(100).public_key();
+        let mut contributors = config.dealers.clone();
+        contributors.extend(config.players.iter().cloned());
+        contributors.sort();
+        contributors.dedup();
+        let mut handles = Vec::new();
+        for contributor in contributors {
+            let (sender, receiver) = oracle.register(contributor.clone(), channel).await.unwrap();
+            let shares = previous.get(&contributor).cloned().unwrap_or_default();
+            let task = Contributor::new(runtime.clone(), contributor.clone(), arbiter.clone(), config.clone(), shares);
+            handles.push((contributor, runtime.spawn("contributor", task.run(sender, receiver))));
+        }
+        let (sender, receiver) = oracle.register(arbiter, channel).await.unwrap();
+        let public = Arbiter::new(runtime.clone(), config.clone())
+            .run(channel as u64, sender, receiver)
+            .await
+            .unwrap();
+        let mut outputs = HashMap::new();
+        for (contributor, handle) in handles {
+            if let Some(output) = handle.await.unwrap() {
+                outputs.insert(contributor, output);
+            }
+        }
+        (public, outputs)
+    }
+
+    #[test]
+    fn test_threshold() {
+        assert_eq!(config(vec![1; 4]).threshold(), Some(3));
+        assert_eq!(config(vec![1000, 1, 1, 1, 1, 1, 1]).threshold(), None);
+        assert_eq!(config(Vec::new()).threshold(), None);
+        assert_eq!(config(vec![u64::MAX, 1]).threshold(), None);
+
+        // One stake per player
+        let mut mismatched = config(vec![1; 4]);
+        mismatched.weights.pop();
+        assert_eq!(mismatched.threshold(), None);
+        assert_eq!(mismatched.player_indices(), None);
+    }
+
+    #[test]
+    fn test_threshold_matches_dealings() {
+        // Commitments must decode with the threshold the validators expect
+        for weights in [vec![1; 4], vec![2, 1, 1, 1, 1, 1], vec![2, 2, 1, 1, 1, 1, 1, 1, 1, 1], vec![5; 10]] {
+            let config = config(weights);
+            let t = config.threshold().unwrap();
+            let indices = config.player_indices().unwrap();
+            let (_, commitment, shares) =
+                dkg::Dealer::new(&mut StdRng::seed_from_u64(0), None, participants(&indices));
+            assert_eq!(shares.len(), indices.iter().map(|owned| owned.len()).sum::<usize>());
+            assert!(poly::Public::deserialize(&commitment.serialize(), t).is_some());
+        }
+    }
+
+    #[test]
+    fn test_weighted_round() {
+        let (executor, runtime, _) = Executor::default();
+        executor.start(async move {
+            let (network, mut oracle) = simulated::Network::new(
+                runtime.clone(),
+                simulated::Config {
+                    registry: Arc::new(Mutex::new(Registry::default())),
+                    max_size: 1024 * 1024,
+                },
+            );
+            runtime.spawn("network", network.run());
+
+            // The first validator holds 2 of the 7 shares
+            let mut weighted = config(vec![1; 6]);
+            weighted.weights[0] = 2;
+            let mut peers = weighted.players.clone();
+            peers.push(Ed25519::from_seed(100).public_key());
+            for a in peers.iter() {
+                for b in peers.iter().filter(|b| *b != a) {
+                    let link = simulated::Link { latency: 10.0, jitter: 1.0, success_rate: 1.0 };
+                    oracle.add_link(a.clone(), b.clone(), link).await.unwrap();
+                }
+            }
+            let (public, outputs) = round(&runtime, &mut oracle, 0, &weighted, &HashMap::new()).await;
+            assert_eq!(weighted.threshold(), Some(5));
+            assert!(poly::Public::deserialize(&public.serialize(), 5).is_some());
+            let indices = weighted.player_indices().unwrap();
+            assert_eq!(indices[0], vec![0, 6]);
+            for (player, owned) in weighted.players.iter().zip(indices.iter()) {
+                let output = &outputs[player];
+                assert_eq!(output.public.serialize(), public.serialize());
+                let received: Vec<u32> = output.shares.iter().map(|share| share.index).collect();
+                assert_eq!(received, *owned);
+                for share in output.shares.iter() {
+                    assert_eq!(public.evaluate(share.index).value, share.public());
+                }
+            }
+
+            // Resharing deals once per previous share, back to equal stakes
+            let mut reshare = config(vec![1; 6]);
+            reshare.dealer_weights = weighted.weights.clone();
+            reshare.previous = Some(public.clone());
+            let previous: HashMap<_, _> = outputs
+                .into_iter()
+                .map(|(player, output)| (player, output.shares))
+                .collect();
+            let (reshared, outputs) = round(&runtime, &mut oracle, 1, &reshare, &previous).await;
+            assert_eq!(poly::public(&reshared), poly::public(&public));
+            assert_eq!(reshare.threshold(), Some(5));
+            assert!(poly::Public::deserialize(&reshared.serialize(), 5).is_some());
+            for (index, player) in reshare.players.iter().enumerate() {
+                let shares = &outputs[player].shares;
+                assert_eq!(shares.len(), 1);
+                assert_eq!(shares[0].index, index as u32);
+                assert_eq!(reshared.evaluate(shares[0].index).value, shares[0].public());
+            }
+        });
+    }
+}
@@ -0,0 +1,197 @@
+//! Loading and storing validator key material.
+//!
+//! Private keys and shares live in hex files only readable by their owner. Deriving
+//! keys from small integer seeds is only meant for local testing: it is compiled in
+//! with the `insecure-seeds` feature only, and binaries only use it when
+//! `--insecure-seeds` is given.
+
+use commonware_cryptography::{bls12381::primitives::group, Ed25519, PublicKey, Scheme};
+use commonware_utils::{from_hex, hex};
+use rand::rngs::OsRng;
+use std::{fs, io::Write, path::Path};
+use thiserror::Error;
+
+/// Errors that can occur when loading key material.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0} is not well-formed")]
+    Malformed(String),
+    #[error("{0} is accessible by other users, restrict it with `chmod 600`")]
+    InsecurePermissions(String),
+    #[error("seeded keys are not available, build with `--features insecure-seeds`")]
+    SeedsDisabled,
+}
+
+/// Generate a fresh Ed25519 key from the OS randomness.
+pub fn generate_signer() -> Ed25519 {
+    Ed25519::new(&mut OsRng)
+}
+
+/// Write a private key, readable only by the current user.
+pub fn write_signer(path: &Path, signer: &Ed25519) -> Result<(), Error> {
+    write_secret(path, &hex(&signer.private_key()))
+}
+
+/// Read a private key written by [`write_signer`].
+pub fn read_signer(path: &Path) -> Result<Ed25519, Error> {
+    let raw = read_secret(path)?;
+    let private_key = from_hex(&raw).ok_or_else(|| Error::Malformed(path.display().to_string()))?;
+    <Ed25519 as Scheme>::from(private_key.into()).ok_or_else(|| Error::Malformed(path.display().to_string()))
+}
+
+/// Write the BLS shares of a validator, one per line, readable only by the current user.
+pub fn write_shares(path: &Path, shares: &[group::Share]) -> Result<(), Error> {
+    let lines: Vec<String> = shares.iter().map(|share| hex(&share.serialize())).collect();
+    write_secret(path, &lines.join("\n"))
+}
+
+/// Read the BLS shares written by [`write_shares`].
+pub fn read_shares(path: &Path) -> Result<Vec<group::Share>, Error> {
+    let raw = read_secret(path)?;
+    let shares = raw
+        .lines()
+        .map(|line| from_hex(line.trim()).and_then(|share| group::Share::deserialize(&share)))
+        .collect::<Option<Vec<_>>>()
+        .filter(|shares| !shares.is_empty())
+        .ok_or_else(|| Error::Malformed(path.display().to_string()))?;
+    Ok(shares)
+}
+
+/// Parse a hex-encoded Ed25519 public key.
+pub fn parse_public_key(value: &str) -> Result<PublicKey, Error> {
+    let public_key: PublicKey = from_hex(value)
+        .ok_or_else(|| Error::Malformed(value.to_string()))?
+        .into();
+    if !Ed25519::validate(&public_key) {
+        return Err(Error::Malformed(value.to_string()));
+    }
+    Ok(public_key)
+}
+
+/// Parse a participant given either by public key or, for local testing, by seed.
+pub fn parse_participant(value: &str, insecure_seeds: bool) -> Result<PublicKey, Error> {
+    if insecure_seeds {
+        return Ok(seeded(value)?.public_key());
+    }
+    parse_public_key(value)
+}
+
+/// Load our signer from a key file or, for local testing, from a seed.
+pub fn load_signer(value: &str, insecure_seeds: bool) -> Result<Ed25519, Error> {
+    if insecure_seeds {
+        return seeded(value);
+    }
+    read_signer(Path::new(value))
+}
+
+/// Derive a key from a small integer seed.
+///
+/// Anyone can derive the same key, so this must never be used outside of tests and
+/// local clusters.
+#[cfg(any(test, feature = "insecure-seeds"))]
+pub fn insecure_signer(seed: u64) -> Ed25519 {
+    Ed25519::from_seed(seed)
+}
+
+/// Derive the key of a seed given in place of a key, if seeded keys are compiled in.
+#[cfg(any(test, feature = "insecure-seeds"))]
+fn seeded(value: &str) -> Result<Ed25519, Error> {
+    let seed = value
+        .parse::<u64>()
+        .map_err(|_| Error::Malformed(value.to_string()))?;
+    Ok(insecure_signer(seed))
+}
+
+#[cfg(not(any(test, feature = "insecure-seeds")))]
+fn seeded(_: &str) -> Result<Ed25519, Error> {
+    Err(Error::SeedsDisabled)
+}
+
+fn write_secret(path: &Path, contents: &str) -> Result<(), Error> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    Ok(())
+}
+
+fn read_secret(path: &Path) -> Result<String, Error> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(path)?.permissions().mode();
+        if mode & 0o077 != 0 {
+            return Err(Error::InsecurePermissions(path.display().to_string()));
+        }
+    }
+    Ok(fs::read_to_string(path)?.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commonware_cryptography::bls12381::dkg::ops;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("little-dipper-keys-{}-{}", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_signer_roundtrip() {
+        let path = temp_path("signer");
+        let signer = generate_signer();
+        write_signer(&path, &signer).unwrap();
+        assert_eq!(read_signer(&path).unwrap().public_key(), signer.public_key());
+
+        // Existing keys are never overwritten
+        assert!(matches!(write_signer(&path, &generate_signer()), Err(Error::Io(_))));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_shares_roundtrip() {
+        let path = temp_path("shares");
+        let (_, shares) = ops::generate_shares(&mut StdRng::seed_from_u64(0), None, 5, 4);
+        write_shares(&path, &shares[1..3]).unwrap();
+        let read = read_shares(&path).unwrap();
+        assert_eq!(read.iter().map(|share| share.index).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(read[1].public(), shares[2].public());
+        fs::remove_file(&path).unwrap();
+
+        // A file without shares is malformed
+        write_shares(&path, &[]).unwrap();
+        assert!(matches!(read_shares(&path), Err(Error::Malformed(_))));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_insecure_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = temp_path("permissions");
+        write_signer(&path, &generate_signer()).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(matches!(read_signer(&path), Err(Error::InsecurePermissions(_))));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_parse_participant() {
+        let public_key = insecure_signer(1).public_key();
+        assert_eq!(parse_participant(&hex(&public_key), false).unwrap(), public_key);
+        assert_eq!(parse_participant("1", true).unwrap(), public_key);
+        assert!(matches!(parse_participant("1", false), Err(Error::Malformed(_))));
+        assert!(matches!(parse_participant("not a seed", true), Err(Error::Malformed(_))));
+    }
+}
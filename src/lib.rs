@@ -3,12 +3,28 @@ pub mod wire {
     include!(concat!(env!("OUT_DIR"), "/wire.rs"));
 }
 #[doc(hidden)]
+pub mod api;
+#[doc(hidden)]
 pub mod application;
 #[doc(hidden)]
+pub mod client;
+#[doc(hidden)]
+pub mod config;
+#[doc(hidden)]
+pub mod dkg;
+#[doc(hidden)]
+pub mod keys;
+#[doc(hidden)]
+pub mod light_client;
+#[doc(hidden)]
 pub const APPLICATION_NAMESPACE: &[u8] = b"_LITTLEDIPPER_CHAT";
 #[doc(hidden)]
 pub const P2P_SUFFIX: &[u8] = b"_P2P";
 #[doc(hidden)]
 pub const CONSENSUS_SUFFIX: &[u8] = b"_CONSENSUS";
 #[doc(hidden)]
+pub const DKG_SUFFIX: &[u8] = b"_DKG";
+#[doc(hidden)]
+pub const TRANSACTION_SUFFIX: &[u8] = b"_TX";
+#[doc(hidden)]
 pub const APPLICATION_P2P_NAMESPACE: &[u8] = b"_LITTLEDIPPER_CHAT_P2P";
@@ -0,0 +1,225 @@
+//! Follow finality without running consensus.
+//!
+//! A light client only needs the group public key (printed by `dealer` or `dkg`). It
+//! pulls threshold finalization certificates from any validator's api, verifies them
+//! exactly as consensus does and tracks the finalized head along with the roots of
+//! recent views, against which chat inclusion proofs and receipts are checked. Nothing
+//! served by the validator is trusted.
+//!
+//! Resharing keeps the group public key, so a light client keeps following across
+//! validator set changes. Switching to a freshly dealt group requires a new client.
+
+use crate::api::Finalization;
+use crate::application::mini_block::ChatProof;
+use crate::application::receipt::{self, Receipt};
+use crate::{APPLICATION_NAMESPACE, CONSENSUS_SUFFIX};
+use commonware_consensus::threshold_simplex::{Prover, View};
+use commonware_cryptography::{bls12381::primitives::group, Digest, Sha256};
+use commonware_utils::union;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Errors that can occur while following a validator.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("finalization certificate is invalid")]
+    InvalidCertificate,
+    #[error("certificate finalizes view {0}, expected view {1}")]
+    ViewMismatch(View, View),
+    #[error("root of view {0} is not known, sync it first")]
+    UnknownView(View),
+    #[error("chat is not included in the finalized root")]
+    NotIncluded,
+    #[error("invalid receipt: {0}")]
+    Receipt(#[from] receipt::Error),
+}
+
+/// Verifies finalization certificates and tracks the finalized chain.
+pub struct LightClient {
+    prover: Prover<Sha256>,
+    hasher: Sha256,
+    /// view -> finalized root of recently verified views
+    roots: BTreeMap<View, Digest>,
+    retained: usize,
+}
+
+impl LightClient {
+    /// Create a light client for the group with the given public key, remembering the
+    /// roots of the last `retained` verified views.
+    pub fn new(public: group::Public, retained: usize) -> Self {
+        let namespace = union(APPLICATION_NAMESPACE, CONSENSUS_SUFFIX);
+        Self {
+            prover: Prover::new(public, &namespace),
+            hasher: Sha256::default(),
+            roots: BTreeMap::new(),
+            retained,
+        }
+    }
+
+    /// Latest verified finalized view and its root.
+    pub fn head(&self) -> Option<(View, &Digest)> {
+        self.roots.last_key_value().map(|(view, root)| (*view, root))
+    }
+
+    /// Finalized root of a tracked view.
+    pub fn root(&self, view: View) -> Option<&Digest> {
+        self.roots.get(&view)
+    }
+
+    /// Verify a finalization certificate and track the root it finalizes.
+    pub fn apply(&mut self, certificate: &[u8]) -> Result<(View, Digest), Error> {
+        let (view, _, root, _, _) = self
+            .prover
+            .deserialize_finalization(certificate.to_vec().into())
+            .ok_or(Error::InvalidCertificate)?;
+        self.track(view, root.clone());
+        Ok((view, root))
+    }
+
+    /// Remember the root of `view`, evicting the lowest tracked views first so the
+    /// view just verified is kept even when it is older than all of them.
+    fn track(&mut self, view: View, root: Digest) {
+        if !self.roots.contains_key(&view) {
+            while self.roots.len() >= self.retained.max(1) {
+                self.roots.pop_first();
+            }
+        }
+        self.roots.insert(view, root);
+    }
+
+    /// Check `chat` was finalized at `view`, whose root must already be tracked, in a
+    /// mini-block signed by its creator.
+    pub fn verify_chat(&mut self, view: View, chat: &[u8], proof: &ChatProof) -> Result<(), Error> {
+        let root = self.roots.get(&view).ok_or(Error::UnknownView(view))?;
+        if proof.header.sig.is_empty() || !proof.header.verify() || !proof.verify(&mut self.hasher, chat, root) {
+            return Err(Error::NotIncluded);
+        }
+        Ok(())
+    }
+
+    /// Check a receipt proves `chat` was finalized, tracking the view it finalizes.
+    pub fn verify_receipt(&mut self, receipt: &Receipt, chat: &[u8]) -> Result<(), Error> {
+        receipt.verify(&self.prover, &mut self.hasher, chat)?;
+        self.apply(&receipt.finalization)?;
+        Ok(())
+    }
+}
+
+/// Pulls finalization certificates from a validator's api into a [`LightClient`].
+pub struct Follower {
+    client: reqwest::Client,
+    /// Base url of the api, e.g. `http://127.0.0.1:4001`.
+    api: String,
+    light_client: LightClient,
+}
+
+impl Follower {
+    pub fn new(api: &str, light_client: LightClient) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api: api.trim_end_matches('/').to_string(),
+            light_client,
+        }
+    }
+
+    pub fn light_client(&mut self) -> &mut LightClient {
+        &mut self.light_client
+    }
+
+    /// Verify the latest finalization, returning the new head if it advanced.
+    pub async fn sync(&mut self) -> Result<Option<(View, Digest)>, Error> {
+        let finalization = self.get("latest").await?;
+        if let Some((head, _)) = self.light_client.head() {
+            if finalization.view <= head {
+                return Ok(None);
+            }
+        }
+        self.light_client.apply(&finalization.certificate).map(Some)
+    }
+
+    /// Verify the finalization of a specific view (e.g. before checking a proof for it).
+    pub async fn fetch(&mut self, view: View) -> Result<Digest, Error> {
+        let finalization = self.get(&view.to_string()).await?;
+        let (finalized, root) = self.light_client.apply(&finalization.certificate)?;
+        if finalized != view {
+            return Err(Error::ViewMismatch(finalized, view));
+        }
+        Ok(root)
+    }
+
+    async fn get(&self, view: &str) -> Result<Finalization, Error> {
+        Ok(self
+            .client
+            .get(format!("{}/finalizations/{}", self.api, view))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::application::mini_block::testing;
+    use commonware_cryptography::bls12381::{dkg::ops, primitives::poly};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn light_client(retained: usize) -> LightClient {
+        let (identity, _) = ops::generate_shares(&mut StdRng::seed_from_u64(0), None, 4, 3);
+        LightClient::new(poly::public(&identity), retained)
+    }
+
+    #[test]
+    fn test_verify_chat() {
+        let mut hasher = Sha256::default();
+        let mut light_client = light_client(2);
+        let mut proto_block = testing::proto_block(2, 2);
+        let proof = proto_block.prove_chat(&mut hasher, 1, 0).unwrap();
+        assert!(matches!(light_client.verify_chat(1, &[1, 0], &proof), Err(Error::UnknownView(1))));
+
+        // Against a tracked root, only the proven chat is included
+        light_client.track(1, proto_block.root(&mut hasher));
+        assert!(light_client.verify_chat(1, &[1, 0], &proof).is_ok());
+        assert!(matches!(light_client.verify_chat(1, &[1, 1], &proof), Err(Error::NotIncluded)));
+
+        // Mini-blocks without their creator's signature are not trusted
+        proto_block.mini_blocks[1].sig.clear();
+        light_client.track(1, proto_block.root(&mut hasher));
+        let proof = proto_block.prove_chat(&mut hasher, 1, 0).unwrap();
+        assert!(matches!(light_client.verify_chat(1, &[1, 0], &proof), Err(Error::NotIncluded)));
+        let receipt = Receipt::new(&mut hasher, 1, Vec::new().into(), &proto_block, 1, 0).unwrap();
+        assert!(matches!(
+            light_client.verify_receipt(&receipt, &[1, 0]),
+            Err(Error::Receipt(receipt::Error::Unsigned))
+        ));
+    }
+
+    #[test]
+    fn test_retained_views() {
+        let mut light_client = light_client(2);
+        light_client.track(5, vec![5; 32].into());
+        light_client.track(6, vec![6; 32].into());
+        light_client.track(7, vec![7; 32].into());
+        assert_eq!(light_client.roots.keys().collect::<Vec<_>>(), vec![&6, &7]);
+
+        // Fetching an old view keeps it, along with the head
+        light_client.track(2, vec![2; 32].into());
+        assert!(light_client.root(2).is_some());
+        assert_eq!(light_client.head().map(|(view, _)| view), Some(7));
+
+        // Re-verifying a tracked view evicts nothing
+        light_client.track(7, vec![7; 32].into());
+        assert_eq!(light_client.roots.len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_certificate() {
+        let mut light_client = light_client(2);
+        assert!(matches!(light_client.apply(&[0; 100]), Err(Error::InvalidCertificate)));
+        assert!(light_client.head().is_none());
+    }
+}